@@ -0,0 +1,4 @@
+ALTER TABLE documents ADD COLUMN mime_type TEXT;
+
+-- Set when a document (e.g. a scanned PDF) yields no extractable text
+ALTER TABLE documents ADD COLUMN no_extractable_text BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,3 @@
+-- Only filled in when documents are fetched in structured mode
+ALTER TABLE document_revisions ADD COLUMN image_count INTEGER;
+ALTER TABLE document_revisions ADD COLUMN table_count INTEGER;
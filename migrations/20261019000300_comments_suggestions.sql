@@ -0,0 +1,31 @@
+CREATE TABLE IF NOT EXISTS document_comments (
+	id INTEGER PRIMARY KEY AUTOINCREMENT,
+	document_id INTEGER NOT NULL,
+	comment_id TEXT NOT NULL,
+	parent_comment_id TEXT, -- set for replies
+	author_name TEXT NOT NULL,
+	author_email TEXT,
+	content TEXT NOT NULL,
+	quoted_text TEXT,
+	created_time TEXT NOT NULL,
+	modified_time TEXT NOT NULL,
+	resolved BOOLEAN NOT NULL DEFAULT FALSE,
+	deleted BOOLEAN NOT NULL DEFAULT FALSE,
+	FOREIGN KEY(document_id) REFERENCES documents(id) ON DELETE CASCADE
+);
+
+CREATE UNIQUE INDEX idx_document_comments_unique ON document_comments(document_id, comment_id);
+
+-- Suggestions have no author in the Docs API; they are tracked by when the poller saw them
+CREATE TABLE IF NOT EXISTS document_suggestions (
+	id INTEGER PRIMARY KEY AUTOINCREMENT,
+	document_id INTEGER NOT NULL,
+	suggestion_id TEXT NOT NULL,
+	kind TEXT NOT NULL, -- "insertion" or "deletion"
+	text TEXT NOT NULL,
+	first_seen TEXT NOT NULL,
+	resolved_at TEXT, -- set once the suggestion is accepted or rejected
+	FOREIGN KEY(document_id) REFERENCES documents(id) ON DELETE CASCADE
+);
+
+CREATE UNIQUE INDEX idx_document_suggestions_unique ON document_suggestions(document_id, suggestion_id, kind);
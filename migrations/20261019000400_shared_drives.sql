@@ -0,0 +1,12 @@
+-- Shared drives seen by the Docwatch account; only enabled drives are polled
+CREATE TABLE IF NOT EXISTS shared_drives (
+	drive_id TEXT PRIMARY KEY NOT NULL,
+	name TEXT NOT NULL,
+	enabled BOOLEAN NOT NULL DEFAULT FALSE,
+	last_polled TEXT,
+	last_error TEXT,
+	created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+ALTER TABLE documents ADD COLUMN drive_id TEXT;
+ALTER TABLE documents ADD COLUMN drive_name TEXT;
@@ -0,0 +1,5 @@
+-- Drive folder mirrored by this row: set on the folder a user bound, and on the subfolders created for it
+ALTER TABLE user_documents ADD COLUMN drive_folder_id TEXT;
+
+-- Bound root folder that created this row; NULL for rows the user created themselves
+ALTER TABLE user_documents ADD COLUMN mirror_root_id TEXT REFERENCES user_documents(id) ON DELETE CASCADE;
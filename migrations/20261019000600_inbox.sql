@@ -0,0 +1,12 @@
+-- When the user last looked at their inbox; documents discovered later are "new"
+ALTER TABLE users ADD COLUMN inbox_seen_at DATETIME;
+
+-- Documents a user dismissed from their inbox
+CREATE TABLE IF NOT EXISTS inbox_ignored (
+	user_id INTEGER NOT NULL,
+	document_id INTEGER NOT NULL,
+	ignored_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+	PRIMARY KEY (user_id, document_id),
+	FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+	FOREIGN KEY (document_id) REFERENCES documents(id) ON DELETE CASCADE
+);
@@ -0,0 +1,15 @@
+ALTER TABLE documents ADD COLUMN owner_email TEXT;
+
+-- Per-user rules that file newly discovered documents into a folder.
+-- Patterns are case-insensitive globs; a NULL pattern matches everything.
+CREATE TABLE IF NOT EXISTS watch_rules (
+	id INTEGER PRIMARY KEY AUTOINCREMENT,
+	user_id INTEGER NOT NULL,
+	name_pattern TEXT,
+	owner_pattern TEXT, -- matched against the owner's email and display name
+	id_parent TEXT, -- target folder, NULL for the top level
+	enabled BOOLEAN NOT NULL DEFAULT TRUE,
+	created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+	FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+	FOREIGN KEY (id_parent) REFERENCES user_documents(id) ON DELETE CASCADE
+);
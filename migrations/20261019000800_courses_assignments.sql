@@ -0,0 +1,30 @@
+CREATE TABLE IF NOT EXISTS courses (
+	id INTEGER PRIMARY KEY AUTOINCREMENT,
+	user_id INTEGER NOT NULL,
+	name TEXT NOT NULL,
+	created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+	FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+-- Times are RFC 3339 UTC with milliseconds, the same format Drive uses for revision_time,
+-- so they can be compared as strings
+CREATE TABLE IF NOT EXISTS assignments (
+	id INTEGER PRIMARY KEY AUTOINCREMENT,
+	course_id INTEGER NOT NULL,
+	name TEXT NOT NULL,
+	open_time TEXT,
+	due_time TEXT NOT NULL,
+	created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+	FOREIGN KEY (course_id) REFERENCES courses(id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS assignment_documents (
+	assignment_id INTEGER NOT NULL,
+	document_id INTEGER NOT NULL,
+	PRIMARY KEY (assignment_id, document_id),
+	FOREIGN KEY (assignment_id) REFERENCES assignments(id) ON DELETE CASCADE,
+	FOREIGN KEY (document_id) REFERENCES documents(id) ON DELETE CASCADE
+);
+
+-- Set when the revision was made after the due time of an assignment the document is linked to
+ALTER TABLE document_revisions ADD COLUMN is_late BOOLEAN NOT NULL DEFAULT FALSE;
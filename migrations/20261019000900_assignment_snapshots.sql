@@ -0,0 +1,18 @@
+-- When the poller last fetched or confirmed a document's content (RFC 3339 UTC)
+ALTER TABLE documents ADD COLUMN last_checked TEXT;
+
+-- Set once the snapshots of an assignment have been taken at its due time
+ALTER TABLE assignments ADD COLUMN frozen_at TEXT;
+
+-- The revision each linked document was at when the assignment was due.
+-- revision_id is NULL when the document had no revision before the deadline.
+CREATE TABLE IF NOT EXISTS assignment_snapshots (
+	assignment_id INTEGER NOT NULL,
+	document_id INTEGER NOT NULL,
+	revision_id INTEGER,
+	frozen_at TEXT NOT NULL,
+	PRIMARY KEY (assignment_id, document_id),
+	FOREIGN KEY (assignment_id) REFERENCES assignments(id) ON DELETE CASCADE,
+	FOREIGN KEY (document_id) REFERENCES documents(id) ON DELETE CASCADE,
+	FOREIGN KEY (revision_id) REFERENCES document_revisions(id) ON DELETE SET NULL
+);
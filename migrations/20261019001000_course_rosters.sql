@@ -0,0 +1,14 @@
+-- Students enrolled in a course, imported from CSV.
+-- Documents are matched to students by owner email (stored lowercase).
+CREATE TABLE IF NOT EXISTS roster_students (
+	id INTEGER PRIMARY KEY AUTOINCREMENT,
+	course_id INTEGER NOT NULL,
+	name TEXT NOT NULL,
+	email TEXT NOT NULL,
+	student_id TEXT,
+	created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+	UNIQUE (course_id, email),
+	FOREIGN KEY (course_id) REFERENCES courses(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_documents_owner_email ON documents(owner_email COLLATE NOCASE);
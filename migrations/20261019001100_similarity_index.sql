@@ -0,0 +1,42 @@
+-- Hashes of 7-word shingles, with the revision in which each document first contained them
+CREATE TABLE IF NOT EXISTS shingles (
+	hash INTEGER NOT NULL,
+	document_id INTEGER NOT NULL,
+	revision_id INTEGER NOT NULL,
+	first_seen TEXT NOT NULL,
+	PRIMARY KEY (hash, document_id),
+	FOREIGN KEY (document_id) REFERENCES documents(id) ON DELETE CASCADE
+) WITHOUT ROWID;
+
+CREATE INDEX IF NOT EXISTS idx_shingles_document ON shingles(document_id);
+
+-- MinHash signature of each document's latest indexed revision
+CREATE TABLE IF NOT EXISTS document_minhash (
+	document_id INTEGER PRIMARY KEY,
+	revision_id INTEGER NOT NULL,
+	signature BLOB NOT NULL,
+	FOREIGN KEY (document_id) REFERENCES documents(id) ON DELETE CASCADE
+);
+
+-- Passages shared between two documents, recorded on the revision that introduced them.
+-- Offsets are character offsets into the respective revision's content.
+CREATE TABLE IF NOT EXISTS similarity_matches (
+	id INTEGER PRIMARY KEY AUTOINCREMENT,
+	document_id INTEGER NOT NULL,
+	revision_id INTEGER NOT NULL,
+	start_offset INTEGER NOT NULL,
+	end_offset INTEGER NOT NULL,
+	other_document_id INTEGER NOT NULL,
+	other_revision_id INTEGER NOT NULL,
+	other_start_offset INTEGER,
+	other_end_offset INTEGER,
+	words INTEGER NOT NULL,
+	source_document_id INTEGER NOT NULL, -- whichever document had the text first
+	created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+	FOREIGN KEY (document_id) REFERENCES documents(id) ON DELETE CASCADE,
+	FOREIGN KEY (revision_id) REFERENCES document_revisions(id) ON DELETE CASCADE,
+	FOREIGN KEY (other_document_id) REFERENCES documents(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_similarity_document ON similarity_matches(document_id);
+CREATE INDEX IF NOT EXISTS idx_similarity_other_document ON similarity_matches(other_document_id);
@@ -0,0 +1,33 @@
+-- Reference texts (prompts, readings, earlier submissions) that new writing is checked against
+CREATE TABLE IF NOT EXISTS reference_texts (
+	id INTEGER PRIMARY KEY AUTOINCREMENT,
+	user_id INTEGER NOT NULL,
+	title TEXT NOT NULL,
+	kind TEXT NOT NULL,
+	file_name TEXT,
+	content TEXT NOT NULL,
+	created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+	FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS reference_shingles (
+	hash INTEGER NOT NULL,
+	reference_id INTEGER NOT NULL,
+	PRIMARY KEY (hash, reference_id),
+	FOREIGN KEY (reference_id) REFERENCES reference_texts(id) ON DELETE CASCADE
+) WITHOUT ROWID;
+
+-- Runs of added text in a revision's diff that also appear in a reference text.
+-- diff_start and diff_end are inclusive indices into the revision's diff.
+CREATE TABLE IF NOT EXISTS corpus_matches (
+	id INTEGER PRIMARY KEY AUTOINCREMENT,
+	revision_id INTEGER NOT NULL,
+	reference_id INTEGER NOT NULL,
+	diff_start INTEGER NOT NULL,
+	diff_end INTEGER NOT NULL,
+	words INTEGER NOT NULL,
+	FOREIGN KEY (revision_id) REFERENCES document_revisions(id) ON DELETE CASCADE,
+	FOREIGN KEY (reference_id) REFERENCES reference_texts(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_corpus_matches_revision ON corpus_matches(revision_id);
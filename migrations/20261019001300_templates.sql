@@ -0,0 +1,17 @@
+-- Instructor-provided starting text for an assignment or a folder of documents
+CREATE TABLE IF NOT EXISTS templates (
+	id INTEGER PRIMARY KEY AUTOINCREMENT,
+	user_id INTEGER NOT NULL,
+	assignment_id INTEGER,
+	folder_id TEXT,
+	title TEXT NOT NULL,
+	content TEXT NOT NULL,
+	created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+	CHECK ((assignment_id IS NULL) != (folder_id IS NULL)),
+	FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+	FOREIGN KEY (assignment_id) REFERENCES assignments(id) ON DELETE CASCADE,
+	FOREIGN KEY (folder_id) REFERENCES user_documents(id) ON DELETE CASCADE
+);
+
+-- Added words of a revision that came from a template; added_words still includes them
+ALTER TABLE document_revisions ADD COLUMN template_words INTEGER NOT NULL DEFAULT 0;
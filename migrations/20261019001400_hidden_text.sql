@@ -0,0 +1,13 @@
+-- Invisible characters, homoglyphs and hidden formatting found in a revision's text.
+-- Every revision stores all of its findings; is_new marks those its previous revision didn't have.
+CREATE TABLE hidden_text_findings (
+	id INTEGER PRIMARY KEY AUTOINCREMENT,
+	revision_id INTEGER NOT NULL REFERENCES document_revisions(id) ON DELETE CASCADE,
+	kind TEXT NOT NULL,          -- zero_width, bidi_control, unusual_space, homoglyph, hidden_formatting
+	char_offset INTEGER,         -- into the revision content; NULL for formatting findings
+	matched TEXT NOT NULL,       -- the offending text, with invisible characters spelled out
+	context TEXT NOT NULL,
+	is_new BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+CREATE INDEX idx_hidden_text_findings_revision ON hidden_text_findings(revision_id);
@@ -0,0 +1,20 @@
+-- Results of pluggable revision analyzers that don't need a table of their own.
+-- detail is a JSON object whose shape depends on the analyzer and kind.
+CREATE TABLE IF NOT EXISTS revision_findings (
+	id INTEGER PRIMARY KEY AUTOINCREMENT,
+	revision_id INTEGER NOT NULL,
+	analyzer TEXT NOT NULL,
+	kind TEXT NOT NULL,
+	score REAL,
+	detail TEXT NOT NULL DEFAULT '{}',
+	created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+	FOREIGN KEY (revision_id) REFERENCES document_revisions(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_revision_findings_revision ON revision_findings(revision_id);
+
+-- Analyzers without a row here use their built-in default
+CREATE TABLE IF NOT EXISTS analyzer_settings (
+	name TEXT PRIMARY KEY,
+	enabled BOOLEAN NOT NULL
+);
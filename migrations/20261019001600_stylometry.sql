@@ -0,0 +1,18 @@
+-- Style features of the text a revision added, excluding template text.
+-- author identifies the student across documents: the owner's email, or username when there is none.
+CREATE TABLE IF NOT EXISTS revision_style (
+	revision_id INTEGER PRIMARY KEY,
+	document_id INTEGER NOT NULL,
+	author TEXT NOT NULL,
+	revision_time TEXT NOT NULL,
+	words INTEGER NOT NULL,
+	avg_sentence_length REAL NOT NULL,
+	vocabulary_richness REAL NOT NULL,
+	readability_grade REAL NOT NULL,
+	function_words TEXT NOT NULL, -- JSON array of relative frequencies, in stylometry::FUNCTION_WORDS order
+	FOREIGN KEY (revision_id) REFERENCES document_revisions(id) ON DELETE CASCADE,
+	FOREIGN KEY (document_id) REFERENCES documents(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_revision_style_author ON revision_style(author);
+CREATE INDEX IF NOT EXISTS idx_revision_style_document ON revision_style(document_id);
@@ -0,0 +1,16 @@
+-- How much of the existing text a revision rewrote in place, sentence by sentence
+CREATE TABLE IF NOT EXISTS revision_rewrites (
+	revision_id INTEGER PRIMARY KEY,
+	sentences_before INTEGER NOT NULL,
+	unchanged INTEGER NOT NULL,
+	edited INTEGER NOT NULL,         -- paired with a new sentence, small changes
+	rewritten INTEGER NOT NULL,      -- paired with a new sentence, most words changed
+	deleted INTEGER NOT NULL,
+	added INTEGER NOT NULL,
+	rewritten_share REAL NOT NULL,   -- rewritten / sentences_before
+	mean_edit_ratio REAL,            -- word edit distance over length, averaged over paired sentences
+	longest_rewrite_run INTEGER NOT NULL,
+	word_change_ratio REAL NOT NULL, -- net change in word count over the words before
+	is_event BOOLEAN NOT NULL DEFAULT FALSE,
+	FOREIGN KEY (revision_id) REFERENCES document_revisions(id) ON DELETE CASCADE
+);
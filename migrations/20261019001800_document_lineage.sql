@@ -0,0 +1,6 @@
+-- Student words in the first version Docwatch saw (template text excluded)
+ALTER TABLE documents ADD COLUMN baseline_words INTEGER;
+
+-- The watched document this one appears to be a copy of, judged by its first version's text
+ALTER TABLE documents ADD COLUMN parent_document_id INTEGER REFERENCES documents(id) ON DELETE SET NULL;
+ALTER TABLE documents ADD COLUMN parent_similarity REAL;
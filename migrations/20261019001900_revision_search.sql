@@ -0,0 +1,15 @@
+-- Full-text index over revision content. The text itself stays in document_revisions;
+-- the poller adds each new revision, and deleted revisions are dropped by the trigger below.
+CREATE VIRTUAL TABLE IF NOT EXISTS revision_fts USING fts5(
+	content,
+	content = 'document_revisions',
+	content_rowid = 'id',
+	tokenize = 'unicode61 remove_diacritics 2'
+);
+
+CREATE TRIGGER IF NOT EXISTS revision_fts_delete AFTER DELETE ON document_revisions BEGIN
+	INSERT INTO revision_fts (revision_fts, rowid, content) VALUES ('delete', old.id, old.content);
+END;
+
+-- Index the revisions stored before search existed
+INSERT INTO revision_fts (revision_fts) VALUES ('rebuild');
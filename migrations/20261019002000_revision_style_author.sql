@@ -0,0 +1,4 @@
+-- Students are now matched by joining revision_style to documents on the current owner email,
+-- so samples recorded before the email was known aren't lost and display names can't collide
+DROP INDEX IF EXISTS idx_revision_style_author;
+ALTER TABLE revision_style DROP COLUMN author;
@@ -0,0 +1,2 @@
+-- Rewrite events are read from revision_rewrites; drop the duplicate findings earlier runs stored
+DELETE FROM revision_findings WHERE analyzer = 'rewrite';
@@ -0,0 +1,3 @@
+-- Headings, list items and tabs of structured fetches, as JSON [{line, kind, level, title?}].
+-- Their markers used to be written into the content; revisions stored that way keep them.
+ALTER TABLE document_revisions ADD COLUMN outline TEXT;
@@ -0,0 +1,595 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use chrono::DateTime;
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::SqlitePool;
+
+use crate::corpus::{added_runs, check_revision};
+use crate::docs_structure::HiddenRun;
+use crate::hidden_text::{formatting_findings, record_findings, scan_text};
+use crate::lineage::{baseline_words, find_parent, record_lineage};
+use crate::poller::OwnedWordChange;
+use crate::process_report::LARGE_BASELINE_WORDS;
+use crate::rewrites::{record_rewrite, rewrite_metrics};
+use crate::similarity::index_revision;
+use crate::state::AppState;
+use crate::stylometry::{added_student_text, record_style, style_features};
+
+// Everything an analyzer gets about one newly stored revision
+pub struct RevisionContext<'a> {
+	pub document_id: i64,
+	pub revision_id: i64,
+	pub revision_time: &'a str,
+	pub previous_time: Option<&'a str>, // None for a document's first revision
+	pub old_content: &'a str,           // empty for a document's first revision
+	pub new_content: &'a str,
+	pub diff: &'a [OwnedWordChange],    // empty for a baseline revision
+	pub hidden_runs: &'a [HiddenRun],   // only known for live structured fetches, never on re-runs
+}
+
+impl RevisionContext<'_> {
+	// Seconds since the previous revision, if there was one and both times parse
+	pub fn seconds_since_previous(&self) -> Option<i64> {
+		let before = DateTime::parse_from_rfc3339(self.previous_time?).ok()?;
+		let after = DateTime::parse_from_rfc3339(self.revision_time).ok()?;
+		Some((after - before).num_seconds())
+	}
+}
+
+// A result stored in the generic revision_findings table
+#[derive(Debug, Serialize)]
+pub struct RevisionFinding {
+	pub kind: String,
+	pub score: Option<f64>,
+	pub detail: Value,
+}
+
+#[async_trait]
+pub trait RevisionAnalyzer: Send + Sync {
+	fn name(&self) -> &'static str;
+	fn description(&self) -> &'static str;
+
+	// Whether the analyzer runs when an admin hasn't set it either way
+	fn enabled_by_default(&self) -> bool {
+		true
+	}
+
+	// Analyzers whose stored results this one reads; it can't be enabled without them
+	fn requires(&self) -> &'static [&'static str] {
+		&[]
+	}
+
+	// Analyzers with their own tables write to them here and return nothing
+	async fn analyze(&self, pool: &SqlitePool, ctx: &RevisionContext<'_>) -> Result<Vec<RevisionFinding>, sqlx::Error>;
+
+	// Drops what earlier runs stored in the analyzer's own tables for a document,
+	// before its revisions are analyzed again from the first one.
+	// A rerun resets every selected document before replaying any revision.
+	async fn reset(&self, _pool: &SqlitePool, _document_id: i64) -> Result<(), sqlx::Error> {
+		Ok(())
+	}
+}
+
+// Every analyzer, in the order they run
+pub fn registry() -> Vec<Box<dyn RevisionAnalyzer>> {
+	vec![
+		Box::new(SimilarityAnalyzer),
+		Box::new(CorpusAnalyzer),
+		Box::new(HiddenTextAnalyzer),
+		Box::new(PasteAnalyzer),
+		Box::new(StyleAnalyzer),
+		Box::new(RewriteAnalyzer),
+		Box::new(LineageAnalyzer),
+	]
+}
+
+// Words in one uninterrupted insertion that suggest it was pasted rather than typed
+const PASTE_MIN_WORDS: usize = 100;
+
+struct SimilarityAnalyzer;
+
+#[async_trait]
+impl RevisionAnalyzer for SimilarityAnalyzer {
+	fn name(&self) -> &'static str {
+		"similarity"
+	}
+
+	fn description(&self) -> &'static str {
+		"Indexes shingles and records passages shared with other documents"
+	}
+
+	async fn analyze(&self, pool: &SqlitePool, ctx: &RevisionContext<'_>) -> Result<Vec<RevisionFinding>, sqlx::Error> {
+		index_revision(pool, ctx.document_id, ctx.revision_id, ctx.revision_time, ctx.new_content).await?;
+		Ok(Vec::new())
+	}
+
+	async fn reset(&self, pool: &SqlitePool, document_id: i64) -> Result<(), sqlx::Error> {
+		sqlx::query!("DELETE FROM shingles WHERE document_id = ?", document_id).execute(pool).await?;
+		sqlx::query!("DELETE FROM document_minhash WHERE document_id = ?", document_id).execute(pool).await?;
+		// A passage is stored once, on whichever document was indexed second, so drop both directions
+		sqlx::query!(
+			"DELETE FROM similarity_matches WHERE document_id = ? OR other_document_id = ?",
+			document_id,
+			document_id
+		)
+		.execute(pool)
+		.await?;
+		Ok(())
+	}
+}
+
+struct CorpusAnalyzer;
+
+#[async_trait]
+impl RevisionAnalyzer for CorpusAnalyzer {
+	fn name(&self) -> &'static str {
+		"corpus"
+	}
+
+	fn description(&self) -> &'static str {
+		"Marks added passages that also occur in a reference text"
+	}
+
+	async fn analyze(&self, pool: &SqlitePool, ctx: &RevisionContext<'_>) -> Result<Vec<RevisionFinding>, sqlx::Error> {
+		check_revision(pool, ctx.revision_id, ctx.new_content, ctx.diff).await?;
+		Ok(Vec::new())
+	}
+
+	async fn reset(&self, pool: &SqlitePool, document_id: i64) -> Result<(), sqlx::Error> {
+		sqlx::query!(
+			"DELETE FROM corpus_matches WHERE revision_id IN (SELECT id FROM document_revisions WHERE document_id = ?)",
+			document_id
+		)
+		.execute(pool)
+		.await?;
+		Ok(())
+	}
+}
+
+struct HiddenTextAnalyzer;
+
+#[async_trait]
+impl RevisionAnalyzer for HiddenTextAnalyzer {
+	fn name(&self) -> &'static str {
+		"hidden_text"
+	}
+
+	fn description(&self) -> &'static str {
+		"Finds invisible characters, mixed-script homoglyphs and text hidden by formatting"
+	}
+
+	async fn analyze(&self, pool: &SqlitePool, ctx: &RevisionContext<'_>) -> Result<Vec<RevisionFinding>, sqlx::Error> {
+		let mut findings = scan_text(ctx.new_content);
+		findings.extend(formatting_findings(ctx.hidden_runs));
+		record_findings(pool, ctx.document_id, ctx.revision_id, &findings).await?;
+		Ok(Vec::new())
+	}
+
+	// Formatting can't be recovered from stored content, so those findings are kept
+	async fn reset(&self, pool: &SqlitePool, document_id: i64) -> Result<(), sqlx::Error> {
+		sqlx::query!(
+			"DELETE FROM hidden_text_findings
+			 WHERE kind != 'hidden_formatting'
+				AND revision_id IN (SELECT id FROM document_revisions WHERE document_id = ?)",
+			document_id
+		)
+		.execute(pool)
+		.await?;
+		Ok(())
+	}
+}
+
+struct PasteAnalyzer;
+
+#[async_trait]
+impl RevisionAnalyzer for PasteAnalyzer {
+	fn name(&self) -> &'static str {
+		"paste"
+	}
+
+	fn description(&self) -> &'static str {
+		"Flags long uninterrupted insertions, with how quickly they appeared"
+	}
+
+	async fn analyze(&self, _pool: &SqlitePool, ctx: &RevisionContext<'_>) -> Result<Vec<RevisionFinding>, sqlx::Error> {
+		let seconds = ctx.seconds_since_previous();
+		let old_words = ctx.old_content.split_whitespace().count();
+
+		Ok(added_runs(ctx.diff)
+			.into_iter()
+			.map(|run| run.text.split_whitespace().count())
+			.filter(|&words| words >= PASTE_MIN_WORDS)
+			.map(|words| RevisionFinding {
+				kind: "long_insertion".to_string(),
+				score: Some(words as f64),
+				detail: serde_json::json!({
+					"words": words,
+					"seconds_since_previous": seconds,
+					"words_before": old_words,
+				}),
+			})
+			.collect())
+	}
+}
+
+struct StyleAnalyzer;
+
+#[async_trait]
+impl RevisionAnalyzer for StyleAnalyzer {
+	fn name(&self) -> &'static str {
+		"stylometry"
+	}
+
+	fn description(&self) -> &'static str {
+		"Measures sentence length, vocabulary, function words and readability of added text"
+	}
+
+	async fn analyze(&self, pool: &SqlitePool, ctx: &RevisionContext<'_>) -> Result<Vec<RevisionFinding>, sqlx::Error> {
+		let added = added_student_text(pool, ctx.document_id, ctx.new_content, ctx.diff).await?;
+		if let Some(features) = style_features(&added) {
+			record_style(pool, ctx.document_id, ctx.revision_id, ctx.revision_time, &features).await?;
+		}
+		Ok(Vec::new())
+	}
+
+	async fn reset(&self, pool: &SqlitePool, document_id: i64) -> Result<(), sqlx::Error> {
+		sqlx::query!("DELETE FROM revision_style WHERE document_id = ?", document_id).execute(pool).await?;
+		Ok(())
+	}
+}
+
+struct RewriteAnalyzer;
+
+#[async_trait]
+impl RevisionAnalyzer for RewriteAnalyzer {
+	fn name(&self) -> &'static str {
+		"rewrite"
+	}
+
+	fn description(&self) -> &'static str {
+		"Measures how many existing sentences were rewritten in place, as paraphrasing tools do"
+	}
+
+	async fn analyze(&self, pool: &SqlitePool, ctx: &RevisionContext<'_>) -> Result<Vec<RevisionFinding>, sqlx::Error> {
+		// A first revision has nothing to rewrite
+		if ctx.previous_time.is_none() {
+			return Ok(Vec::new());
+		}
+
+		// revision_rewrites.is_event is where rewrite events are read from, so no finding is returned
+		let metrics = rewrite_metrics(ctx.old_content, ctx.new_content);
+		record_rewrite(pool, ctx.revision_id, &metrics).await?;
+		Ok(Vec::new())
+	}
+
+	async fn reset(&self, pool: &SqlitePool, document_id: i64) -> Result<(), sqlx::Error> {
+		sqlx::query!(
+			"DELETE FROM revision_rewrites WHERE revision_id IN (SELECT id FROM document_revisions WHERE document_id = ?)",
+			document_id
+		)
+		.execute(pool)
+		.await?;
+		Ok(())
+	}
+}
+
+struct LineageAnalyzer;
+
+#[async_trait]
+impl RevisionAnalyzer for LineageAnalyzer {
+	fn name(&self) -> &'static str {
+		"lineage"
+	}
+
+	fn description(&self) -> &'static str {
+		"Flags large first versions and finds the document a new one was copied from"
+	}
+
+	// Copies are found by the MinHash signatures the similarity analyzer keeps
+	fn requires(&self) -> &'static [&'static str] {
+		&["similarity"]
+	}
+
+	// A baseline has 0 added words, so a finished essay pasted into a fresh document would otherwise look like no work
+	async fn analyze(&self, pool: &SqlitePool, ctx: &RevisionContext<'_>) -> Result<Vec<RevisionFinding>, sqlx::Error> {
+		if ctx.previous_time.is_some() {
+			return Ok(Vec::new());
+		}
+
+		let words = baseline_words(pool, ctx.document_id, ctx.new_content).await?;
+		let parent = find_parent(pool, ctx.document_id, ctx.revision_time, ctx.new_content).await?;
+		record_lineage(pool, ctx.document_id, words, parent.as_ref()).await?;
+
+		let mut findings = Vec::new();
+		if words >= LARGE_BASELINE_WORDS {
+			findings.push(RevisionFinding {
+				kind: "large_baseline".to_string(),
+				score: Some(words as f64),
+				detail: serde_json::json!({ "words": words }),
+			});
+		}
+		if let Some(parent) = parent {
+			println!("🧬 Document {} looks like a copy of {} ({:.0}% similar)", ctx.document_id, parent.doc_id, parent.similarity * 100.0);
+			findings.push(RevisionFinding {
+				kind: "copied_from".to_string(),
+				score: Some(parent.similarity),
+				detail: serde_json::to_value(&parent).unwrap_or_default(),
+			});
+		}
+		Ok(findings)
+	}
+
+	async fn reset(&self, pool: &SqlitePool, document_id: i64) -> Result<(), sqlx::Error> {
+		sqlx::query!(
+			"UPDATE documents SET baseline_words = NULL, parent_document_id = NULL, parent_similarity = NULL WHERE id = ?",
+			document_id
+		)
+		.execute(pool)
+		.await?;
+		Ok(())
+	}
+}
+
+#[derive(Debug, Serialize)]
+pub struct AnalyzerStatus {
+	pub name: &'static str,
+	pub description: &'static str,
+	pub enabled: bool,
+	pub requires: &'static [&'static str],
+}
+
+pub async fn analyzer_statuses(pool: &SqlitePool) -> Result<Vec<AnalyzerStatus>, sqlx::Error> {
+	let settings: HashMap<String, bool> = sqlx::query!(r#"SELECT name AS "name!", enabled FROM analyzer_settings"#)
+		.fetch_all(pool)
+		.await?
+		.into_iter()
+		.map(|s| (s.name, s.enabled))
+		.collect();
+
+	Ok(registry()
+		.iter()
+		.map(|a| AnalyzerStatus {
+			name: a.name(),
+			description: a.description(),
+			enabled: settings.get(a.name()).copied().unwrap_or(a.enabled_by_default()),
+			requires: a.requires(),
+		})
+		.collect())
+}
+
+pub enum SettingChange {
+	Updated,
+	UnknownAnalyzer,
+	// Refused, because it would leave an enabled analyzer without one it requires
+	Blocked(String),
+}
+
+pub async fn set_analyzer_enabled(pool: &SqlitePool, name: &str, enabled: bool) -> Result<SettingChange, sqlx::Error> {
+	let statuses = analyzer_statuses(pool).await?;
+	let Some(analyzer) = statuses.iter().find(|a| a.name == name) else {
+		return Ok(SettingChange::UnknownAnalyzer);
+	};
+
+	if enabled {
+		let missing: Vec<&str> = analyzer
+			.requires
+			.iter()
+			.copied()
+			.filter(|required| statuses.iter().any(|s| s.name == *required && !s.enabled))
+			.collect();
+		if !missing.is_empty() {
+			return Ok(SettingChange::Blocked(format!("{} requires {}; enable it first", name, missing.join(", "))));
+		}
+	} else {
+		let dependents: Vec<&str> = statuses
+			.iter()
+			.filter(|s| s.enabled && s.requires.contains(&name))
+			.map(|s| s.name)
+			.collect();
+		if !dependents.is_empty() {
+			return Ok(SettingChange::Blocked(format!("{} is required by {}; disable those first", name, dependents.join(", "))));
+		}
+	}
+
+	sqlx::query!(
+		"INSERT INTO analyzer_settings (name, enabled) VALUES (?, ?)
+		ON CONFLICT(name) DO UPDATE SET enabled = excluded.enabled",
+		name,
+		enabled
+	)
+	.execute(pool)
+	.await?;
+
+	Ok(SettingChange::Updated)
+}
+
+async fn store_findings(
+	pool: &SqlitePool,
+	analyzer: &str,
+	revision_id: i64,
+	findings: &[RevisionFinding],
+) -> Result<(), sqlx::Error> {
+	for finding in findings {
+		let detail = finding.detail.to_string();
+		sqlx::query!(
+			"INSERT INTO revision_findings (revision_id, analyzer, kind, score, detail) VALUES (?, ?, ?, ?, ?)",
+			revision_id,
+			analyzer,
+			finding.kind,
+			finding.score,
+			detail
+		)
+		.execute(pool)
+		.await?;
+	}
+	Ok(())
+}
+
+async fn run_one(pool: &SqlitePool, analyzer: &dyn RevisionAnalyzer, ctx: &RevisionContext<'_>) -> Result<(), sqlx::Error> {
+	let findings = analyzer.analyze(pool, ctx).await?;
+	store_findings(pool, analyzer.name(), ctx.revision_id, &findings).await
+}
+
+// Runs every enabled analyzer on a new revision.
+// A failing analyzer is logged and skipped, so it can't hold up polling or the other analyzers.
+pub async fn run_analyzers(pool: &SqlitePool, ctx: &RevisionContext<'_>) -> Result<(), sqlx::Error> {
+	let enabled: Vec<&'static str> = analyzer_statuses(pool)
+		.await?
+		.into_iter()
+		.filter(|s| s.enabled)
+		.map(|s| s.name)
+		.collect();
+
+	for analyzer in registry().iter().filter(|a| enabled.contains(&a.name())) {
+		if let Err(e) = run_one(pool, analyzer.as_ref(), ctx).await {
+			eprintln!("⚠️ Analyzer {} failed on revision {}: {:?}", analyzer.name(), ctx.revision_id, e);
+		}
+	}
+
+	Ok(())
+}
+
+#[derive(Debug, Default)]
+pub struct RerunSummary {
+	pub analyzers: Vec<&'static str>,
+	pub documents: usize,
+	pub revisions: usize,
+	pub failures: usize,
+}
+
+pub enum RerunPlan {
+	Ready(Vec<&'static str>),
+	UnknownAnalyzer,
+	// Refused, because an analyzer to re-run needs one that is disabled
+	Blocked(String),
+}
+
+// Picks the analyzers a re-run would use.
+// With no analyzer named, every enabled analyzer runs; a named analyzer runs even if disabled,
+// but not without the analyzers it requires.
+pub async fn plan_rerun(pool: &SqlitePool, analyzer: Option<&str>) -> Result<RerunPlan, sqlx::Error> {
+	let statuses = analyzer_statuses(pool).await?;
+	let selected: Vec<&AnalyzerStatus> = match analyzer {
+		Some(name) => match statuses.iter().find(|s| s.name == name) {
+			Some(status) => vec![status],
+			None => return Ok(RerunPlan::UnknownAnalyzer),
+		},
+		None => statuses.iter().filter(|s| s.enabled).collect(),
+	};
+
+	for status in &selected {
+		let missing: Vec<&str> = status
+			.requires
+			.iter()
+			.copied()
+			.filter(|required| statuses.iter().any(|s| s.name == *required && !s.enabled))
+			.collect();
+		if !missing.is_empty() {
+			return Ok(RerunPlan::Blocked(format!("{} requires {}; enable it first", status.name, missing.join(", "))));
+		}
+	}
+
+	Ok(RerunPlan::Ready(selected.iter().map(|s| s.name).collect()))
+}
+
+// Clears and re-runs the given analyzers over the stored history of one or all documents.
+// Polling is paused until it finishes: revisions are replayed across documents in time order,
+// so a revision stored midway would be analyzed before the history it follows.
+pub async fn rerun_analyzers(
+	state: &AppState,
+	analyzers: &[&str],
+	document_id: Option<i64>,
+) -> Result<RerunSummary, sqlx::Error> {
+	let pool = &state.db;
+	let _guard = state.poll_lock.lock().await;
+
+	let registry = registry();
+	let selected: Vec<&dyn RevisionAnalyzer> =
+		registry.iter().filter(|a| analyzers.contains(&a.name())).map(|a| a.as_ref()).collect();
+
+	let documents = match document_id {
+		Some(id) => vec![id],
+		None => sqlx::query_scalar!(r#"SELECT id AS "id!" FROM documents ORDER BY id"#)
+			.fetch_all(pool)
+			.await?,
+	};
+
+	let mut summary = RerunSummary {
+		analyzers: selected.iter().map(|a| a.name()).collect(),
+		documents: documents.len(),
+		..Default::default()
+	};
+
+	// Reset everything first, so no document is compared against another's stale results
+	for &document_id in &documents {
+		for analyzer in &selected {
+			analyzer.reset(pool, document_id).await?;
+			let name = analyzer.name();
+			sqlx::query!(
+				"DELETE FROM revision_findings
+				 WHERE analyzer = ? AND revision_id IN (SELECT id FROM document_revisions WHERE document_id = ?)",
+				name,
+				document_id
+			)
+			.execute(pool)
+			.await?;
+		}
+	}
+
+	// Then replay revisions across documents in the order they were written, as polling saw them
+	let revisions = sqlx::query!(
+		r#"
+		SELECT id AS "id!", document_id
+		FROM document_revisions
+		WHERE ? IS NULL OR document_id = ?
+		ORDER BY revision_time, id
+		"#,
+		document_id,
+		document_id
+	)
+	.fetch_all(pool)
+	.await?;
+
+	let mut previous: HashMap<i64, (String, String)> = HashMap::new(); // document id -> (time, content)
+	for revision in revisions {
+		let row = sqlx::query!(
+			"SELECT revision_time, content, diff FROM document_revisions WHERE id = ?",
+			revision.id
+		)
+		.fetch_one(pool)
+		.await?;
+
+		let diff: Vec<OwnedWordChange> = row
+			.diff
+			.as_deref()
+			.filter(|d| !d.is_empty())
+			.and_then(|d| serde_json::from_str(d).ok())
+			.unwrap_or_default();
+
+		let before = previous.get(&revision.document_id);
+		let ctx = RevisionContext {
+			document_id: revision.document_id,
+			revision_id: revision.id,
+			revision_time: &row.revision_time,
+			previous_time: before.map(|(time, _)| time.as_str()),
+			old_content: before.map(|(_, content)| content.as_str()).unwrap_or(""),
+			new_content: &row.content,
+			diff: &diff,
+			hidden_runs: &[],
+		};
+
+		for analyzer in &selected {
+			if let Err(e) = run_one(pool, *analyzer, &ctx).await {
+				eprintln!("⚠️ Analyzer {} failed on revision {}: {:?}", analyzer.name(), revision.id, e);
+				summary.failures += 1;
+			}
+		}
+		summary.revisions += 1;
+
+		previous.insert(revision.document_id, (row.revision_time, row.content));
+	}
+
+	Ok(summary)
+}
@@ -0,0 +1,158 @@
+use std::time::Duration;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use sqlx::SqlitePool;
+
+use crate::poller::poll_document;
+use crate::templates::refresh_template_words;
+use crate::state::AppState;
+
+// Normalizes a user-supplied RFC 3339 timestamp to UTC with milliseconds ("2025-04-13T23:59:00.000Z"),
+// matching Drive's modifiedTime so due times and revision times compare correctly as strings
+pub fn normalize_timestamp(input: &str) -> Result<String, String> {
+	DateTime::parse_from_rfc3339(input)
+		.map(|t| t.to_utc().to_rfc3339_opts(SecondsFormat::Millis, true))
+		.map_err(|e| format!("Invalid timestamp {}: {}", input, e))
+}
+
+pub fn now_timestamp() -> String {
+	Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+// Recomputes the late flag on every revision of a document.
+// Needed whenever a revision is added, the document is (un)linked, or a due time changes.
+pub async fn refresh_late_flags(pool: &SqlitePool, document_id: i64) -> Result<(), sqlx::Error> {
+	sqlx::query!(
+		r#"
+		UPDATE document_revisions
+		SET is_late = EXISTS (
+			SELECT 1
+			FROM assignment_documents ad
+			JOIN assignments a ON a.id = ad.assignment_id
+			WHERE ad.document_id = document_revisions.document_id
+				AND document_revisions.revision_time > a.due_time
+		)
+		WHERE document_id = ?
+		"#,
+		document_id
+	)
+	.execute(pool)
+	.await?;
+
+	Ok(())
+}
+
+// Everything derived from which assignments a document is linked to: late flags and template words
+pub async fn refresh_assignment_links(pool: &SqlitePool, document_id: i64) -> Result<(), sqlx::Error> {
+	refresh_late_flags(pool, document_id).await?;
+	refresh_template_words(pool, document_id).await
+}
+
+// Refreshes the late flags of every document linked to an assignment
+pub async fn refresh_assignment_late_flags(pool: &SqlitePool, assignment_id: i64) -> Result<(), sqlx::Error> {
+	let documents = sqlx::query_scalar!(
+		"SELECT document_id FROM assignment_documents WHERE assignment_id = ?",
+		assignment_id
+	)
+	.fetch_all(pool)
+	.await?;
+
+	for document_id in documents {
+		refresh_late_flags(pool, document_id).await?;
+	}
+
+	Ok(())
+}
+
+// Watches for assignment deadlines, independently of the 5-minute poll, so snapshots are taken on time
+pub async fn deadline_loop(state: AppState) {
+	let mut interval = tokio::time::interval(Duration::from_secs(30));
+
+	loop {
+		interval.tick().await;
+		if let Err(e) = freeze_due_assignments(&state).await {
+			eprintln!("Deadline freeze error: {:?}", e);
+		}
+	}
+}
+
+async fn freeze_due_assignments(state: &AppState) -> Result<(), sqlx::Error> {
+	let pool = &state.db;
+	let now = now_timestamp();
+
+	let due = sqlx::query!(
+		r#"SELECT id AS "id!", name, due_time FROM assignments WHERE frozen_at IS NULL AND due_time <= ?"#,
+		now
+	)
+	.fetch_all(pool)
+	.await?;
+
+	for assignment in due {
+		let documents = sqlx::query!(
+			r#"
+			SELECT d.id AS "id!", d.doc_id, d.last_checked
+			FROM assignment_documents ad
+			JOIN documents d ON d.id = ad.document_id
+			WHERE ad.assignment_id = ?
+			"#,
+			assignment.id
+		)
+		.fetch_all(pool)
+		.await?;
+
+		for doc in documents {
+			// Edits made since the last poll would otherwise be missing from the snapshot.
+			// Only a check made at or after the due time has seen every edit before it.
+			if doc.last_checked.as_deref().is_none_or(|checked| checked < assignment.due_time.as_str()) {
+				if let Err(e) = poll_document(state, &doc.doc_id).await {
+					eprintln!("⚠️ Failed to fetch {} at the deadline of {}: {}", doc.doc_id, assignment.name, e);
+				}
+			}
+
+			freeze_document(pool, assignment.id, doc.id, &assignment.due_time).await?;
+		}
+
+		sqlx::query!("UPDATE assignments SET frozen_at = ? WHERE id = ?", now, assignment.id)
+			.execute(pool)
+			.await?;
+
+		println!("🧊 Froze snapshots for assignment: {}", assignment.name);
+	}
+
+	Ok(())
+}
+
+// Points the document's snapshot at its last revision at or before the due time
+pub async fn freeze_document(
+	pool: &SqlitePool,
+	assignment_id: i64,
+	document_id: i64,
+	due_time: &str,
+) -> Result<(), sqlx::Error> {
+	let revision_id = sqlx::query_scalar!(
+		r#"
+		SELECT id AS "id!" FROM document_revisions
+		WHERE document_id = ? AND revision_time <= ?
+		ORDER BY revision_time DESC, id DESC
+		LIMIT 1
+		"#,
+		document_id,
+		due_time
+	)
+	.fetch_optional(pool)
+	.await?;
+
+	let now = now_timestamp();
+	sqlx::query!(
+		"INSERT INTO assignment_snapshots (assignment_id, document_id, revision_id, frozen_at) VALUES (?, ?, ?, ?)
+		ON CONFLICT(assignment_id, document_id) DO UPDATE SET revision_id = excluded.revision_id, frozen_at = excluded.frozen_at",
+		assignment_id,
+		document_id,
+		revision_id,
+		now
+	)
+	.execute(pool)
+	.await?;
+
+	Ok(())
+}
@@ -0,0 +1,119 @@
+use clap::{Parser, Subcommand};
+use reqwest::Client;
+use serde::Deserialize;
+
+/// CLI for managing Docwatch's revision analyzers via the backend API
+#[derive(Parser)]
+#[command(name = "docwatch-analyzectl", about = "Revision analyzer CLI for Docwatch")]
+struct Cli {
+	#[command(subcommand)]
+	command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+	/// List analyzers and whether each runs on new revisions
+	List,
+	/// Run an analyzer on new revisions
+	Enable { name: String },
+	/// Stop running an analyzer on new revisions
+	Disable { name: String },
+	/// Clear and re-run analyzers over stored revisions
+	Rerun {
+		/// Only this analyzer (runs even if disabled); every enabled analyzer if omitted
+		#[arg(short, long)]
+		analyzer: Option<String>,
+		/// Only this document (Google Drive file id); every document if omitted
+		#[arg(short, long)]
+		doc_id: Option<String>,
+	},
+}
+
+#[derive(Deserialize)]
+struct AnalyzerStatus {
+	name: String,
+	description: String,
+	enabled: bool,
+}
+
+#[derive(Deserialize)]
+struct RerunStarted {
+	analyzers: Vec<String>,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+	dotenv::dotenv().ok();
+
+	if std::env::var("ADMIN_TOKEN").is_err() {
+		// Try fallback path (e.g., used in production)
+		let _ = dotenv::from_path("/etc/docwatch/.env");
+	}
+
+	let base_url = std::env::var("DOCWATCH_URL").unwrap_or_else(|_| "http://localhost:3009/docwatch/api".to_string());
+	let token = std::env::var("ADMIN_TOKEN").expect("Missing ADMIN_TOKEN (set in .env)");
+
+	let cli = Cli::parse();
+	let client = Client::new();
+
+	match cli.command {
+		Commands::List => {
+			let res = client
+				.get(format!("{}/admin/analyzers", base_url))
+				.header("Authorization", format!("Bearer {}", token))
+				.send()
+				.await?;
+
+			if res.status().is_success() {
+				let analyzers: Vec<AnalyzerStatus> = res.json().await?;
+				for a in analyzers {
+					let state = if a.enabled { "enabled " } else { "disabled" };
+					println!("{:<12} {}  {}", a.name, state, a.description);
+				}
+			} else {
+				println!("❌ Failed to list analyzers: {}", res.text().await?);
+			}
+		}
+
+		Commands::Enable { name } => set_enabled(&client, &base_url, &token, &name, true).await?,
+
+		Commands::Disable { name } => set_enabled(&client, &base_url, &token, &name, false).await?,
+
+		Commands::Rerun { analyzer, doc_id } => {
+			let res = client
+				.post(format!("{}/admin/analyzers/rerun", base_url))
+				.header("Authorization", format!("Bearer {}", token))
+				.json(&serde_json::json!({ "analyzer": analyzer, "doc_id": doc_id }))
+				.send()
+				.await?;
+
+			if res.status().is_success() {
+				let started: RerunStarted = res.json().await?;
+				println!(
+					"🔁 Re-running {} in the background; polling pauses until it finishes. See the server log for the result.",
+					started.analyzers.join(", ")
+				);
+			} else {
+				println!("❌ Failed: {}", res.text().await?);
+			}
+		}
+	}
+
+	Ok(())
+}
+
+async fn set_enabled(client: &Client, base_url: &str, token: &str, name: &str, enabled: bool) -> Result<(), reqwest::Error> {
+	let res = client
+		.patch(format!("{}/admin/analyzers/{}", base_url, name))
+		.header("Authorization", format!("Bearer {}", token))
+		.json(&serde_json::json!({ "enabled": enabled }))
+		.send()
+		.await?;
+
+	if res.status().is_success() {
+		println!("✅ Analyzer {} {}", name, if enabled { "enabled" } else { "disabled" });
+	} else {
+		println!("❌ Failed: {}", res.text().await?);
+	}
+	Ok(())
+}
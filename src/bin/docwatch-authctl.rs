@@ -1,64 +1,303 @@
-use std::{io::Write, fs::File};
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::{Parser, Subcommand};
+use serde_json::Value;
 use tiny_http::{Server, Response};
 
+use docwatch::token_manager::{Credentials, DriveAccess, TokenManager, DEFAULT_TOKEN_PATH};
+
+const TOKEN_URI: &str = "https://oauth2.googleapis.com/token";
+
+/// CLI for authorizing Docwatch with the Google API
+#[derive(Parser)]
+#[command(name = "docwatch-authctl", about = "Google API authorization CLI for Docwatch")]
+struct Cli {
+	/// Where the refresh token is stored
+	#[arg(long, env = "GOOGLE_TOKEN_PATH", default_value = DEFAULT_TOKEN_PATH)]
+	token_path: String,
+
+	#[command(subcommand)]
+	command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+	/// Authorize in a browser and receive the callback on localhost (default)
+	Login {
+		/// Local port for the OAuth callback; forward it with `ssh -L` when working remotely
+		#[arg(long, default_value_t = 8080)]
+		port: u16,
+	},
+	/// Authorize from any device by entering a code, for servers without a browser
+	Device,
+	/// Show the authorized account, granted scopes and token expiry
+	Status,
+	/// Revoke the stored refresh token at Google and delete it locally
+	Revoke,
+	/// Check that the Drive API can be reached with the current credentials
+	Test,
+}
+
 #[tokio::main]
-async fn main() {
-	
+async fn main() -> Result<()> {
+
 	dotenv::dotenv().ok();
-	
+
 	if std::env::var("ADMIN_TOKEN").is_err() {
 		// Try fallback path (e.g., used in production)
 		let _ = dotenv::from_path("/etc/docwatch/.env");
 	}
-	
-	let client_id = std::env::var("GOOGLE_CLIENT_ID").expect("Missing GOOGLE_CLIENT_ID");
-	let client_secret = std::env::var("GOOGLE_CLIENT_SECRET").expect("Missing GOOGLE_CLIENT_SECRET");
-	let redirect_uri = "http://localhost:8080/callback";
-	let scope = "https://www.googleapis.com/auth/drive";
+
+	let cli = Cli::parse();
+
+	match cli.command.unwrap_or(Commands::Login { port: 8080 }) {
+		Commands::Login { port } => login(&cli.token_path, port).await,
+		Commands::Device => device_login(&cli.token_path).await,
+		Commands::Status => status(&cli.token_path).await,
+		Commands::Revoke => revoke(&cli.token_path).await,
+		Commands::Test => test(&cli.token_path).await,
+	}
+}
+
+fn client_credentials() -> Result<(String, String)> {
+	let client_id = std::env::var("GOOGLE_CLIENT_ID").context("Missing GOOGLE_CLIENT_ID (set in .env)")?;
+	let client_secret = std::env::var("GOOGLE_CLIENT_SECRET").context("Missing GOOGLE_CLIENT_SECRET (set in .env)")?;
+	Ok((client_id, client_secret))
+}
+
+async fn login(token_path: &str, port: u16) -> Result<()> {
+	let (client_id, client_secret) = client_credentials()?;
+	let redirect_uri = format!("http://localhost:{}/callback", port);
+	// Random value echoed back by Google, so a stray request to the callback can't plant a token
+	let state = uuid::Uuid::new_v4().to_string();
 
 	let auth_url = format!(
-		"https://accounts.google.com/o/oauth2/v2/auth?response_type=code&client_id={}&redirect_uri={}&scope={}&access_type=offline&prompt=consent",
+		"https://accounts.google.com/o/oauth2/v2/auth?response_type=code&client_id={}&redirect_uri={}&scope={}&state={}&access_type=offline&prompt=consent",
 		client_id,
-		urlencoding::encode(redirect_uri),
-		urlencoding::encode(scope)
+		urlencoding::encode(&redirect_uri),
+		urlencoding::encode(DriveAccess::from_env().scope()),
+		state
 	);
-	
+
 	if webbrowser::open(&auth_url).is_err() {
 		println!("\nPlease open the following URL in your browser manually:\n\n{}\n", auth_url);
 	}
 
-	println!("Waiting for OAuth callback...");
+	println!("Waiting for OAuth callback on port {}...", port);
 
-	let server = Server::http("0.0.0.0:8080").unwrap();
+	let server = Server::http(("127.0.0.1", port)).map_err(|e| anyhow!("Failed to listen on port {}: {}", port, e))?;
 	for request in server.incoming_requests() {
-		let query = request.url();
-		if query.starts_with("/callback?") {
-			let code = query.split("code=").nth(1).unwrap().split('&').next().unwrap();
+		let url = reqwest::Url::parse(&format!("http://localhost{}", request.url()))?;
+		if url.path() != "/callback" {
+			let _ = request.respond(Response::from_string("Not found").with_status_code(404));
+			continue;
+		}
+
+		let param = |name: &str| url.query_pairs().find(|(k, _)| k == name).map(|(_, v)| v.into_owned());
+
+		if param("state").as_deref() != Some(state.as_str()) {
+			let _ = request.respond(Response::from_string("State mismatch, ignoring this request.").with_status_code(400));
+			eprintln!("⚠️ Ignored callback with missing or wrong state parameter");
+			continue;
+		}
+
+		if let Some(error) = param("error") {
+			let _ = request.respond(Response::from_string("Authorization was not granted. You can close this window."));
+			bail!("Authorization failed: {}", error);
+		}
+
+		let code = param("code").ok_or_else(|| anyhow!("Callback did not include an authorization code"))?;
+
+		let params = [
+			("code", code.as_str()),
+			("client_id", client_id.as_str()),
+			("client_secret", client_secret.as_str()),
+			("redirect_uri", redirect_uri.as_str()),
+			("grant_type", "authorization_code"),
+		];
+
+		let res = reqwest::Client::new()
+			.post(TOKEN_URI)
+			.form(&params)
+			.send()
+			.await?
+			.json::<Value>()
+			.await?;
+
+		save_token(token_path, &res)?;
+
+		let _ = request.respond(Response::from_string("Token saved. You can close this window."));
+		println!("✅ Token saved to {}", token_path);
+		return Ok(());
+	}
+
+	bail!("Callback server stopped before authorization completed")
+}
+
+// OAuth device authorization grant (RFC 8628).
+// The OAuth client must be of type "TVs and Limited Input devices". Google only grants that client
+// type a few Drive scopes (drive.file, drive.appdata), so it is refused for the scope Docwatch needs
+// to read documents unless Google allows it.
+async fn device_login(token_path: &str) -> Result<()> {
+	let (client_id, client_secret) = client_credentials()?;
+	let client = reqwest::Client::new();
+	let scope = DriveAccess::from_env().scope();
 
-			let body = format!(
-				"code={}&client_id={}&client_secret={}&redirect_uri={}&grant_type=authorization_code",
-				code, client_id, client_secret, redirect_uri
+	let res = client
+		.post("https://oauth2.googleapis.com/device/code")
+		.form(&[("client_id", client_id.as_str()), ("scope", scope)])
+		.send()
+		.await?
+		.json::<Value>()
+		.await?;
+
+	if let Some(error) = res["error"].as_str() {
+		if error == "invalid_scope" {
+			bail!(
+				"Google doesn't allow the device flow for {}. Run `docwatch-authctl login` instead, \
+				forwarding its port over SSH (ssh -L 8080:localhost:8080 server) on a headless server.",
+				scope
 			);
+		}
+		bail!("Device authorization request failed: {} {}", error, res["error_description"].as_str().unwrap_or(""));
+	}
+
+	let device_code = res["device_code"].as_str().context("Response has no device_code")?;
+	let user_code = res["user_code"].as_str().context("Response has no user_code")?;
+	let verification_url = res["verification_url"].as_str().unwrap_or("https://www.google.com/device");
+	let mut interval = res["interval"].as_u64().unwrap_or(5);
+
+	println!("\nOn any device, open:\n\n\t{}\n\nand enter the code:\n\n\t{}\n", verification_url, user_code);
+	println!("Waiting for authorization...");
 
-			let client = reqwest::Client::new();
-			let res = client
-				.post("https://oauth2.googleapis.com/token")
-				.header("Content-Type", "application/x-www-form-urlencoded")
-				.body(body)
-				.send()
-				.await
-				.unwrap()
-				.text()
-				.await
-				.unwrap();
-
-			let mut file = File::create("/opt/docwatch/google_token.json").unwrap();
-			file.write_all(res.as_bytes()).unwrap();
-
-			request.respond(Response::from_string("Token saved. You can close this window.")).unwrap();
-			break;
+	loop {
+		tokio::time::sleep(Duration::from_secs(interval)).await;
+
+		let res = client
+			.post(TOKEN_URI)
+			.form(&[
+				("client_id", client_id.as_str()),
+				("client_secret", client_secret.as_str()),
+				("device_code", device_code),
+				("grant_type", "urn:ietf:params:oauth:grant-type:device_code"),
+			])
+			.send()
+			.await?
+			.json::<Value>()
+			.await?;
+
+		match res["error"].as_str() {
+			None => {
+				save_token(token_path, &res)?;
+				println!("✅ Token saved to {}", token_path);
+				return Ok(());
+			}
+			Some("authorization_pending") => {}
+			Some("slow_down") => interval += 5,
+			Some("access_denied") => bail!("Authorization was denied"),
+			Some("expired_token") => bail!("The code expired before it was entered, please run again"),
+			Some(other) => bail!("Authorization failed: {} {}", other, res["error_description"].as_str().unwrap_or("")),
 		}
 	}
+}
+
+fn save_token(token_path: &str, res: &Value) -> Result<()> {
+	if res["refresh_token"].as_str().is_none() {
+		bail!("Token response did not include a refresh token: {}", res);
+	}
+
+	let contents = serde_json::to_string_pretty(res)?;
+	std::fs::write(token_path, contents).with_context(|| format!("Failed to write {}", token_path))?;
+
+	// The refresh token grants Drive access, keep it private to the service user
+	#[cfg(unix)]
+	{
+		use std::os::unix::fs::PermissionsExt;
+		std::fs::set_permissions(token_path, std::fs::Permissions::from_mode(0o600))
+			.with_context(|| format!("Failed to restrict permissions on {}", token_path))?;
+	}
+
+	Ok(())
+}
+
+// Uses the same credential lookup as the server, with the token path from the command line
+fn token_manager(token_path: &str) -> TokenManager {
+	let credentials = match Credentials::from_env() {
+		Credentials::OAuthRefreshToken { .. } => Credentials::OAuthRefreshToken { token_path: token_path.to_string() },
+		service_account => service_account,
+	};
+	TokenManager::with_credentials(credentials)
+}
+
+async fn status(token_path: &str) -> Result<()> {
+	let tokens = token_manager(token_path);
+	let access_token = tokens.access_token().await?;
+	let client = reqwest::Client::new();
+
+	let info = client
+		.get("https://oauth2.googleapis.com/tokeninfo")
+		.query(&[("access_token", access_token.as_str())])
+		.send()
+		.await?
+		.json::<Value>()
+		.await?;
+
+	let about = client
+		.get("https://www.googleapis.com/drive/v3/about?fields=user(displayName,emailAddress)")
+		.bearer_auth(&access_token)
+		.send()
+		.await?
+		.json::<Value>()
+		.await?;
+
+	let status = tokens.status().await;
+
+	println!("Account:    {} <{}>",
+		about["user"]["displayName"].as_str().unwrap_or("unknown"),
+		about["user"]["emailAddress"].as_str().unwrap_or("unknown"));
+	println!("Scopes:     {}", info["scope"].as_str().unwrap_or("unknown"));
+	println!("Expires at: {}", status.expires_at.as_deref().unwrap_or("unknown"));
+	Ok(())
+}
+
+async fn revoke(token_path: &str) -> Result<()> {
+	let data = std::fs::read_to_string(token_path).with_context(|| format!("Failed to read {}", token_path))?;
+	let saved: Value = serde_json::from_str(&data).with_context(|| format!("Invalid JSON in {}", token_path))?;
+	let refresh_token = saved["refresh_token"].as_str().context("No refresh token stored")?;
+
+	let res = reqwest::Client::new()
+		.post("https://oauth2.googleapis.com/revoke")
+		.form(&[("token", refresh_token)])
+		.send()
+		.await?;
+
+	if !res.status().is_success() {
+		// Already revoked tokens come back as invalid_token; removing the local copy is still right
+		eprintln!("⚠️ Google did not accept the revocation: {}", res.text().await?);
+	}
+
+	std::fs::remove_file(token_path).with_context(|| format!("Failed to delete {}", token_path))?;
+	println!("🗑️  Token revoked and {} deleted", token_path);
+	Ok(())
+}
+
+async fn test(token_path: &str) -> Result<()> {
+	let tokens = token_manager(token_path);
+	let access_token = tokens.access_token().await?;
+
+	let res = reqwest::Client::new()
+		.get("https://www.googleapis.com/drive/v3/files?pageSize=5&fields=files(name)&supportsAllDrives=true")
+		.bearer_auth(&access_token)
+		.send()
+		.await?;
+
+	if !res.status().is_success() {
+		bail!("Drive API request failed: {} {}", res.status(), res.text().await?);
+	}
 
-	println!("Token saved to google_token.json");
+	let body = res.json::<Value>().await?;
+	let files = body["files"].as_array().map(|f| f.len()).unwrap_or(0);
+	println!("✅ Drive API reachable, {} file(s) visible on the first page", files);
+	Ok(())
 }
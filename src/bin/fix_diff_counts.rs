@@ -1,3 +1,4 @@
+use serde::de::IgnoredAny;
 use serde::Deserialize;
 use sqlx::SqlitePool;
 
@@ -6,7 +7,7 @@ use sqlx::SqlitePool;
 enum OwnedWordChange {
 	Added(String),
 	Removed(String),
-	Unchanged(String),
+	Unchanged(IgnoredAny), // only added and removed words are counted
 }
 
 #[tokio::main]
@@ -0,0 +1,121 @@
+use sqlx::SqlitePool;
+
+use crate::google_api::{get_comments, get_google_suggestions, FetchMode, MIME_GOOGLE_DOC};
+use crate::token_manager::TokenManager;
+
+// Brings stored comments and suggestions for one document up to date.
+// `seen_time` is the document's modifiedTime and dates suggestion changes on the revision timeline.
+pub async fn sync_annotations(
+	pool: &SqlitePool,
+	tokens: &TokenManager,
+	document_id: i64,
+	file_id: &str,
+	mime_type: &str,
+	seen_time: &str,
+) -> Result<(), String> {
+	sync_comments(pool, tokens, document_id, file_id).await?;
+	sync_suggestions(pool, tokens, document_id, file_id, mime_type, seen_time).await
+}
+
+// Adding a comment or reply doesn't change the file's modifiedTime, so this runs on every poll.
+// Only comments changed since the newest stored one are fetched.
+pub async fn sync_comments(pool: &SqlitePool, tokens: &TokenManager, document_id: i64, file_id: &str) -> Result<(), String> {
+	let since = sqlx::query_scalar!("SELECT MAX(modified_time) FROM document_comments WHERE document_id = ?", document_id)
+		.fetch_one(pool)
+		.await
+		.map_err(|e| format!("DB error: {}", e))?;
+
+	let comments = get_comments(tokens, file_id, since.as_deref()).await?;
+
+	for c in comments {
+		sqlx::query!(
+			"INSERT INTO document_comments (
+				document_id, comment_id, parent_comment_id, author_name, author_email,
+				content, quoted_text, created_time, modified_time, resolved, deleted
+			) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+			ON CONFLICT(document_id, comment_id) DO UPDATE SET
+				content = excluded.content,
+				quoted_text = excluded.quoted_text,
+				modified_time = excluded.modified_time,
+				resolved = excluded.resolved,
+				deleted = excluded.deleted",
+			document_id,
+			c.id,
+			c.parent_id,
+			c.author_name,
+			c.author_email,
+			c.content,
+			c.quoted_text,
+			c.created_time,
+			c.modified_time,
+			c.resolved,
+			c.deleted
+		)
+		.execute(pool)
+		.await
+		.map_err(|e| format!("DB error: {}", e))?;
+	}
+
+	Ok(())
+}
+
+// Suggestions are part of the document, so they only change along with its modifiedTime
+pub async fn sync_suggestions(
+	pool: &SqlitePool,
+	tokens: &TokenManager,
+	document_id: i64,
+	file_id: &str,
+	mime_type: &str,
+	seen_time: &str,
+) -> Result<(), String> {
+	// Suggestions need the Docs API, which is only expected to be enabled in structured mode
+	if mime_type != MIME_GOOGLE_DOC || FetchMode::from_env() != FetchMode::Structured {
+		return Ok(());
+	}
+
+	let current = get_google_suggestions(tokens, file_id).await?;
+
+	for s in &current {
+		sqlx::query!(
+			"INSERT INTO document_suggestions (document_id, suggestion_id, kind, text, first_seen)
+			VALUES (?, ?, ?, ?, ?)
+			ON CONFLICT(document_id, suggestion_id, kind) DO UPDATE SET
+				text = excluded.text,
+				resolved_at = NULL",
+			document_id,
+			s.id,
+			s.kind,
+			s.text,
+			seen_time
+		)
+		.execute(pool)
+		.await
+		.map_err(|e| format!("DB error: {}", e))?;
+	}
+
+	// Anything still open in the DB but gone from the document was accepted or rejected
+	let open = sqlx::query!(
+		"SELECT id, suggestion_id, kind FROM document_suggestions
+		WHERE document_id = ? AND resolved_at IS NULL",
+		document_id
+	)
+	.fetch_all(pool)
+	.await
+	.map_err(|e| format!("DB error: {}", e))?;
+
+	for row in open {
+		let still_open = current.iter().any(|s| s.id == row.suggestion_id && s.kind == row.kind);
+		if !still_open {
+			sqlx::query!(
+				"UPDATE document_suggestions SET resolved_at = ? WHERE id = ?",
+				seen_time,
+				row.id
+			)
+			.execute(pool)
+			.await
+			.map_err(|e| format!("DB error: {}", e))?;
+		}
+	}
+
+	Ok(())
+}
@@ -0,0 +1,224 @@
+use std::collections::{HashMap, HashSet};
+
+use sqlx::SqlitePool;
+
+use crate::poller::{diff_words, OwnedWordChange};
+use crate::similarity::{shingles, MIN_PASSAGE_WORDS, SHINGLE_WORDS};
+
+// Reference texts are shared by all users: a prompt or prior submission uploaded by one
+// instructor is checked against every watched document.
+
+pub const REFERENCE_KINDS: [&str; 4] = ["prompt", "reading", "prior_submission", "other"];
+
+pub async fn add_reference(
+	pool: &SqlitePool,
+	user_id: i64,
+	title: &str,
+	kind: &str,
+	file_name: Option<&str>,
+	content: &str,
+) -> Result<i64, sqlx::Error> {
+	let mut tx = pool.begin().await?;
+
+	let reference_id = sqlx::query_scalar!(
+		r#"INSERT INTO reference_texts (user_id, title, kind, file_name, content) VALUES (?, ?, ?, ?, ?) RETURNING id AS "id!""#,
+		user_id,
+		title,
+		kind,
+		file_name,
+		content
+	)
+	.fetch_one(&mut *tx)
+	.await?;
+
+	let hashes: HashSet<i64> = shingles(content).into_iter().map(|s| s.hash).collect();
+	for hash in hashes {
+		sqlx::query!(
+			"INSERT OR IGNORE INTO reference_shingles (hash, reference_id) VALUES (?, ?)",
+			hash,
+			reference_id
+		)
+		.execute(&mut *tx)
+		.await?;
+	}
+
+	tx.commit().await?;
+	Ok(reference_id)
+}
+
+// Text added in one stretch of a diff, with the diff index each token starts at
+pub struct AddedRun {
+	pub text: String,
+	token_starts: Vec<(usize, usize)>, // (char offset in text, diff index)
+}
+
+impl AddedRun {
+	fn diff_index_at(&self, char_offset: usize) -> usize {
+		let i = self.token_starts.partition_point(|(start, _)| *start <= char_offset);
+		self.token_starts[i.saturating_sub(1)].1
+	}
+}
+
+// Consecutive added tokens form a run; removed tokens and unchanged whitespace don't interrupt it,
+// since they don't separate the added words in the new text
+pub fn added_runs(diff: &[OwnedWordChange]) -> Vec<AddedRun> {
+	let mut runs = Vec::new();
+	let mut current: Option<AddedRun> = None;
+
+	for (index, change) in diff.iter().enumerate() {
+		match change {
+			OwnedWordChange::Added(text) => {
+				let run = current.get_or_insert_with(|| AddedRun { text: String::new(), token_starts: Vec::new() });
+				run.token_starts.push((run.text.chars().count(), index));
+				run.text.push_str(text);
+			}
+			OwnedWordChange::Removed(_) => {}
+			OwnedWordChange::Unchanged(text) if text.trim().is_empty() => {
+				if let Some(run) = current.as_mut() {
+					run.token_starts.push((run.text.chars().count(), index));
+					run.text.push_str(text);
+				}
+			}
+			OwnedWordChange::Unchanged(_) => runs.extend(current.take()),
+		}
+	}
+	runs.extend(current);
+
+	runs
+}
+
+struct CorpusMatch {
+	reference_id: i64,
+	diff_start: usize,
+	diff_end: usize,
+	shingles: usize,
+	end_offset: usize,
+}
+
+fn passage_words(m: &CorpusMatch) -> usize {
+	m.shingles + SHINGLE_WORDS - 1
+}
+
+// The added runs of a revision. A baseline has no diff, so all of its text is one added run,
+// indexed like the diff the API shows for it.
+fn revision_runs(content: &str, diff: &[OwnedWordChange]) -> Vec<AddedRun> {
+	if diff.is_empty() {
+		let baseline: Vec<OwnedWordChange> = diff_words("", content).into_iter().map(Into::into).collect();
+		return added_runs(&baseline);
+	}
+	added_runs(diff)
+}
+
+// Passages of a run made of overlapping shingles found in the same reference text.
+// `references` gives the references containing each shingle hash.
+fn match_passages(run: &AddedRun, references: &HashMap<i64, Vec<i64>>) -> Vec<CorpusMatch> {
+	let mut matches: Vec<CorpusMatch> = Vec::new();
+
+	for shingle in shingles(&run.text) {
+		for &reference_id in references.get(&shingle.hash).into_iter().flatten() {
+			// Extend a passage of the same reference that this shingle overlaps
+			if let Some(m) = matches
+				.iter_mut()
+				.find(|m| m.reference_id == reference_id && shingle.span.start < m.end_offset)
+			{
+				m.diff_end = run.diff_index_at(shingle.span.end - 1);
+				m.end_offset = shingle.span.end;
+				m.shingles += 1;
+				continue;
+			}
+			matches.push(CorpusMatch {
+				reference_id,
+				diff_start: run.diff_index_at(shingle.span.start),
+				diff_end: run.diff_index_at(shingle.span.end - 1),
+				shingles: 1,
+				end_offset: shingle.span.end,
+			});
+		}
+	}
+
+	matches.retain(|m| passage_words(m) >= MIN_PASSAGE_WORDS);
+	matches
+}
+
+// Records which added passages of a revision also occur in a reference text
+pub async fn check_revision(
+	pool: &SqlitePool,
+	revision_id: i64,
+	content: &str,
+	diff: &[OwnedWordChange],
+) -> Result<(), sqlx::Error> {
+	for run in revision_runs(content, diff) {
+		let mut references = HashMap::new();
+		for shingle in shingles(&run.text) {
+			if references.contains_key(&shingle.hash) {
+				continue;
+			}
+			let ids = sqlx::query_scalar!("SELECT reference_id FROM reference_shingles WHERE hash = ?", shingle.hash)
+				.fetch_all(pool)
+				.await?;
+			references.insert(shingle.hash, ids);
+		}
+
+		for m in match_passages(&run, &references) {
+			let words = passage_words(&m) as i64;
+			let (diff_start, diff_end) = (m.diff_start as i64, m.diff_end as i64);
+			sqlx::query!(
+				"INSERT INTO corpus_matches (revision_id, reference_id, diff_start, diff_end, words) VALUES (?, ?, ?, ?, ?)",
+				revision_id,
+				m.reference_id,
+				diff_start,
+				diff_end,
+				words
+			)
+			.execute(pool)
+			.await?;
+		}
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const ESSAY: &str = "Last year's essay argued that the printing press changed how ordinary people \
+		understood religion, politics and their own place in the world around them.";
+
+	fn references_for(text: &str, reference_id: i64) -> HashMap<i64, Vec<i64>> {
+		shingles(text).into_iter().map(|s| (s.hash, vec![reference_id])).collect()
+	}
+
+	#[test]
+	fn baseline_is_checked_as_one_added_run() {
+		let content = format!("My essay\n\n{}", ESSAY);
+		let runs = revision_runs(&content, &[]);
+		assert_eq!(runs.len(), 1);
+		assert_eq!(runs[0].text, content);
+
+		let matches = match_passages(&runs[0], &references_for(ESSAY, 7));
+		assert_eq!(matches.len(), 1);
+		let tokens: Vec<String> = diff_words("", &content).into_iter().map(|c| OwnedWordChange::from(c).into_text()).collect();
+		assert_eq!(matches[0].reference_id, 7);
+		assert_eq!(tokens[matches[0].diff_start], "Last");
+		assert_eq!(tokens[matches[0].diff_end], "them.");
+	}
+
+	#[test]
+	fn short_overlaps_are_not_passages() {
+		let content = "the printing press changed how ordinary people lived";
+		let runs = revision_runs(content, &[]);
+		assert!(match_passages(&runs[0], &references_for(ESSAY, 7)).is_empty());
+	}
+
+	#[test]
+	fn only_added_text_of_a_later_revision_is_checked() {
+		let old = "Introduction.";
+		let new = format!("Introduction. {}", ESSAY);
+		let diff: Vec<OwnedWordChange> = diff_words(old, &new).into_iter().map(Into::into).collect();
+		let runs = revision_runs(&new, &diff);
+		assert_eq!(runs.len(), 1);
+		assert!(!runs[0].text.contains("Introduction"));
+		assert_eq!(match_passages(&runs[0], &references_for(ESSAY, 3)).len(), 1);
+	}
+}
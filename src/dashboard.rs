@@ -0,0 +1,301 @@
+use std::collections::BTreeMap;
+
+use chrono::DateTime;
+use serde::Serialize;
+use sqlx::SqlitePool;
+
+use crate::templates::{template_words_in_text, user_template_hashes, user_template_words};
+
+// Revisions further apart than this belong to separate writing sessions
+pub const SESSION_GAP: chrono::Duration = chrono::Duration::minutes(30);
+
+#[derive(Debug, Serialize)]
+pub struct FlaggedEvent {
+	pub revision_id: i64,
+	pub revision_time: String,
+	pub kind: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DocumentStats {
+	pub doc_id: String,
+	pub name: String,
+	pub owner_username: String,
+	pub total_words: i64,
+	pub revisions: i64,
+	pub sessions: i64,
+	pub first_edit: Option<String>,
+	pub last_edit: Option<String>,
+	pub late_edits: i64,
+	pub late_added_words: i64,
+	pub flagged_events: Vec<FlaggedEvent>,
+	pub outliers: Vec<&'static str>, // metrics outside the class's usual range
+}
+
+#[derive(Debug, Serialize)]
+pub struct Distribution {
+	pub min: f64,
+	pub p10: f64,
+	pub p25: f64,
+	pub median: f64,
+	pub p75: f64,
+	pub p90: f64,
+	pub max: f64,
+}
+
+impl Distribution {
+	fn of(values: &mut [f64]) -> Option<Distribution> {
+		if values.is_empty() {
+			return None;
+		}
+		values.sort_by(|a, b| a.total_cmp(b));
+
+		Some(Distribution {
+			min: values[0],
+			p10: percentile(values, 0.10),
+			p25: percentile(values, 0.25),
+			median: percentile(values, 0.50),
+			p75: percentile(values, 0.75),
+			p90: percentile(values, 0.90),
+			max: values[values.len() - 1],
+		})
+	}
+
+	// Tukey's fences: more than 1.5 interquartile ranges outside the middle half
+	fn is_outlier(&self, value: f64) -> bool {
+		let iqr = self.p75 - self.p25;
+		value < self.p25 - 1.5 * iqr || value > self.p75 + 1.5 * iqr
+	}
+}
+
+// Linear interpolation between closest ranks; `sorted` must be sorted and non-empty
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+	let rank = p * (sorted.len() - 1) as f64;
+	let lower = rank.floor() as usize;
+	let upper = rank.ceil() as usize;
+	sorted[lower] + (sorted[upper] - sorted[lower]) * (rank - lower as f64)
+}
+
+#[derive(Debug, Serialize)]
+pub struct ClassDashboard {
+	pub documents: Vec<DocumentStats>,
+	pub distributions: BTreeMap<&'static str, Distribution>,
+}
+
+type Metric = (&'static str, fn(&DocumentStats) -> f64);
+
+const METRICS: [Metric; 6] = [
+	("total_words", |d| d.total_words as f64),
+	("revisions", |d| d.revisions as f64),
+	("sessions", |d| d.sessions as f64),
+	("late_edits", |d| d.late_edits as f64),
+	("late_added_words", |d| d.late_added_words as f64),
+	("flagged_events", |d| d.flagged_events.len() as f64),
+];
+
+pub fn count_sessions(revision_times: &[String]) -> i64 {
+	let times: Vec<_> = revision_times
+		.iter()
+		.filter_map(|t| DateTime::parse_from_rfc3339(t).ok())
+		.collect();
+
+	if times.is_empty() {
+		return 0;
+	}
+
+	1 + times.windows(2).filter(|w| w[1] - w[0] > SESSION_GAP).count() as i64
+}
+
+async fn document_stats(pool: &SqlitePool, user_id: i64, document_id: i64) -> Result<Option<DocumentStats>, sqlx::Error> {
+	let Some(doc) = sqlx::query!(
+		"SELECT doc_id, name, owner_username, latest_content FROM documents WHERE id = ?",
+		document_id
+	)
+	.fetch_optional(pool)
+	.await?
+	else {
+		return Ok(None);
+	};
+
+	let revisions = sqlx::query!(
+		r#"
+		SELECT id AS "id!", revision_time, added_words, template_words, is_late
+		FROM document_revisions
+		WHERE document_id = ?
+		ORDER BY revision_time
+		"#,
+		document_id
+	)
+	.fetch_all(pool)
+	.await?;
+
+	let times: Vec<String> = revisions.iter().map(|r| r.revision_time.clone()).collect();
+
+	// Words the student wrote, not counting template text
+	let total_words = match doc.latest_content {
+		Some(content) => {
+			let hashes = user_template_hashes(pool, user_id, document_id).await?;
+			content.split_whitespace().count() as i64 - template_words_in_text(&content, &hashes)
+		}
+		None => 0,
+	};
+
+	let user_template_words = user_template_words(pool, user_id, document_id).await?;
+
+	let mut flagged_events = Vec::new();
+	let mut late_edits = 0;
+	let mut late_added_words = 0;
+	for r in &revisions {
+		if r.is_late {
+			late_edits += 1;
+			let template_words = user_template_words
+				.as_ref()
+				.and_then(|words| words.get(&r.id).copied())
+				.unwrap_or(r.template_words);
+			late_added_words += r.added_words.unwrap_or(0) - template_words;
+			flagged_events.push(FlaggedEvent {
+				revision_id: r.id,
+				revision_time: r.revision_time.clone(),
+				kind: "late_edit".to_string(),
+			});
+		}
+	}
+
+	// Everything the analyzers flagged, once per revision and kind:
+	// hidden text a revision introduced, in-place rewrite events, generic analyzer findings
+	// (long insertions, large baselines, copies), and passages also found in the reference corpus
+	// or first written in another watched document
+	let analyzer_events = sqlx::query!(
+		r#"
+		SELECT r.id AS "revision_id!", r.revision_time AS "revision_time!", 'hidden_text' AS "kind!: String"
+		FROM document_revisions r
+		JOIN hidden_text_findings f ON f.revision_id = r.id
+		WHERE r.document_id = ? AND f.is_new
+		UNION
+		SELECT r.id, r.revision_time, 'rewrite'
+		FROM document_revisions r
+		JOIN revision_rewrites w ON w.revision_id = r.id
+		WHERE r.document_id = ? AND w.is_event
+		UNION
+		SELECT r.id, r.revision_time, f.kind
+		FROM document_revisions r
+		JOIN revision_findings f ON f.revision_id = r.id
+		WHERE r.document_id = ?
+		UNION
+		SELECT r.id, r.revision_time, 'corpus_match'
+		FROM document_revisions r
+		JOIN corpus_matches m ON m.revision_id = r.id
+		WHERE r.document_id = ?
+		UNION
+		SELECT r.id, r.revision_time, 'similarity_match'
+		FROM document_revisions r
+		JOIN similarity_matches m ON m.revision_id = r.id
+		WHERE r.document_id = ? AND m.source_document_id != m.document_id
+		"#,
+		document_id,
+		document_id,
+		document_id,
+		document_id,
+		document_id
+	)
+	.fetch_all(pool)
+	.await?;
+
+	flagged_events.extend(analyzer_events.into_iter().map(|e| FlaggedEvent {
+		revision_id: e.revision_id,
+		revision_time: e.revision_time,
+		kind: e.kind,
+	}));
+	flagged_events.sort_by(|a, b| a.revision_time.cmp(&b.revision_time));
+
+	Ok(Some(DocumentStats {
+		doc_id: doc.doc_id,
+		name: doc.name,
+		owner_username: doc.owner_username,
+		total_words,
+		revisions: revisions.len() as i64,
+		sessions: count_sessions(&times),
+		first_edit: times.first().cloned(),
+		last_edit: times.last().cloned(),
+		late_edits,
+		late_added_words,
+		flagged_events,
+		outliers: Vec::new(),
+	}))
+}
+
+// Per-document statistics for a set of documents, plus class-wide distributions of each metric
+pub async fn build_dashboard(pool: &SqlitePool, user_id: i64, document_ids: &[i64]) -> Result<ClassDashboard, sqlx::Error> {
+	let mut documents = Vec::new();
+	for &id in document_ids {
+		if let Some(stats) = document_stats(pool, user_id, id).await? {
+			documents.push(stats);
+		}
+	}
+
+	let mut distributions = BTreeMap::new();
+	for (metric, value) in METRICS {
+		let mut values: Vec<f64> = documents.iter().map(value).collect();
+		let Some(dist) = Distribution::of(&mut values) else {
+			continue;
+		};
+
+		for doc in documents.iter_mut() {
+			if dist.is_outlier(value(doc)) {
+				doc.outliers.push(metric);
+			}
+		}
+		distributions.insert(metric, dist);
+	}
+
+	documents.sort_by(|a, b| a.owner_username.cmp(&b.owner_username).then_with(|| a.name.cmp(&b.name)));
+
+	Ok(ClassDashboard { documents, distributions })
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn empty_has_no_distribution() {
+		assert!(Distribution::of(&mut []).is_none());
+	}
+
+	#[test]
+	fn single_value_is_every_percentile() {
+		let d = Distribution::of(&mut [7.0]).unwrap();
+		assert_eq!((d.min, d.p10, d.median, d.p90, d.max), (7.0, 7.0, 7.0, 7.0, 7.0));
+		assert!(!d.is_outlier(7.0));
+		assert!(d.is_outlier(7.5));
+	}
+
+	#[test]
+	fn percentiles_interpolate_between_ranks() {
+		let d = Distribution::of(&mut [4.0, 1.0, 3.0, 2.0]).unwrap();
+		assert_eq!(d.min, 1.0);
+		assert_eq!(d.p25, 1.75);
+		assert_eq!(d.median, 2.5);
+		assert_eq!(d.p75, 3.25);
+		assert_eq!(d.max, 4.0);
+	}
+
+	#[test]
+	fn outliers_use_tukey_fences() {
+		let d = Distribution::of(&mut [10.0, 11.0, 12.0, 13.0, 14.0]).unwrap();
+		// p25 = 11, p75 = 13, so the fences are 8 and 16
+		assert!(!d.is_outlier(8.0));
+		assert!(!d.is_outlier(16.0));
+		assert!(d.is_outlier(7.9));
+		assert!(d.is_outlier(16.1));
+	}
+
+	#[test]
+	fn sessions_split_on_long_gaps() {
+		let times = |ts: &[&str]| ts.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+		assert_eq!(count_sessions(&[]), 0);
+		assert_eq!(count_sessions(&times(&["2026-01-01T10:00:00Z"])), 1);
+		assert_eq!(count_sessions(&times(&["2026-01-01T10:00:00Z", "2026-01-01T10:20:00Z", "2026-01-01T12:00:00Z"])), 2);
+		assert_eq!(count_sessions(&times(&["not a time"])), 0);
+	}
+}
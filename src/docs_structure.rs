@@ -0,0 +1,286 @@
+use serde::Serialize;
+use serde_json::Value;
+
+// Flattened view of a Docs API `documents.get` response.
+// The text carries no markup of its own, so structure is never diffed or counted as words.
+// Headings, list items and the tabs of a multi-tab document are recorded in the outline by line instead;
+// tabs are also separated by a blank line.
+#[derive(Debug, Default)]
+pub struct StructuredText {
+	pub text: String,
+	pub outline: Vec<OutlineEntry>,
+	pub image_count: i64,
+	pub table_count: i64,
+	pub hidden_runs: Vec<HiddenRun>,
+	lines: usize, // line breaks in `text` so far
+}
+
+impl StructuredText {
+	fn push_text(&mut self, text: &str) {
+		self.lines += text.matches('\n').count();
+		self.text.push_str(text);
+	}
+
+	fn mark_line(&mut self, kind: &'static str, level: usize, title: Option<&str>) {
+		self.outline.push(OutlineEntry { line: self.lines, kind, level, title: title.map(str::to_string) });
+	}
+}
+
+// A structural element starting at a line of the text (0-based)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OutlineEntry {
+	pub line: usize,
+	pub kind: &'static str, // "tab", "heading" or "list_item"
+	pub level: usize,       // heading level, or list nesting level
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub title: Option<String>, // tabs only, since their title isn't part of the text
+}
+
+// Text styled so a reader can't see it, while it still counts as document text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HiddenRun {
+	pub text: String,
+	pub reason: &'static str, // "white_text" or "tiny_text"
+}
+
+// Fonts this small can't be read at normal zoom
+const TINY_FONT_PT: f64 = 2.0;
+
+pub fn render_document(doc: &Value) -> StructuredText {
+	let mut out = StructuredText::default();
+
+	let mut tabs = Vec::new();
+	if let Some(top) = doc["tabs"].as_array() {
+		collect_tabs(top, &mut tabs);
+	}
+
+	if tabs.is_empty() {
+		// Response without tab content (legacy single-body document)
+		render_content(&doc["body"]["content"], &mut out);
+		return out;
+	}
+
+	let multi_tab = tabs.len() > 1;
+	for (i, tab) in tabs.iter().enumerate() {
+		if multi_tab {
+			if i > 0 {
+				out.push_text("\n");
+			}
+			let title = tab["tabProperties"]["title"].as_str().unwrap_or("Untitled Tab");
+			out.mark_line("tab", 0, Some(title));
+		}
+		render_content(&tab["documentTab"]["body"]["content"], &mut out);
+	}
+
+	out
+}
+
+// Tabs can nest; flatten them depth first so child tabs follow their parent
+fn collect_tabs<'a>(tabs: &'a [Value], out: &mut Vec<&'a Value>) {
+	for tab in tabs {
+		out.push(tab);
+		if let Some(children) = tab["childTabs"].as_array() {
+			collect_tabs(children, out);
+		}
+	}
+}
+
+fn render_content(content: &Value, out: &mut StructuredText) {
+	let Some(elements) = content.as_array() else {
+		return;
+	};
+
+	for element in elements {
+		if let Some(paragraph) = element.get("paragraph") {
+			render_paragraph(paragraph, out);
+		} else if let Some(table) = element.get("table") {
+			out.table_count += 1;
+			for row in table["tableRows"].as_array().into_iter().flatten() {
+				for cell in row["tableCells"].as_array().into_iter().flatten() {
+					render_content(&cell["content"], out);
+				}
+			}
+		} else if let Some(toc) = element.get("tableOfContents") {
+			render_content(&toc["content"], out);
+		}
+	}
+}
+
+fn render_paragraph(paragraph: &Value, out: &mut StructuredText) {
+	let mut line = String::new();
+
+	for element in paragraph["elements"].as_array().into_iter().flatten() {
+		if let Some(content) = element["textRun"]["content"].as_str() {
+			line.push_str(content);
+			if let Some(reason) = hidden_reason(&element["textRun"]["textStyle"]) {
+				if !content.trim().is_empty() {
+					out.hidden_runs.push(HiddenRun { text: content.to_string(), reason });
+				}
+			}
+		} else if element.get("inlineObjectElement").is_some() {
+			out.image_count += 1;
+		}
+	}
+
+	if let Some(positioned) = paragraph["positionedObjectIds"].as_array() {
+		out.image_count += positioned.len() as i64;
+	}
+
+	if line.trim().is_empty() {
+		out.push_text(&line);
+		return;
+	}
+
+	let style = paragraph["paragraphStyle"]["namedStyleType"].as_str().unwrap_or("");
+	let heading_level = match style {
+		"TITLE" => Some(1),
+		"SUBTITLE" => Some(2),
+		s => s.strip_prefix("HEADING_").and_then(|n| n.parse::<usize>().ok()),
+	};
+
+	if let Some(level) = heading_level {
+		out.mark_line("heading", level, None);
+	} else if let Some(bullet) = paragraph.get("bullet") {
+		let nesting = bullet["nestingLevel"].as_u64().unwrap_or(0) as usize;
+		out.mark_line("list_item", nesting, None);
+	}
+
+	out.push_text(&line);
+}
+
+// White text without a highlight disappears on the (white) page
+fn hidden_reason(style: &Value) -> Option<&'static str> {
+	if style["fontSize"]["magnitude"].as_f64().is_some_and(|size| size < TINY_FONT_PT) {
+		return Some("tiny_text");
+	}
+
+	// The API leaves out colour components that are zero
+	let rgb = &style["foregroundColor"]["color"]["rgbColor"];
+	let is_white = rgb.is_object()
+		&& ["red", "green", "blue"].iter().all(|c| rgb[*c].as_f64().unwrap_or(0.0) >= 0.95);
+	if is_white && style["backgroundColor"]["color"].is_null() {
+		return Some("white_text");
+	}
+
+	None
+}
+
+// A pending (not yet accepted or rejected) suggested edit
+#[derive(Debug, PartialEq, Eq)]
+pub struct Suggestion {
+	pub id: String,
+	pub kind: &'static str, // "insertion" or "deletion"
+	pub text: String,
+}
+
+// Gathers suggested insertions and deletions from a document fetched with SUGGESTIONS_INLINE.
+// A single suggestion can span several text runs; their text is concatenated in document order.
+pub fn collect_suggestions(doc: &Value) -> Vec<Suggestion> {
+	let mut suggestions: Vec<Suggestion> = Vec::new();
+
+	let mut tabs = Vec::new();
+	if let Some(top) = doc["tabs"].as_array() {
+		collect_tabs(top, &mut tabs);
+	}
+
+	if tabs.is_empty() {
+		collect_suggested_runs(&doc["body"]["content"], &mut suggestions);
+	}
+	for tab in tabs {
+		collect_suggested_runs(&tab["documentTab"]["body"]["content"], &mut suggestions);
+	}
+
+	suggestions
+}
+
+fn collect_suggested_runs(content: &Value, out: &mut Vec<Suggestion>) {
+	for element in content.as_array().into_iter().flatten() {
+		if let Some(paragraph) = element.get("paragraph") {
+			for run in paragraph["elements"].as_array().into_iter().flatten() {
+				let text_run = &run["textRun"];
+				let Some(text) = text_run["content"].as_str() else {
+					continue;
+				};
+
+				for (field, kind) in [("suggestedInsertionIds", "insertion"), ("suggestedDeletionIds", "deletion")] {
+					for id in text_run[field].as_array().into_iter().flatten().filter_map(|v| v.as_str()) {
+						match out.iter_mut().find(|s| s.id == id && s.kind == kind) {
+							Some(existing) => existing.text.push_str(text),
+							None => out.push(Suggestion {
+								id: id.to_string(),
+								kind,
+								text: text.to_string(),
+							}),
+						}
+					}
+				}
+			}
+		} else if let Some(table) = element.get("table") {
+			for row in table["tableRows"].as_array().into_iter().flatten() {
+				for cell in row["tableCells"].as_array().into_iter().flatten() {
+					collect_suggested_runs(&cell["content"], out);
+				}
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use serde_json::json;
+
+	fn paragraph(text: &str, style: &str) -> Value {
+		json!({ "paragraph": {
+			"elements": [{ "textRun": { "content": text } }],
+			"paragraphStyle": { "namedStyleType": style },
+		}})
+	}
+
+	#[test]
+	fn headings_and_lists_are_outlined_not_written_into_the_text() {
+		let mut item = paragraph("Second point\n", "NORMAL_TEXT");
+		item["paragraph"]["bullet"] = json!({ "nestingLevel": 1 });
+		let doc = json!({ "body": { "content": [
+			paragraph("Introduction\n", "HEADING_2"),
+			paragraph("First paragraph.\n", "NORMAL_TEXT"),
+			item,
+		]}});
+
+		let rendered = render_document(&doc);
+		assert_eq!(rendered.text, "Introduction\nFirst paragraph.\nSecond point\n");
+		assert_eq!(
+			rendered.outline,
+			[
+				OutlineEntry { line: 0, kind: "heading", level: 2, title: None },
+				OutlineEntry { line: 2, kind: "list_item", level: 1, title: None },
+			]
+		);
+	}
+
+	#[test]
+	fn tabs_are_separated_and_titled_in_the_outline() {
+		let tab = |title: &str, text: &str| json!({
+			"tabProperties": { "title": title },
+			"documentTab": { "body": { "content": [paragraph(text, "NORMAL_TEXT")] } },
+		});
+		let doc = json!({ "tabs": [tab("Draft", "One.\n"), tab("Notes", "Two.\n")] });
+
+		let rendered = render_document(&doc);
+		assert_eq!(rendered.text, "One.\n\nTwo.\n");
+		assert_eq!(rendered.outline[0], OutlineEntry { line: 0, kind: "tab", level: 0, title: Some("Draft".into()) });
+		assert_eq!(rendered.outline[1], OutlineEntry { line: 2, kind: "tab", level: 0, title: Some("Notes".into()) });
+	}
+
+	#[test]
+	fn tables_and_inline_images_are_counted() {
+		let doc = json!({ "body": { "content": [
+			{ "paragraph": { "elements": [{ "inlineObjectElement": {} }, { "textRun": { "content": "Figure\n" } }] } },
+			{ "table": { "tableRows": [{ "tableCells": [{ "content": [paragraph("Cell\n", "NORMAL_TEXT")] }] }] } },
+		]}});
+
+		let rendered = render_document(&doc);
+		assert_eq!((rendered.image_count, rendered.table_count), (1, 1));
+		assert_eq!(rendered.text, "Figure\nCell\n");
+	}
+}
@@ -0,0 +1,204 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use sqlx::SqlitePool;
+use uuid::Uuid;
+
+use crate::google_api::{list_folder_children, DriveChild, MIME_FOLDER};
+use crate::token_manager::TokenManager;
+
+// Updates every user folder that is bound to a Drive folder.
+// Runs after the poller has inserted new documents, so freshly shared docs can be filed right away.
+pub async fn sync_folder_mirrors(pool: &SqlitePool, tokens: &TokenManager) -> Result<(), sqlx::Error> {
+	let roots = sqlx::query!(
+		r#"
+		SELECT id AS "id!", user_id, drive_folder_id AS "drive_folder_id!"
+		FROM user_documents
+		WHERE is_folder = TRUE AND drive_folder_id IS NOT NULL AND mirror_root_id IS NULL
+		"#
+	)
+	.fetch_all(pool)
+	.await?;
+
+	for root in roots {
+		if let Err(e) = sync_mirror(pool, tokens, &root.id, root.user_id, &root.drive_folder_id).await {
+			eprintln!("⚠️ Failed to mirror Drive folder {}: {}", root.drive_folder_id, e);
+		}
+	}
+
+	Ok(())
+}
+
+// Drive contents of a folder, breadth first, as (parent folder id, child) pairs.
+// Parents always come before their children.
+async fn walk_drive_folder(tokens: &TokenManager, folder_id: &str) -> Result<Vec<(String, DriveChild)>, String> {
+	let mut entries = Vec::new();
+	let mut visited = HashSet::from([folder_id.to_string()]);
+	let mut queue = VecDeque::from([folder_id.to_string()]);
+
+	while let Some(parent) = queue.pop_front() {
+		for child in list_folder_children(tokens, &parent).await? {
+			if child.mime_type == MIME_FOLDER {
+				// Drive items can have several parents; only descend into each folder once
+				if !visited.insert(child.id.clone()) {
+					continue;
+				}
+				queue.push_back(child.id.clone());
+			}
+			entries.push((parent.clone(), child));
+		}
+	}
+
+	Ok(entries)
+}
+
+async fn sync_mirror(
+	pool: &SqlitePool,
+	tokens: &TokenManager,
+	root_id: &str,
+	user_id: i64,
+	drive_folder_id: &str,
+) -> Result<(), String> {
+	// List everything before touching the DB, so a failed request never removes rows
+	let tree = walk_drive_folder(tokens, drive_folder_id).await?;
+
+	let db_err = |e: sqlx::Error| format!("DB error: {}", e);
+
+	let existing_folders = sqlx::query!(
+		r#"
+		SELECT id AS "id!", drive_folder_id
+		FROM user_documents
+		WHERE mirror_root_id = ? AND is_folder = TRUE
+		"#,
+		root_id
+	)
+	.fetch_all(pool)
+	.await
+	.map_err(db_err)?;
+
+	let mut existing: HashMap<String, String> = existing_folders
+		.into_iter()
+		.filter_map(|row| row.drive_folder_id.map(|drive_id| (drive_id, row.id)))
+		.collect();
+
+	// Drive folder id -> user_documents id of its mirror
+	let mut folder_rows: HashMap<String, String> = HashMap::from([(drive_folder_id.to_string(), root_id.to_string())]);
+	let mut seen_docs: HashSet<i64> = HashSet::new();
+
+	for (parent, child) in &tree {
+		let Some(parent_row) = folder_rows.get(parent).cloned() else {
+			continue;
+		};
+
+		if child.mime_type == MIME_FOLDER {
+			// Follow renames and moves of folders that are already mirrored
+			let row_id = match existing.remove(&child.id) {
+				Some(row_id) => {
+					sqlx::query!(
+						"UPDATE user_documents SET folder_name = ?, id_parent = ? WHERE id = ?",
+						child.name,
+						parent_row,
+						row_id
+					)
+					.execute(pool)
+					.await
+					.map_err(db_err)?;
+					row_id
+				}
+				None => {
+					let row_id = Uuid::new_v4().to_string();
+					sqlx::query!(
+						"INSERT INTO user_documents (id, user_id, is_folder, folder_name, id_parent, drive_folder_id, mirror_root_id)
+						VALUES (?, ?, TRUE, ?, ?, ?, ?)",
+						row_id,
+						user_id,
+						child.name,
+						parent_row,
+						child.id,
+						root_id
+					)
+					.execute(pool)
+					.await
+					.map_err(db_err)?;
+					row_id
+				}
+			};
+			folder_rows.insert(child.id.clone(), row_id);
+			continue;
+		}
+
+		// Only documents the poller already tracks can be watched; others are picked up on a later poll
+		let document_id = sqlx::query_scalar!(
+			r#"SELECT id AS "id!" FROM documents WHERE doc_id = ?"#,
+			child.id
+		)
+		.fetch_optional(pool)
+		.await
+		.map_err(db_err)?;
+
+		let Some(document_id) = document_id else {
+			continue;
+		};
+
+		// A doc the user already filed by hand stays where they put it
+		let row_id = Uuid::new_v4().to_string();
+		sqlx::query!(
+			"INSERT INTO user_documents (id, user_id, document_id, id_parent, mirror_root_id)
+			VALUES (?, ?, ?, ?, ?)
+			ON CONFLICT(user_id, document_id) DO UPDATE SET id_parent = excluded.id_parent
+			WHERE user_documents.mirror_root_id = excluded.mirror_root_id",
+			row_id,
+			user_id,
+			document_id,
+			parent_row,
+			root_id
+		)
+		.execute(pool)
+		.await
+		.map_err(db_err)?;
+
+		seen_docs.insert(document_id);
+	}
+
+	// Documents removed from the Drive folder leave the watchlist
+	let mirrored_docs = sqlx::query!(
+		r#"
+		SELECT id AS "id!", document_id
+		FROM user_documents
+		WHERE mirror_root_id = ? AND is_folder = FALSE
+		"#,
+		root_id
+	)
+	.fetch_all(pool)
+	.await
+	.map_err(db_err)?;
+
+	for row in mirrored_docs {
+		if row.document_id.is_some_and(|id| seen_docs.contains(&id)) {
+			continue;
+		}
+		sqlx::query!("DELETE FROM user_documents WHERE id = ?", row.id)
+			.execute(pool)
+			.await
+			.map_err(db_err)?;
+	}
+
+	// Whatever is left in `existing` no longer exists in Drive
+	for row_id in existing.into_values() {
+		// Keep anything the user filed into the folder by hand
+		sqlx::query!(
+			"UPDATE user_documents SET id_parent = ? WHERE id_parent = ? AND mirror_root_id IS NULL",
+			root_id,
+			row_id
+		)
+		.execute(pool)
+		.await
+		.map_err(db_err)?;
+
+		sqlx::query!("DELETE FROM user_documents WHERE id = ?", row_id)
+			.execute(pool)
+			.await
+			.map_err(db_err)?;
+	}
+
+	Ok(())
+}
@@ -1,5 +1,4 @@
 use serde_json::Value;
-use reqwest;
 use std::collections::HashMap;
 use anyhow::{anyhow, Result};
 use zip::ZipArchive;
@@ -7,40 +6,21 @@ use quick_xml::events::Event;
 use quick_xml::Reader;
 use std::io::{Cursor, Read};
 
-pub async fn get_access_token() -> Result<String, reqwest::Error> {
-	let client_id = std::env::var("GOOGLE_CLIENT_ID").unwrap();
-	let client_secret = std::env::var("GOOGLE_CLIENT_SECRET").unwrap();
-	let refresh_token = std::fs::read_to_string("google_token.json")
-		.ok()
-		.and_then(|data| serde_json::from_str::<Value>(&data).ok())
-		.and_then(|v| v["refresh_token"].as_str().map(|s| s.to_string()))
-		.expect("Missing refresh token");
-
-	let params = [
-		("client_id".to_string(), client_id),
-		("client_secret".to_string(), client_secret),
-		("refresh_token".to_string(), refresh_token),
-		("grant_type".to_string(), "refresh_token".to_string()),
-	];
-
-	let res = reqwest::Client::new()
-		.post("https://oauth2.googleapis.com/token")
-		.form(&params)
-		.send()
-		.await?
-		.json::<Value>()
-		.await?;
-
-	Ok(res["access_token"].as_str().unwrap().to_string())
-}
+use crate::token_manager::{DriveAccess, TokenManager};
+use crate::docs_structure::{render_document, collect_suggestions, HiddenRun, Suggestion};
 
 // unused but keep for later in case useful
 // originally used for finding which docs to monitor
 // just sharing the doc with an admin user is more streamlined
 // no need to mess around with document IDs
-// TODO: remove edit capability for service user
-pub async fn add_docwatch_property(file_id: &str) -> Result<()> {
-	let token = get_access_token().await?;
+// Writes to Drive, so it is refused unless GOOGLE_DRIVE_ACCESS=full
+#[allow(dead_code)]
+pub async fn add_docwatch_property(tokens: &TokenManager, file_id: &str) -> Result<()> {
+	if tokens.drive_access() != DriveAccess::Full {
+		return Err(anyhow!("Docwatch is running with read-only Drive access"));
+	}
+
+	let token = tokens.access_token().await?;
 
 	let url = format!(
 		"https://www.googleapis.com/drive/v3/files/{}?supportsAllDrives=true",
@@ -70,57 +50,195 @@ pub async fn add_docwatch_property(file_id: &str) -> Result<()> {
 	}
 }
 
-pub async fn get_google_docs() -> Result<HashMap<String, (String, String, String, String, String)>, reqwest::Error> {
-	let token = get_access_token().await?;
+// A watchable file as listed by Drive
+#[derive(Debug, Clone)]
+pub struct DriveFile {
+	pub name: String,
+	pub modified_time: String,
+	pub export_link: String, // may be blank for DOCX and PDF
+	pub owner_username: String,
+	pub owner_email: Option<String>,
+	pub mime_type: String,
+	pub drive_id: Option<String>, // set for files living in a shared drive
+}
+
+const WATCHED_FILES_QUERY: &str = "(mimeType='application/vnd.google-apps.document'+or+mimeType='application/vnd.openxmlformats-officedocument.wordprocessingml.document'+or+mimeType='application/pdf')+and+trashed=false";
+
+// Files in My Drive or shared directly with the Docwatch account, including files shared
+// from a shared drive the account isn't a member of
+pub async fn get_google_docs(tokens: &TokenManager) -> Result<HashMap<String, DriveFile>, String> {
+	list_files(tokens, "corpora=user&includeItemsFromAllDrives=true").await
+}
+
+// Files in one shared drive; only called for drives an admin has enabled
+pub async fn get_shared_drive_docs(tokens: &TokenManager, drive_id: &str) -> Result<HashMap<String, DriveFile>, String> {
+	list_files(
+		tokens,
+		&format!("corpora=drive&driveId={}&includeItemsFromAllDrives=true", urlencoding::encode(drive_id)),
+	)
+	.await
+}
+
+async fn list_files(tokens: &TokenManager, corpus: &str) -> Result<HashMap<String, DriveFile>, String> {
 	let mut results = HashMap::new();
 	let mut page_token: Option<String> = None;
 
 	loop {
 		let mut url = format!(
-			"https://www.googleapis.com/drive/v3/files?q=(mimeType='application/vnd.google-apps.document'+or+mimeType='application/vnd.openxmlformats-officedocument.wordprocessingml.document')+and+trashed=false&fields=files(id,name,modifiedTime,mimeType,owners(displayName,emailAddress),exportLinks),nextPageToken&supportsAllDrives=true&pageSize=1000"
+			"https://www.googleapis.com/drive/v3/files?q={}&{}&fields=files({}),nextPageToken&supportsAllDrives=true&pageSize=1000",
+			WATCHED_FILES_QUERY,
+			corpus,
+			FILE_FIELDS
 		);
 
 		if let Some(token) = &page_token {
 			url.push_str(&format!("&pageToken={}", token));
 		}
 
-		let res = reqwest::Client::new()
-			.get(&url)
-			.bearer_auth(&token)
-			.send()
-			.await?
-			.json::<serde_json::Value>()
-			.await?;
+		let res = get_json(tokens, &url).await?;
 
 		if let Some(files) = res["files"].as_array() {
-			for file in files {
-				let id = file.get("id").and_then(|v| v.as_str()).unwrap_or("");
-				let name = file.get("name").and_then(|v| v.as_str()).unwrap_or("Untitled");
-				let modified = file.get("modifiedTime").and_then(|v| v.as_str()).unwrap_or("");
-				let mime_type = file.get("mimeType").and_then(|v| v.as_str()).unwrap_or("");
-				let export_link = file["exportLinks"]["text/plain"].as_str().unwrap_or("");
-
-				let owner_username = file["owners"]
-					.as_array()
-					.and_then(|owners| owners.get(0))
-					.and_then(|owner| {
-						owner.get("displayName").and_then(|v| v.as_str())
-							.or_else(|| owner.get("emailAddress").and_then(|v| v.as_str()))
-					})
-					.unwrap_or("unknown");
-
-				if !id.is_empty() && !modified.is_empty() {
-					results.insert(
-						id.to_string(),
-						(
-							name.to_string(),
-							modified.to_string(),
-							export_link.to_string(), // may be blank for DOCX
-							owner_username.to_string(),
-							mime_type.to_string()
-						)
-					);
-				}
+			results.extend(files.iter().filter_map(parse_drive_file));
+		}
+
+		page_token = res["nextPageToken"].as_str().map(|s| s.to_string());
+		if page_token.is_none() {
+			break;
+		}
+	}
+
+	Ok(results)
+}
+
+const FILE_FIELDS: &str = "id,name,modifiedTime,mimeType,driveId,owners(displayName,emailAddress),exportLinks";
+
+fn parse_drive_file(file: &Value) -> Option<(String, DriveFile)> {
+	let id = file.get("id").and_then(|v| v.as_str()).unwrap_or("");
+	let name = file.get("name").and_then(|v| v.as_str()).unwrap_or("Untitled");
+	let modified = file.get("modifiedTime").and_then(|v| v.as_str()).unwrap_or("");
+	let mime_type = file.get("mimeType").and_then(|v| v.as_str()).unwrap_or("");
+	let export_link = file["exportLinks"]["text/plain"].as_str().unwrap_or("");
+
+	// Shared drive files have no owners; the drive owns them
+	let owner = file["owners"].as_array().and_then(|owners| owners.first());
+	let owner_username = owner
+		.and_then(|owner| {
+			owner.get("displayName").and_then(|v| v.as_str())
+				.or_else(|| owner.get("emailAddress").and_then(|v| v.as_str()))
+		})
+		.unwrap_or("unknown");
+	let owner_email = owner.and_then(|owner| owner.get("emailAddress")).and_then(|v| v.as_str());
+
+	if id.is_empty() || modified.is_empty() {
+		return None;
+	}
+
+	Some((
+		id.to_string(),
+		DriveFile {
+			name: name.to_string(),
+			modified_time: modified.to_string(),
+			export_link: export_link.to_string(),
+			owner_username: owner_username.to_string(),
+			owner_email: owner_email.map(|s| s.to_string()),
+			mime_type: mime_type.to_string(),
+			drive_id: file["driveId"].as_str().map(|s| s.to_string()),
+		},
+	))
+}
+
+// Current metadata of a single file, for fetching one document outside the regular poll
+pub async fn get_file(tokens: &TokenManager, file_id: &str) -> Result<DriveFile, String> {
+	let url = format!(
+		"https://www.googleapis.com/drive/v3/files/{}?fields={}&supportsAllDrives=true",
+		file_id,
+		FILE_FIELDS
+	);
+	let res = get_json(tokens, &url).await?;
+
+	parse_drive_file(&res)
+		.map(|(_, file)| file)
+		.ok_or_else(|| format!("Incomplete metadata for file {}", file_id))
+}
+
+pub const MIME_FOLDER: &str = "application/vnd.google-apps.folder";
+
+#[derive(Debug, Clone)]
+pub struct DriveChild {
+	pub id: String,
+	pub name: String,
+	pub mime_type: String,
+}
+
+// Direct children of a Drive folder (files and subfolders), excluding trashed items
+pub async fn list_folder_children(tokens: &TokenManager, folder_id: &str) -> Result<Vec<DriveChild>, String> {
+	let mut results = Vec::new();
+	let mut page_token: Option<String> = None;
+
+	loop {
+		let mut url = format!(
+			"https://www.googleapis.com/drive/v3/files?q={}&fields=files(id,name,mimeType),nextPageToken&supportsAllDrives=true&includeItemsFromAllDrives=true&pageSize=1000",
+			urlencoding::encode(&format!("'{}' in parents and trashed=false", folder_id.replace('\'', "\\'")))
+		);
+
+		if let Some(token) = &page_token {
+			url.push_str(&format!("&pageToken={}", token));
+		}
+
+		let res = get_json(tokens, &url).await?;
+
+		for file in res["files"].as_array().into_iter().flatten() {
+			if let Some(id) = file["id"].as_str() {
+				results.push(DriveChild {
+					id: id.to_string(),
+					name: file["name"].as_str().unwrap_or("Untitled").to_string(),
+					mime_type: file["mimeType"].as_str().unwrap_or("").to_string(),
+				});
+			}
+		}
+
+		page_token = res["nextPageToken"].as_str().map(|s| s.to_string());
+		if page_token.is_none() {
+			break;
+		}
+	}
+
+	Ok(results)
+}
+
+// Name of a Drive folder, or an error if the id is not a folder Docwatch can see
+pub async fn get_folder_name(tokens: &TokenManager, folder_id: &str) -> Result<String, String> {
+	let url = format!(
+		"https://www.googleapis.com/drive/v3/files/{}?fields=name,mimeType&supportsAllDrives=true",
+		urlencoding::encode(folder_id)
+	);
+	let file = get_json(tokens, &url).await?;
+
+	if file["mimeType"].as_str() != Some(MIME_FOLDER) {
+		return Err(format!("{} is not a folder", folder_id));
+	}
+
+	Ok(file["name"].as_str().unwrap_or("Untitled Folder").to_string())
+}
+
+// Shared drives the Docwatch account is a member of, as (id, name)
+pub async fn list_shared_drives(tokens: &TokenManager) -> Result<Vec<(String, String)>, String> {
+	let mut results = Vec::new();
+	let mut page_token: Option<String> = None;
+
+	loop {
+		let mut url = String::from("https://www.googleapis.com/drive/v3/drives?pageSize=100&fields=drives(id,name),nextPageToken");
+
+		if let Some(token) = &page_token {
+			url.push_str(&format!("&pageToken={}", token));
+		}
+
+		let res = get_json(tokens, &url).await?;
+
+		for drive in res["drives"].as_array().into_iter().flatten() {
+			if let Some(id) = drive["id"].as_str() {
+				let name = drive["name"].as_str().unwrap_or("Untitled Drive");
+				results.push((id.to_string(), name.to_string()));
 			}
 		}
 
@@ -133,8 +251,8 @@ pub async fn get_google_docs() -> Result<HashMap<String, (String, String, String
 	Ok(results)
 }
 
-pub async fn get_google_text(export_link: &str) -> Result<String, String> {
-	let token = get_access_token().await.map_err(|e| format!("Token error: {}", e))?;
+pub async fn get_google_text(tokens: &TokenManager, export_link: &str) -> Result<String, String> {
+	let token = tokens.access_token().await.map_err(|e| format!("Token error: {}", e))?;
 
 	let client = reqwest::Client::new();
 	let text_resp = client
@@ -146,6 +264,7 @@ pub async fn get_google_text(export_link: &str) -> Result<String, String> {
 
 	if !text_resp.status().is_success() {
 		let status = text_resp.status();
+		forget_rejected_token(tokens, status).await;
 		let err_body = text_resp.text().await.unwrap_or_else(|_| "<unreadable>".into());
 		return Err(format!(
 			"Text export failed: {}\n{}",
@@ -160,24 +279,244 @@ pub async fn get_google_text(export_link: &str) -> Result<String, String> {
 		.map_err(|e| format!("Failed to decode text: {}", e))
 }
 
-pub async fn get_docx_text(file_id: &str) -> Result<String, String> {
-	let token = get_access_token().await.map_err(|e| format!("Token error: {}", e))?;
+pub const MIME_GOOGLE_DOC: &str = "application/vnd.google-apps.document";
+pub const MIME_DOCX: &str = "application/vnd.openxmlformats-officedocument.wordprocessingml.document";
+pub const MIME_PDF: &str = "application/pdf";
+
+// How Google Docs are fetched, set with GOOGLE_DOCS_FETCH_MODE in .env
+// "plain" (default) uses the text/plain export link,
+// "structured" reads the Docs API JSON and keeps headings, lists, tabs and image/table counts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FetchMode {
+	Plain,
+	Structured,
+}
+
+impl FetchMode {
+	pub fn from_env() -> Self {
+		match std::env::var("GOOGLE_DOCS_FETCH_MODE").as_deref() {
+			Ok("structured") => FetchMode::Structured,
+			_ => FetchMode::Plain,
+		}
+	}
+}
+
+// Extracted content of one document version.
+// The outline, image and table counts, and text hidden by formatting are only known for structured fetches.
+#[derive(Debug, Default)]
+pub struct FetchedDocument {
+	pub text: String,
+	pub outline: Option<String>, // JSON list of docs_structure::OutlineEntry
+	pub image_count: Option<i64>,
+	pub table_count: Option<i64>,
+	pub hidden_runs: Vec<HiddenRun>,
+}
+
+impl From<String> for FetchedDocument {
+	fn from(text: String) -> Self {
+		FetchedDocument { text, ..Default::default() }
+	}
+}
+
+// Fetches the text of a document, picking the extraction path by MIME type
+pub async fn get_document_text(tokens: &TokenManager, file_id: &str, mime_type: &str, export_link: &str) -> Result<FetchedDocument, String> {
+	match mime_type {
+		MIME_GOOGLE_DOC => match FetchMode::from_env() {
+			FetchMode::Plain => get_google_text(tokens, export_link).await.map(Into::into),
+			FetchMode::Structured => get_google_structured(tokens, file_id).await,
+		},
+		MIME_DOCX => get_docx_text(tokens, file_id).await.map(Into::into),
+		MIME_PDF => get_pdf_text(tokens, file_id).await.map(Into::into),
+		other => Err(format!("Unsupported MIME type: {}", other)),
+	}
+}
+
+// Reads the document through the Docs API instead of the plain text export.
+// This avoids the export size limit and includes every tab of multi-tab documents.
+pub async fn get_google_structured(tokens: &TokenManager, file_id: &str) -> Result<FetchedDocument, String> {
+	let url = format!(
+		"https://docs.googleapis.com/v1/documents/{}?includeTabsContent=true&suggestionsViewMode=PREVIEW_WITHOUT_SUGGESTIONS",
+		file_id
+	);
+	let doc = get_json(tokens, &url).await?;
+
+	let rendered = render_document(&doc);
+
+	Ok(FetchedDocument {
+		text: rendered.text,
+		outline: serde_json::to_string(&rendered.outline).ok(),
+		image_count: Some(rendered.image_count),
+		table_count: Some(rendered.table_count),
+		hidden_runs: rendered.hidden_runs,
+	})
+}
+
+// Open suggestions in a Google Doc, read with suggestions shown inline.
+// The Docs API does not say who made a suggestion, only what it changes.
+pub async fn get_google_suggestions(tokens: &TokenManager, file_id: &str) -> Result<Vec<Suggestion>, String> {
+	let url = format!(
+		"https://docs.googleapis.com/v1/documents/{}?includeTabsContent=true&suggestionsViewMode=SUGGESTIONS_INLINE",
+		file_id
+	);
+	let doc = get_json(tokens, &url).await?;
+
+	Ok(collect_suggestions(&doc))
+}
+
+#[derive(Debug)]
+pub struct DriveComment {
+	pub id: String,
+	pub parent_id: Option<String>, // set for replies
+	pub author_name: String,
+	pub author_email: Option<String>,
+	pub content: String,
+	pub quoted_text: Option<String>,
+	pub created_time: String,
+	pub modified_time: String,
+	pub resolved: bool,
+	pub deleted: bool,
+}
+
+// Comments and their replies, flattened; replies carry the id of their comment
+// A comment's modifiedTime also moves when a reply is added, so `modified_since` still finds new replies
+pub async fn get_comments(
+	tokens: &TokenManager,
+	file_id: &str,
+	modified_since: Option<&str>,
+) -> Result<Vec<DriveComment>, String> {
+	let mut results = Vec::new();
+	let mut page_token: Option<String> = None;
+
+	loop {
+		let mut url = format!(
+			"https://www.googleapis.com/drive/v3/files/{}/comments?includeDeleted=true&pageSize=100&fields=comments(id,author(displayName,emailAddress),content,quotedFileContent,createdTime,modifiedTime,resolved,deleted,replies(id,author(displayName,emailAddress),content,createdTime,modifiedTime,deleted)),nextPageToken",
+			file_id
+		);
+
+		if let Some(since) = modified_since {
+			url.push_str(&format!("&startModifiedTime={}", urlencoding::encode(since)));
+		}
+
+		if let Some(token) = &page_token {
+			url.push_str(&format!("&pageToken={}", token));
+		}
+
+		let res = get_json(tokens, &url).await?;
+
+		for comment in res["comments"].as_array().into_iter().flatten() {
+			let Some(comment_id) = comment["id"].as_str() else {
+				continue;
+			};
+
+			results.push(parse_comment(comment, None, comment["resolved"].as_bool().unwrap_or(false)));
+
+			for reply in comment["replies"].as_array().into_iter().flatten() {
+				if reply["id"].as_str().is_some() {
+					results.push(parse_comment(reply, Some(comment_id), false));
+				}
+			}
+		}
+
+		page_token = res["nextPageToken"].as_str().map(|s| s.to_string());
+		if page_token.is_none() {
+			break;
+		}
+	}
+
+	Ok(results)
+}
+
+fn parse_comment(value: &Value, parent_id: Option<&str>, resolved: bool) -> DriveComment {
+	let author = &value["author"];
+
+	DriveComment {
+		id: value["id"].as_str().unwrap_or("").to_string(),
+		parent_id: parent_id.map(|s| s.to_string()),
+		author_name: author["displayName"].as_str().unwrap_or("unknown").to_string(),
+		author_email: author["emailAddress"].as_str().map(|s| s.to_string()),
+		content: value["content"].as_str().unwrap_or("").to_string(),
+		quoted_text: value["quotedFileContent"]["value"].as_str().map(|s| s.to_string()),
+		created_time: value["createdTime"].as_str().unwrap_or("").to_string(),
+		modified_time: value["modifiedTime"].as_str().unwrap_or("").to_string(),
+		resolved,
+		deleted: value["deleted"].as_bool().unwrap_or(false),
+	}
+}
+
+// A rejected access token was revoked or replaced before it expired; the next request gets a new one
+async fn forget_rejected_token(tokens: &TokenManager, status: reqwest::StatusCode) {
+	if status == reqwest::StatusCode::UNAUTHORIZED {
+		tokens.invalidate().await;
+	}
+}
+
+async fn get_json(tokens: &TokenManager, url: &str) -> Result<Value, String> {
+	let token = tokens.access_token().await.map_err(|e| format!("Token error: {}", e))?;
+
+	let resp = reqwest::Client::new()
+		.get(url)
+		.bearer_auth(&token)
+		.send()
+		.await
+		.map_err(|e| format!("Request failed: {}", e))?;
+
+	if !resp.status().is_success() {
+		let status = resp.status();
+		forget_rejected_token(tokens, status).await;
+		let err_body = resp.text().await.unwrap_or_else(|_| "<unreadable>".into());
+		return Err(format!(
+			"Google API request failed: {}\n{}",
+			status,
+			err_body
+		));
+	}
+
+	resp
+		.json::<Value>()
+		.await
+		.map_err(|e| format!("Failed to decode response: {}", e))
+}
+
+async fn download_file(tokens: &TokenManager, file_id: &str) -> Result<Vec<u8>, String> {
+	let token = tokens.access_token().await.map_err(|e| format!("Token error: {}", e))?;
 
 	let url = format!(
 		"https://www.googleapis.com/drive/v3/files/{}?alt=media&supportsAllDrives=true",
 		file_id
 	);
 
-	let bytes = reqwest::Client::new()
+	let resp = reqwest::Client::new()
 		.get(&url)
 		.bearer_auth(&token)
 		.send()
 		.await
-		.map_err(|e| format!("Failed to download DOCX: {}", e))?
+		.map_err(|e| format!("Failed to download file: {}", e))?;
+
+	if !resp.status().is_success() {
+		let status = resp.status();
+		forget_rejected_token(tokens, status).await;
+		let err_body = resp.text().await.unwrap_or_else(|_| "<unreadable>".into());
+		return Err(format!(
+			"File download failed: {}\n{}",
+			status,
+			err_body
+		));
+	}
+
+	resp
 		.bytes()
 		.await
-		.map_err(|e| format!("Failed to read DOCX bytes: {}", e))?;
+		.map(|b| b.to_vec())
+		.map_err(|e| format!("Failed to read file bytes: {}", e))
+}
 
+pub async fn get_docx_text(tokens: &TokenManager, file_id: &str) -> Result<String, String> {
+	let bytes = download_file(tokens, file_id).await?;
+	extract_docx_text(bytes)
+}
+
+// Plain text of a DOCX file's main document part
+pub fn extract_docx_text(bytes: Vec<u8>) -> Result<String, String> {
 	let reader = Cursor::new(bytes);
 	let mut archive = ZipArchive::new(reader).map_err(|e| format!("Failed to open DOCX zip: {}", e))?;
 
@@ -205,3 +544,13 @@ pub async fn get_docx_text(file_id: &str) -> Result<String, String> {
 
 	Ok(text)
 }
+
+pub async fn get_pdf_text(tokens: &TokenManager, file_id: &str) -> Result<String, String> {
+	let bytes = download_file(tokens, file_id).await?;
+
+	// pdf-extract is CPU bound and can panic on malformed files, so keep it off the runtime
+	tokio::task::spawn_blocking(move || pdf_extract::extract_text_from_mem(&bytes))
+		.await
+		.map_err(|e| format!("PDF extraction aborted: {}", e))?
+		.map_err(|e| format!("Failed to extract PDF text: {}", e))
+}
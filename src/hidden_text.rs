@@ -0,0 +1,285 @@
+use std::collections::{HashMap, HashSet};
+
+use sqlx::SqlitePool;
+
+use crate::docs_structure::HiddenRun;
+
+// Characters shown on either side of a finding
+const CONTEXT_CHARS: usize = 20;
+
+#[derive(Debug, Clone)]
+pub struct Finding {
+	pub kind: &'static str,
+	pub char_offset: Option<i64>,
+	pub matched: String,
+	pub context: String,
+}
+
+// Characters that take up no space, often used to split words so they aren't matched
+fn is_zero_width(c: char) -> bool {
+	matches!(c, '\u{200B}' | '\u{200C}' | '\u{200D}' | '\u{2060}' | '\u{FEFF}' | '\u{00AD}' | '\u{180E}')
+}
+
+// Characters that reorder how text is displayed without changing what is stored
+fn is_bidi_control(c: char) -> bool {
+	matches!(c, '\u{200E}' | '\u{200F}' | '\u{061C}' | '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}')
+}
+
+// Blank characters other than ordinary spaces, usable as invisible padding
+fn is_unusual_space(c: char) -> bool {
+	matches!(
+		c,
+		'\u{2000}'..='\u{200A}' | '\u{205F}' | '\u{3000}' | '\u{2800}' | '\u{3164}' | '\u{115F}' | '\u{1160}' | '\u{FFA0}'
+	)
+}
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy)]
+enum Script {
+	Latin,
+	Cyrillic,
+	Greek,
+}
+
+fn script(c: char) -> Option<Script> {
+	match c {
+		'a'..='z' | 'A'..='Z' | '\u{00C0}'..='\u{024F}' => Some(Script::Latin),
+		'\u{0400}'..='\u{04FF}' => Some(Script::Cyrillic),
+		'\u{0370}'..='\u{03FF}' => Some(Script::Greek),
+		_ => None,
+	}
+}
+
+// Invisible characters written out as code points, so findings can be read
+fn spell_out(text: &str) -> String {
+	text.chars()
+		.map(|c| {
+			if is_zero_width(c) || is_bidi_control(c) || is_unusual_space(c) {
+				format!("<U+{:04X}>", c as u32)
+			} else {
+				c.to_string()
+			}
+		})
+		.collect()
+}
+
+// Non-Latin letters of a mixed-script word written out too, since they look like Latin ones
+fn spell_out_word(word: &[char]) -> String {
+	word.iter()
+		.map(|c| match script(*c) {
+			Some(Script::Cyrillic | Script::Greek) => format!("<U+{:04X}>", *c as u32),
+			_ => spell_out(&c.to_string()),
+		})
+		.collect()
+}
+
+fn context(chars: &[char], start: usize, end: usize) -> String {
+	let from = start.saturating_sub(CONTEXT_CHARS);
+	let to = (end + CONTEXT_CHARS).min(chars.len());
+	spell_out(&chars[from..to].iter().collect::<String>())
+}
+
+// Scans a revision's text for characters and words meant to fool word counts or similarity checks.
+// Consecutive characters of the same kind are reported as one finding.
+pub fn scan_text(content: &str) -> Vec<Finding> {
+	let chars: Vec<char> = content.chars().collect();
+	let mut findings = Vec::new();
+
+	let mut i = 0;
+	while i < chars.len() {
+		let kind = match chars[i] {
+			c if is_zero_width(c) => "zero_width",
+			c if is_bidi_control(c) => "bidi_control",
+			c if is_unusual_space(c) => "unusual_space",
+			_ => {
+				i += 1;
+				continue;
+			}
+		};
+
+		let start = i;
+		while i < chars.len() && chars[i] == chars[start] {
+			i += 1;
+		}
+		findings.push(Finding {
+			kind,
+			char_offset: Some(start as i64),
+			matched: spell_out(&chars[start..i].iter().collect::<String>()),
+			context: context(&chars, start, i),
+		});
+	}
+
+	// Words mixing Latin letters with look-alike Cyrillic or Greek ones
+	let mut start = None;
+	for i in 0..=chars.len() {
+		let in_word = i < chars.len() && (chars[i].is_alphanumeric() || is_zero_width(chars[i]));
+		match (in_word, start) {
+			(true, None) => start = Some(i),
+			(false, Some(s)) => {
+				let scripts: HashSet<Script> = chars[s..i].iter().filter_map(|c| script(*c)).collect();
+				if scripts.len() > 1 && scripts.contains(&Script::Latin) {
+					findings.push(Finding {
+						kind: "homoglyph",
+						char_offset: Some(s as i64),
+						matched: spell_out_word(&chars[s..i]),
+						context: context(&chars, s, i),
+					});
+				}
+				start = None;
+			}
+			_ => {}
+		}
+	}
+
+	findings.sort_by_key(|f| f.char_offset);
+	findings
+}
+
+// Text the Docs API reported as white-on-white or too small to read
+pub fn formatting_findings(runs: &[HiddenRun]) -> Vec<Finding> {
+	runs.iter()
+		.map(|run| Finding {
+			kind: "hidden_formatting",
+			char_offset: None,
+			matched: run.reason.to_string(),
+			context: spell_out(&run.text.chars().take(2 * CONTEXT_CHARS).collect::<String>()),
+		})
+		.collect()
+}
+
+// Formatting findings have no position, so their text tells them apart
+fn finding_key(finding: &Finding) -> (String, String) {
+	let key = if finding.kind == "hidden_formatting" { &finding.context } else { &finding.matched };
+	(finding.kind.to_string(), key.clone())
+}
+
+// Which findings the previous revision didn't have. The same character can be inserted again,
+// so findings are counted per kind and text: any beyond the previous count are new.
+fn mark_new(previous: &[(String, String)], findings: &[Finding]) -> Vec<bool> {
+	let mut remaining: HashMap<&(String, String), usize> = HashMap::new();
+	for key in previous {
+		*remaining.entry(key).or_default() += 1;
+	}
+
+	findings
+		.iter()
+		.map(|finding| match remaining.get_mut(&finding_key(finding)) {
+			Some(count) if *count > 0 => {
+				*count -= 1;
+				false
+			}
+			_ => true,
+		})
+		.collect()
+}
+
+// Stores a revision's findings, marking those its document's previous revision didn't have
+pub async fn record_findings(
+	pool: &SqlitePool,
+	document_id: i64,
+	revision_id: i64,
+	findings: &[Finding],
+) -> Result<(), sqlx::Error> {
+	if findings.is_empty() {
+		return Ok(());
+	}
+
+	let previous = sqlx::query!(
+		r#"
+		SELECT f.kind, f.matched, f.context
+		FROM hidden_text_findings f
+		WHERE f.revision_id = (
+			SELECT id FROM document_revisions
+			WHERE document_id = ? AND id < ?
+			ORDER BY revision_time DESC, id DESC
+			LIMIT 1
+		)
+		"#,
+		document_id,
+		revision_id
+	)
+	.fetch_all(pool)
+	.await?;
+
+	let previous: Vec<(String, String)> = previous
+		.into_iter()
+		.map(|p| {
+			let key = if p.kind == "hidden_formatting" { p.context } else { p.matched };
+			(p.kind, key)
+		})
+		.collect();
+	let new = mark_new(&previous, findings);
+
+	let mut tx = pool.begin().await?;
+	for (finding, is_new) in findings.iter().zip(new) {
+		sqlx::query!(
+			"INSERT INTO hidden_text_findings (revision_id, kind, char_offset, matched, context, is_new) VALUES (?, ?, ?, ?, ?, ?)",
+			revision_id,
+			finding.kind,
+			finding.char_offset,
+			finding.matched,
+			finding.context,
+			is_new
+		)
+		.execute(&mut *tx)
+		.await?;
+	}
+	tx.commit().await?;
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn kinds(findings: &[Finding]) -> Vec<(&'static str, Option<i64>, &str)> {
+		findings.iter().map(|f| (f.kind, f.char_offset, f.matched.as_str())).collect()
+	}
+
+	#[test]
+	fn clean_and_empty_text_have_no_findings() {
+		assert!(scan_text("").is_empty());
+		assert!(scan_text("Plain text, with ünïcode and 東京 and Ελληνικά words.").is_empty());
+	}
+
+	#[test]
+	fn invisible_runs_are_one_finding_at_char_offsets() {
+		let findings = scan_text("café\u{200B}\u{200B}word\u{202E}x\u{3000}");
+		assert_eq!(
+			kinds(&findings),
+			[
+				("zero_width", Some(4), "<U+200B><U+200B>"),
+				("bidi_control", Some(10), "<U+202E>"),
+				("unusual_space", Some(12), "<U+3000>"),
+			]
+		);
+	}
+
+	#[test]
+	fn different_invisible_characters_are_separate_findings() {
+		let findings = scan_text("a\u{200B}\u{200C}b");
+		assert_eq!(kinds(&findings), [("zero_width", Some(1), "<U+200B>"), ("zero_width", Some(2), "<U+200C>")]);
+	}
+
+	#[test]
+	fn mixed_script_words_are_homoglyphs() {
+		// Cyrillic а (U+0430) in an otherwise Latin word
+		let findings = scan_text("the p\u{0430}per is fine");
+		assert_eq!(kinds(&findings), [("homoglyph", Some(4), "p<U+0430>per")]);
+		assert_eq!(findings[0].context, "the p\u{0430}per is fine");
+	}
+
+	#[test]
+	fn whole_cyrillic_words_are_not_homoglyphs() {
+		assert!(scan_text("привет world").is_empty());
+	}
+
+	#[test]
+	fn repeated_characters_count_as_new() {
+		let previous = vec![("zero_width".to_string(), "<U+200B>".to_string())];
+		let findings = scan_text("a\u{200B}b c\u{200B}d");
+		assert_eq!(mark_new(&previous, &findings), [false, true]);
+		assert_eq!(mark_new(&[], &findings), [true, true]);
+		assert_eq!(mark_new(&previous, &findings[..1]), [false]);
+	}
+}
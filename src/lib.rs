@@ -1,3 +1,4 @@
 // src/lib.rs
 pub mod users;
 pub mod state;
+pub mod token_manager;
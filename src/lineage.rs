@@ -0,0 +1,168 @@
+use std::collections::HashSet;
+
+use serde::Serialize;
+use sqlx::SqlitePool;
+
+use crate::similarity::{estimated_similarity, minhash_signature, shingles, MIN_PASSAGE_WORDS, SHINGLE_WORDS};
+use crate::templates::{all_template_hashes, template_hashes, template_words_in_text};
+
+// A new document whose first version is at least this similar to an existing one is taken as a copy of it
+const COPY_SIMILARITY: f64 = 0.5;
+// "Make a copy" names the new file "Copy of ...", which is enough to accept a weaker match
+const NAMED_COPY_SIMILARITY: f64 = 0.3;
+
+#[derive(Debug, Serialize)]
+pub struct Parent {
+	#[serde(skip)]
+	pub document_id: i64,
+	pub doc_id: String,
+	pub name: String,
+	pub similarity: f64,
+	pub named_as_copy: bool,
+}
+
+// Words of a document's first observed version that aren't template text
+pub async fn baseline_words(pool: &SqlitePool, document_id: i64, content: &str) -> Result<i64, sqlx::Error> {
+	let hashes = template_hashes(pool, document_id).await?;
+	Ok(content.split_whitespace().count() as i64 - template_words_in_text(content, &hashes))
+}
+
+// Shingle hashes of a text that aren't template text
+fn student_hashes(content: &str, templates: &HashSet<i64>) -> HashSet<i64> {
+	shingles(content).into_iter().map(|s| s.hash).filter(|h| !templates.contains(h)).collect()
+}
+
+fn jaccard(a: &HashSet<i64>, b: &HashSet<i64>) -> f64 {
+	let union = a.union(b).count();
+	if union == 0 {
+		return 0.0;
+	}
+	a.intersection(b).count() as f64 / union as f64
+}
+
+fn copy_threshold(named_as_copy: bool) -> f64 {
+	if named_as_copy {
+		NAMED_COPY_SIMILARITY
+	} else {
+		COPY_SIMILARITY
+	}
+}
+
+// The existing document a new one was most likely copied from.
+// Candidates must have been seen before this document's first version, so an original isn't
+// taken for a copy of a later copy. Their latest indexed text is compared, so a parent
+// that has changed a lot since the copy was made may be missed.
+//
+// Students starting from the same instructor template share most of their first versions, so
+// template text is left out of the comparison. Every registered template is, since a new document
+// usually isn't linked to its assignment yet. The stored MinHash signatures cover whole texts and
+// only pick the candidates whose student text is then compared exactly.
+pub async fn find_parent(
+	pool: &SqlitePool,
+	document_id: i64,
+	revision_time: &str,
+	content: &str,
+) -> Result<Option<Parent>, sqlx::Error> {
+	let templates = all_template_hashes(pool).await?;
+	let own_hashes = student_hashes(content, &templates);
+	// Too little student text to tell a copy from a coincidence
+	if own_hashes.len() + SHINGLE_WORDS - 1 < MIN_PASSAGE_WORDS {
+		return Ok(None);
+	}
+	let own = minhash_signature(&shingles(content));
+
+	let name = sqlx::query_scalar!("SELECT name FROM documents WHERE id = ?", document_id)
+		.fetch_one(pool)
+		.await?;
+
+	let candidates = sqlx::query!(
+		r#"
+		SELECT m.document_id AS "document_id!", m.signature, r.content, d.doc_id, d.name
+		FROM document_minhash m
+		JOIN document_revisions r ON r.id = m.revision_id
+		JOIN documents d ON d.id = m.document_id
+		WHERE m.document_id != ?
+			AND (SELECT MIN(revision_time) FROM document_revisions WHERE document_id = m.document_id) <= ?
+		"#,
+		document_id,
+		revision_time
+	)
+	.fetch_all(pool)
+	.await?;
+
+	let best = candidates
+		.into_iter()
+		.filter(|c| estimated_similarity(&own, &c.signature) >= NAMED_COPY_SIMILARITY)
+		.map(|c| {
+			let similarity = jaccard(&own_hashes, &student_hashes(&c.content, &templates));
+			let named_as_copy = name.strip_prefix("Copy of ").is_some_and(|rest| rest == c.name);
+			Parent { document_id: c.document_id, doc_id: c.doc_id, name: c.name, similarity, named_as_copy }
+		})
+		.filter(|p| p.similarity >= copy_threshold(p.named_as_copy))
+		.max_by(|a, b| a.similarity.total_cmp(&b.similarity));
+
+	Ok(best)
+}
+
+pub async fn record_lineage(
+	pool: &SqlitePool,
+	document_id: i64,
+	baseline_words: i64,
+	parent: Option<&Parent>,
+) -> Result<(), sqlx::Error> {
+	let parent_id = parent.map(|p| p.document_id);
+	let similarity = parent.map(|p| p.similarity);
+
+	sqlx::query!(
+		"UPDATE documents SET baseline_words = ?, parent_document_id = ?, parent_similarity = ? WHERE id = ?",
+		baseline_words,
+		parent_id,
+		similarity,
+		document_id
+	)
+	.execute(pool)
+	.await?;
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const TEMPLATE: &str = "Lab report template. Aim: state the question your experiment answers in one sentence. \
+		Method: list the equipment you used and every step you followed so another student could repeat it. \
+		Results: include a table of your measurements with units and a graph of the main relationship. \
+		Discussion: explain whether the results support your hypothesis and describe two sources of error.";
+
+	fn hashes(text: &str) -> HashSet<i64> {
+		shingles(text).into_iter().map(|s| s.hash).collect()
+	}
+
+	#[test]
+	fn students_sharing_a_template_are_not_copies() {
+		let alice = format!("{} Aim: we measured how the length of a pendulum changes the time of one full swing.", TEMPLATE);
+		let bob = format!("{} Aim: our group tested whether warmer water dissolves more sugar before it saturates.", TEMPLATE);
+
+		// On whole texts the shared template alone looks like a copy
+		assert!(jaccard(&hashes(&alice), &hashes(&bob)) >= COPY_SIMILARITY);
+
+		let templates = hashes(TEMPLATE);
+		let similarity = jaccard(&student_hashes(&alice, &templates), &student_hashes(&bob, &templates));
+		assert!(similarity < NAMED_COPY_SIMILARITY, "{}", similarity);
+	}
+
+	#[test]
+	fn copied_student_text_still_matches_after_masking() {
+		let original = format!("{} Aim: we measured how the length of a pendulum changes the time of one full swing.", TEMPLATE);
+		let copy = format!("{} Aim: we measured how the length of a pendulum changes the time of one full swing!", TEMPLATE);
+
+		let templates = hashes(TEMPLATE);
+		assert_eq!(jaccard(&student_hashes(&original, &templates), &student_hashes(&copy, &templates)), 1.0);
+	}
+
+	#[test]
+	fn a_first_version_that_is_all_template_has_no_student_text() {
+		assert!(student_hashes(TEMPLATE, &hashes(TEMPLATE)).is_empty());
+	}
+}
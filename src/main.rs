@@ -6,6 +6,25 @@ mod routes;
 mod state;
 mod poller;
 mod google_api;
+mod token_manager;
+mod docs_structure;
+mod comments;
+mod folder_mirror;
+mod matching;
+mod watch_rules;
+mod assignments;
+mod roster;
+mod dashboard;
+mod similarity;
+mod corpus;
+mod templates;
+mod hidden_text;
+mod process_report;
+mod analyzers;
+mod stylometry;
+mod rewrites;
+mod lineage;
+mod search;
 
 pub mod users;
 
@@ -15,6 +34,7 @@ use serve_static::serve_spa;
 use state::AppState;
 
 use crate::poller::poll_loop;
+use crate::assignments::deadline_loop;
 
 #[tokio::main]
 async fn main() {
@@ -24,10 +44,21 @@ async fn main() {
 	println!("Docwatch is starting up!");
 	
 	let state = AppState::new().await;
+
+	// Surface a missing or insufficient grant at startup instead of on the first poll
+	match state.tokens.validate_scopes().await {
+		Ok(scopes) => println!("Google access ({:?}) granted scopes: {}", state.tokens.drive_access(), scopes.join(" ")),
+		Err(e) => eprintln!("⚠️ {}", e),
+	}
 	
-	let db_pool_for_polling = state.db.clone();
+	let state_for_polling = state.clone();
+	tokio::spawn(async move {
+		poll_loop(state_for_polling).await;
+	});
+
+	let state_for_deadlines = state.clone();
 	tokio::spawn(async move {
-		poll_loop(db_pool_for_polling).await;
+		deadline_loop(state_for_deadlines).await;
 	});
 
 	let app = Router::new()
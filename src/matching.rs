@@ -0,0 +1,72 @@
+// Case-insensitive glob matching for user-supplied name patterns such as `Essay 2*`.
+// `*` matches any run of characters and `?` a single character; everything else is literal.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+	let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+	let text: Vec<char> = text.to_lowercase().chars().collect();
+
+	let (mut p, mut t) = (0, 0);
+	// Position of the last `*` and the text index it was matched against
+	let mut backtrack: Option<(usize, usize)> = None;
+
+	while t < text.len() {
+		if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+			p += 1;
+			t += 1;
+		} else if p < pattern.len() && pattern[p] == '*' {
+			backtrack = Some((p, t));
+			p += 1;
+		} else if let Some((star, matched)) = backtrack {
+			p = star + 1;
+			t = matched + 1;
+			backtrack = Some((star, matched + 1));
+		} else {
+			return false;
+		}
+	}
+
+	pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+	use super::glob_match;
+
+	#[test]
+	fn literal_and_case_insensitive() {
+		assert!(glob_match("Essay 2", "essay 2"));
+		assert!(!glob_match("Essay 2", "Essay 22"));
+		assert!(!glob_match("Essay", "Essa"));
+	}
+
+	#[test]
+	fn star_matches_any_run() {
+		assert!(glob_match("Essay 2*", "Essay 2 - Ada Lovelace"));
+		assert!(glob_match("Essay 2*", "Essay 2"));
+		assert!(glob_match("*@school.edu", "ada@school.edu"));
+		assert!(!glob_match("*@school.edu", "ada@school.edu.evil.com"));
+		assert!(glob_match("*draft*final*", "My draft (final) v2"));
+		assert!(glob_match("**", ""));
+	}
+
+	#[test]
+	fn star_backtracks() {
+		assert!(glob_match("*ab", "aab"));
+		assert!(glob_match("a*b*c", "abbbc"));
+		assert!(!glob_match("a*b*c", "abbbcd"));
+	}
+
+	#[test]
+	fn question_mark_is_one_char() {
+		assert!(glob_match("Essay ?", "Essay 3"));
+		assert!(!glob_match("Essay ?", "Essay "));
+		assert!(glob_match("Caf?", "Café"));
+		assert!(glob_match("RÉSUMÉ*", "résumé final"));
+	}
+
+	#[test]
+	fn empty_pattern_matches_only_empty_text() {
+		assert!(glob_match("", ""));
+		assert!(!glob_match("", "a"));
+		assert!(!glob_match("?", ""));
+	}
+}
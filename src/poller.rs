@@ -1,10 +1,22 @@
 use sqlx::{SqlitePool};
+use std::collections::HashMap;
 use std::time::Duration;
 use serde::{Serialize, Deserialize};
 use diff::{self, Result as DiffResult};
 use regex::Regex;
 
-use crate::google_api::{get_google_docs, get_google_text, get_docx_text};
+use crate::google_api::{
+	get_file, get_google_docs, get_shared_drive_docs, list_shared_drives, get_document_text, DriveFile, FetchedDocument, MIME_PDF,
+};
+use crate::token_manager::TokenManager;
+use crate::comments::{sync_annotations, sync_comments, sync_suggestions};
+use crate::folder_mirror::sync_folder_mirrors;
+use crate::watch_rules::apply_rules_to_document;
+use crate::assignments::refresh_late_flags;
+use crate::templates::{template_hashes, template_mask, template_words_in_diff};
+use crate::analyzers::{run_analyzers, RevisionContext};
+use crate::search::index_revision_text;
+use crate::state::AppState;
 
 #[derive(Debug)]
 pub enum WordChange<'a> {
@@ -13,7 +25,7 @@ pub enum WordChange<'a> {
 	Unchanged(&'a str),
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", content = "text")]
 pub enum OwnedWordChange {
 	Added(String),
@@ -21,6 +33,14 @@ pub enum OwnedWordChange {
 	Unchanged(String),
 }
 
+impl OwnedWordChange {
+	pub fn into_text(self) -> String {
+		match self {
+			OwnedWordChange::Added(text) | OwnedWordChange::Removed(text) | OwnedWordChange::Unchanged(text) => text,
+		}
+	}
+}
+
 impl<'a> From<WordChange<'a>> for OwnedWordChange {
 	fn from(wc: WordChange<'a>) -> Self {
 		match wc {
@@ -31,14 +51,24 @@ impl<'a> From<WordChange<'a>> for OwnedWordChange {
 	}
 }
 
-pub async fn poll_loop(pool: SqlitePool) {
+pub async fn poll_loop(state: AppState) {
 	let mut interval = tokio::time::interval(Duration::from_secs(5*60));
 
+	let mut backfilled = false;
+
 	loop {
 		interval.tick().await;
-		if let Err(e) = poll_all_docs(&pool).await {
+		if let Err(e) = poll_all_docs(&state).await {
 			eprintln!("Polling error: {:?}", e);
 		}
+
+		// Once, after the first poll has refreshed every listed document
+		if !backfilled {
+			backfilled = true;
+			if let Err(e) = backfill_owner_emails(&state).await {
+				eprintln!("⚠️ Failed to backfill owner emails: {:?}", e);
+			}
+		}
 	}
 }
 
@@ -75,151 +105,491 @@ pub fn diff_words<'a>(old: &'a str, new: &'a str) -> Vec<WordChange<'a>> {
 		.collect()
 }
 
-async fn poll_all_docs(pool: &SqlitePool) -> Result<(), sqlx::Error> {
-	let modified_map = get_google_docs().await.unwrap_or_default();
+async fn poll_all_docs(state: &AppState) -> Result<(), sqlx::Error> {
+	let pool = &state.db;
+	let tokens = &state.tokens;
 
-	for (doc_id, (name, modified_time, export_link, owner_username, mime_type)) in modified_map {
-		let db_doc = sqlx::query!(
-			"SELECT id, last_updated, latest_content FROM documents WHERE doc_id = ?",
-			doc_id
-		)
-		.fetch_optional(pool)
-		.await?;
+	let mut modified_map = match get_google_docs(tokens).await {
+		Ok(map) => map,
+		Err(e) => {
+			eprintln!("⚠️ Failed to list Google documents: {}", e);
+			HashMap::new()
+		}
+	};
 
-		match db_doc {
-			Some(db) => {
-				if db.last_updated != modified_time {
-					let latest_content = db.latest_content.unwrap_or_default();
-
-					// Fetch the document's current content (only when needed)
-					let new_content = match mime_type.as_str() {
-						"application/vnd.google-apps.document" => match get_google_text(&export_link).await {
-							Ok(text) => text,
-							Err(e) => {
-								eprintln!("⚠️ Failed to fetch Google Doc text: {}", e);
-								continue;
-							}
-						},
-						"application/vnd.openxmlformats-officedocument.wordprocessingml.document" => match get_docx_text(&doc_id).await {
-							Ok(text) => text,
-							Err(e) => {
-								eprintln!("⚠️ Failed to fetch DOCX content: {}", e);
-								continue;
-							}
-						},
-						other => {
-							eprintln!("⚠️ Unsupported MIME type: {}", other);
-							continue;
-						}
-					};
-
-					let diff = diff_words(&latest_content, &new_content);
-					let (added_words, deleted_words) = count_words_from_diff(&diff);
-
-					if added_words > 0 || deleted_words > 0 {
-						println!("New revision found for: {}", name);
-
-						let owned_diff: Vec<OwnedWordChange> = diff.into_iter().map(Into::into).collect();
-						let diff_json = serde_json::to_string(&owned_diff)
-							.map_err(|e| sqlx::Error::ColumnDecode {
-								index: "diff_json".into(),
-								source: Box::new(e),
-							})?;
-							
-						let added_words = added_words as i64;
-						let deleted_words = deleted_words as i64;
-
-						sqlx::query!(
-							"INSERT INTO document_revisions (
-								document_id, revision_time, content, diff, added_words, deleted_words
-							) VALUES (?, ?, ?, ?, ?, ?)",
-							db.id,
-							modified_time,
-							new_content,
-							diff_json,
-							added_words,
-							deleted_words
-						)
-						.execute(pool)
-						.await?;
-
-						sqlx::query!(
-							"UPDATE documents
-							 SET name = ?, last_updated = ?, latest_content = ?, export_link = ?, owner_username = ?
-							 WHERE id = ?",
-							name,
-							modified_time,
-							new_content,
-							export_link,
-							owner_username,
-							db.id
-						)
-						.execute(pool)
-						.await?;
-					}
-				}
+	// Keyed by file id, so a file both shared directly and listed through an enabled drive is polled once
+	let drive_names = poll_shared_drives(pool, tokens, &mut modified_map).await?;
+
+	for (doc_id, file) in modified_map {
+		let drive_name = file.drive_id.as_ref().and_then(|id| drive_names.get(id)).cloned();
+		poll_file(state, &doc_id, file, drive_name).await?;
+	}
+
+	sync_folder_mirrors(pool, tokens).await?;
+
+	Ok(())
+}
+
+// Checks one listed file against the database, storing a new revision when its content changed
+async fn poll_file(
+	state: &AppState,
+	doc_id: &str,
+	file: DriveFile,
+	drive_name: Option<String>,
+) -> Result<(), sqlx::Error> {
+	let pool = &state.db;
+	let tokens = &state.tokens;
+	let _guard = state.poll_lock.lock().await;
+
+	let checked_at = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
+
+	if update_document(pool, tokens, doc_id, file, drive_name).await? {
+		sqlx::query!("UPDATE documents SET last_checked = ? WHERE doc_id = ?", checked_at, doc_id)
+			.execute(pool)
+			.await?;
+	}
+
+	Ok(())
+}
+
+// Returns false when the content could not be fetched, so the stored copy may be stale
+async fn update_document(
+	pool: &SqlitePool,
+	tokens: &TokenManager,
+	doc_id: &str,
+	file: DriveFile,
+	drive_name: Option<String>,
+) -> Result<bool, sqlx::Error> {
+	let db_doc = sqlx::query!(
+		"SELECT id AS \"id!\", last_updated, latest_content FROM documents WHERE doc_id = ?",
+		doc_id
+	)
+	.fetch_optional(pool)
+	.await?;
+
+	// Owner and location can change without the content changing, and rosters match on the owner
+	if let Some(db) = &db_doc {
+		update_metadata(pool, db.id, &file, drive_name.as_deref()).await?;
+	}
+
+	let DriveFile { name, modified_time, export_link, owner_username, owner_email, mime_type, drive_id } = file;
+
+	match db_doc {
+		Some(db) => {
+			if let Err(e) = sync_comments(pool, tokens, db.id, doc_id).await {
+				eprintln!("⚠️ Failed to sync comments for {}: {}", name, e);
 			}
-			None => {
-				// 📄 New document — must always fetch content
-				let new_content = match mime_type.as_str() {
-					"application/vnd.google-apps.document" => match get_google_text(&export_link).await {
-						Ok(text) => text,
-						Err(e) => {
-							eprintln!("⚠️ Failed to fetch Google Doc text: {}", e);
-							continue;
-						}
-					},
-					"application/vnd.openxmlformats-officedocument.wordprocessingml.document" => match get_docx_text(&doc_id).await {
-						Ok(text) => text,
-						Err(e) => {
-							eprintln!("⚠️ Failed to fetch DOCX content: {}", e);
-							continue;
-						}
-					},
-					other => {
-						eprintln!("⚠️ Unsupported MIME type: {}", other);
-						continue;
+
+			if db.last_updated != modified_time {
+				// Fetch the document's current content (only when needed)
+				let fetched = match get_document_text(tokens, doc_id, &mime_type, &export_link).await {
+					Ok(fetched) => fetched,
+					Err(e) => {
+						eprintln!("⚠️ Failed to fetch content for {}: {}", name, e);
+						return Ok(false);
 					}
 				};
+				let new_content = &fetched.text;
 
-				// Insert into `documents` with owner_username
-				let res = sqlx::query!(
-					"INSERT INTO documents (
-						doc_id, name, last_updated, export_link, latest_content, owner_username
-					) VALUES (?, ?, ?, ?, ?, ?)",
-					doc_id,
-					name,
-					modified_time,
-					export_link,
-					new_content,
-					owner_username
-				)
-				.execute(pool)
-				.await?;
+				if let Err(e) = sync_suggestions(pool, tokens, db.id, doc_id, &mime_type, &modified_time).await {
+					eprintln!("⚠️ Failed to sync suggestions for {}: {}", name, e);
+				}
+
+				if is_missing_text(&mime_type, new_content) {
+					flag_missing_text(pool, db.id, &name, &modified_time).await?;
+					return Ok(true);
+				}
+
+				let Some(latest_content) = db.latest_content else {
+					// Earlier versions had no extractable text, so this is the first real baseline
+					insert_baseline_revision(pool, db.id, &modified_time, &fetched).await?;
+
+					sqlx::query!(
+						"UPDATE documents
+						 SET name = ?, last_updated = ?, latest_content = ?, no_extractable_text = FALSE
+						 WHERE id = ?",
+						name,
+						modified_time,
+						new_content,
+						db.id
+					)
+					.execute(pool)
+					.await?;
+
+					println!("📄 Inserted baseline revision for previously textless document: {}", name);
+					return Ok(true);
+				};
+
+				let diff = diff_words(&latest_content, new_content);
+				let (added_words, deleted_words) = count_words_from_diff(&diff);
+
+				// Inserting or removing an image or table changes no words, but is still a revision
+				if added_words > 0 || deleted_words > 0 || structure_changed(pool, db.id, &fetched).await? {
+					println!("New revision found for: {}", name);
+
+					let owned_diff: Vec<OwnedWordChange> = diff.into_iter().map(Into::into).collect();
+					let diff_json = serde_json::to_string(&owned_diff)
+						.map_err(|e| sqlx::Error::ColumnDecode {
+							index: "diff_json".into(),
+							source: Box::new(e),
+						})?;
+						
+					let added_words = added_words as i64;
+					let deleted_words = deleted_words as i64;
+
+					let revision_id = sqlx::query!(
+						"INSERT INTO document_revisions (
+							document_id, revision_time, content, diff, added_words, deleted_words, image_count, table_count, outline
+						) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+						db.id,
+						modified_time,
+						new_content,
+						diff_json,
+						added_words,
+						deleted_words,
+						fetched.image_count,
+						fetched.table_count,
+						fetched.outline
+					)
+					.execute(pool)
+					.await?
+					.last_insert_rowid();
+
+					on_new_revision(pool, db.id, revision_id, &modified_time, &fetched, &owned_diff).await?;
+
+					sqlx::query!(
+						"UPDATE documents SET last_updated = ?, latest_content = ?, no_extractable_text = FALSE WHERE id = ?",
+						modified_time,
+						new_content,
+						db.id
+					)
+					.execute(pool)
+					.await?;
+				}
+			}
+		}
+		None => {
+			// 📄 New document — must always fetch content
+			let fetched = match get_document_text(tokens, doc_id, &mime_type, &export_link).await {
+				Ok(fetched) => fetched,
+				Err(e) => {
+					eprintln!("⚠️ Failed to fetch content for {}: {}", name, e);
+					return Ok(false);
+				}
+			};
+			let new_content = &fetched.text;
+
+			let no_text = is_missing_text(&mime_type, new_content);
+			// Textless documents get no baseline; the first version with text becomes one
+			let latest_content = if no_text { None } else { Some(new_content.as_str()) };
+
+			// Insert into `documents` with owner_username
+			let res = sqlx::query!(
+				"INSERT INTO documents (
+					doc_id, name, last_updated, export_link, latest_content, owner_username, mime_type, no_extractable_text,
+					drive_id, drive_name, owner_email
+				) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+				doc_id,
+				name,
+				modified_time,
+				export_link,
+				latest_content,
+				owner_username,
+				mime_type,
+				no_text,
+				drive_id,
+				drive_name,
+				owner_email
+			)
+			.execute(pool)
+			.await?;
+
+			let new_doc_id = res.last_insert_rowid();
+
+			if let Err(e) = apply_rules_to_document(pool, new_doc_id).await {
+				eprintln!("⚠️ Failed to apply watch rules to {}: {:?}", name, e);
+			}
+
+			if let Err(e) = sync_annotations(pool, tokens, new_doc_id, doc_id, &mime_type, &modified_time).await {
+				eprintln!("⚠️ Failed to sync comments for {}: {}", name, e);
+			}
+
+			if no_text {
+				println!("⚠️ New document has no extractable text (scanned PDF?): {}", name);
+				return Ok(true);
+			}
+
+			insert_baseline_revision(pool, new_doc_id, &modified_time, &fetched).await?;
+
+			println!("📄 Inserted new document and baseline revision: {}", name);
+		}
+	}
+
+	Ok(true)
+}
+
+// Fetches one document right away instead of waiting for the next poll
+pub async fn poll_document(state: &AppState, doc_id: &str) -> Result<(), String> {
+	let file = get_file(&state.tokens, doc_id).await?;
+
+	let drive_name = shared_drive_name(&state.db, file.drive_id.as_deref())
+		.await
+		.map_err(|e| format!("DB error: {}", e))?;
+
+	poll_file(state, doc_id, file, drive_name)
+		.await
+		.map_err(|e| format!("DB error: {}", e))
+}
+
+async fn shared_drive_name(pool: &SqlitePool, drive_id: Option<&str>) -> Result<Option<String>, sqlx::Error> {
+	match drive_id {
+		Some(drive_id) => sqlx::query_scalar!("SELECT name FROM shared_drives WHERE drive_id = ?", drive_id)
+			.fetch_optional(pool)
+			.await,
+		None => Ok(None),
+	}
+}
+
+async fn update_metadata(
+	pool: &SqlitePool,
+	document_id: i64,
+	file: &DriveFile,
+	drive_name: Option<&str>,
+) -> Result<(), sqlx::Error> {
+	sqlx::query!(
+		"UPDATE documents
+		 SET name = ?, export_link = ?, owner_username = ?, owner_email = ?, mime_type = ?, drive_id = ?, drive_name = ?
+		 WHERE id = ?",
+		file.name,
+		file.export_link,
+		file.owner_username,
+		file.owner_email,
+		file.mime_type,
+		file.drive_id,
+		drive_name,
+		document_id
+	)
+	.execute(pool)
+	.await?;
 
-				let new_doc_id = res.last_insert_rowid();
+	Ok(())
+}
+
+// Documents stored before owner emails were recorded, and not in the listing, get their metadata
+// fetched one by one. Files that are no longer accessible keep a NULL owner_email.
+async fn backfill_owner_emails(state: &AppState) -> Result<(), sqlx::Error> {
+	let documents = sqlx::query!(r#"SELECT id AS "id!", doc_id FROM documents WHERE owner_email IS NULL"#)
+		.fetch_all(&state.db)
+		.await?;
+
+	let mut updated = 0;
+	for doc in &documents {
+		let file = match get_file(&state.tokens, &doc.doc_id).await {
+			Ok(file) => file,
+			Err(e) => {
+				eprintln!("⚠️ Failed to fetch metadata for {}: {}", doc.doc_id, e);
+				continue;
+			}
+		};
+		let drive_name = shared_drive_name(&state.db, file.drive_id.as_deref()).await?;
+		update_metadata(&state.db, doc.id, &file, drive_name.as_deref()).await?;
+		if file.owner_email.is_some() {
+			updated += 1;
+		}
+	}
 
+	if !documents.is_empty() {
+		println!("📇 Backfilled owner emails for {} of {} documents", updated, documents.len());
+	}
+
+	Ok(())
+}
+
+// Records the shared drives Docwatch can see and adds the files of every enabled drive to `files`.
+// Each drive is listed on its own, so one failing drive doesn't hold up the others.
+// Returns the names of all known drives by id for labelling documents.
+async fn poll_shared_drives(
+	pool: &SqlitePool,
+	tokens: &TokenManager,
+	files: &mut HashMap<String, DriveFile>,
+) -> Result<HashMap<String, String>, sqlx::Error> {
+	match list_shared_drives(tokens).await {
+		Ok(drives) => {
+			for (drive_id, name) in drives {
+				// New drives start disabled until an admin allow-lists them
 				sqlx::query!(
-					"INSERT INTO document_revisions (
-						document_id, revision_time, content, diff, added_words, deleted_words
-					) VALUES (?, ?, ?, ?, ?, ?)",
-					new_doc_id,
-					modified_time,
-					new_content,
-					"", // no diff for initial version
-					0,
-					0
+					"INSERT INTO shared_drives (drive_id, name) VALUES (?, ?)
+					ON CONFLICT(drive_id) DO UPDATE SET name = excluded.name",
+					drive_id,
+					name
 				)
 				.execute(pool)
 				.await?;
-
-				println!("📄 Inserted new document and baseline revision: {}", name);
 			}
 		}
+		Err(e) => eprintln!("⚠️ Failed to list shared drives: {}", e),
+	}
+
+	let drives = sqlx::query!("SELECT drive_id, name, enabled FROM shared_drives")
+		.fetch_all(pool)
+		.await?;
+
+	// Files shared directly from a drive that isn't enabled are still labelled with its name
+	let mut names = HashMap::new();
+	for drive in drives {
+		if !drive.enabled {
+			names.insert(drive.drive_id, drive.name);
+			continue;
+		}
+		let polled_at = chrono::Utc::now().to_rfc3339();
+		let error = match get_shared_drive_docs(tokens, &drive.drive_id).await {
+			Ok(drive_files) => {
+				files.extend(drive_files);
+				None
+			}
+			Err(e) => {
+				eprintln!("⚠️ Failed to list shared drive {}: {}", drive.name, e);
+				Some(e)
+			}
+		};
+
+		sqlx::query!(
+			"UPDATE shared_drives SET last_polled = ?, last_error = ? WHERE drive_id = ?",
+			polled_at,
+			error,
+			drive.drive_id
+		)
+		.execute(pool)
+		.await?;
+
+		names.insert(drive.drive_id, drive.name);
 	}
 
+	Ok(names)
+}
+
+// Scanned PDFs come back as whitespace only; treat that as "needs OCR" rather than an empty document
+fn is_missing_text(mime_type: &str, content: &str) -> bool {
+	mime_type == MIME_PDF && content.trim().is_empty()
+}
+
+async fn flag_missing_text(
+	pool: &SqlitePool,
+	document_id: i64,
+	name: &str,
+	modified_time: &str,
+) -> Result<(), sqlx::Error> {
+	sqlx::query!(
+		"UPDATE documents SET name = ?, last_updated = ?, no_extractable_text = TRUE WHERE id = ?",
+		name,
+		modified_time,
+		document_id
+	)
+	.execute(pool)
+	.await?;
+
+	println!("⚠️ No extractable text in new version of: {}", name);
 	Ok(())
 }
 
+// Whether the image or table count differs from the latest revision.
+// Counts are only known for structured fetches, so switching fetch modes is not a change.
+async fn structure_changed(pool: &SqlitePool, document_id: i64, fetched: &FetchedDocument) -> Result<bool, sqlx::Error> {
+	let Some(latest) = sqlx::query!(
+		"SELECT image_count, table_count FROM document_revisions
+		 WHERE document_id = ?
+		 ORDER BY revision_time DESC, id DESC
+		 LIMIT 1",
+		document_id
+	)
+	.fetch_optional(pool)
+	.await?
+	else {
+		return Ok(false);
+	};
 
+	let differs = |before: Option<i64>, after: Option<i64>| before.zip(after).is_some_and(|(b, a)| b != a);
+	Ok(differs(latest.image_count, fetched.image_count) || differs(latest.table_count, fetched.table_count))
+}
+
+async fn insert_baseline_revision(
+	pool: &SqlitePool,
+	document_id: i64,
+	revision_time: &str,
+	fetched: &FetchedDocument,
+) -> Result<(), sqlx::Error> {
+	let revision_id = sqlx::query!(
+		"INSERT INTO document_revisions (
+			document_id, revision_time, content, diff, added_words, deleted_words, image_count, table_count, outline
+		) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+		document_id,
+		revision_time,
+		fetched.text,
+		"", // no diff for initial version
+		0,
+		0,
+		fetched.image_count,
+		fetched.table_count,
+		fetched.outline
+	)
+	.execute(pool)
+	.await?
+	.last_insert_rowid();
+
+	on_new_revision(pool, document_id, revision_id, revision_time, fetched, &[]).await
+}
 
+// Bookkeeping that follows every stored revision
+async fn on_new_revision(
+	pool: &SqlitePool,
+	document_id: i64,
+	revision_id: i64,
+	revision_time: &str,
+	fetched: &FetchedDocument,
+	diff: &[OwnedWordChange],
+) -> Result<(), sqlx::Error> {
+	refresh_late_flags(pool, document_id).await?;
+
+	let hashes = template_hashes(pool, document_id).await?;
+	let template_words = template_words_in_diff(diff, &template_mask(diff, &hashes));
+	if template_words > 0 {
+		sqlx::query!(
+			"UPDATE document_revisions SET template_words = ? WHERE id = ?",
+			template_words,
+			revision_id
+		)
+		.execute(pool)
+		.await?;
+	}
+
+	// A failed search update shouldn't hold up polling
+	if let Err(e) = index_revision_text(pool, revision_id, &fetched.text).await {
+		eprintln!("⚠️ Failed to add revision {} to the search index: {:?}", revision_id, e);
+	}
+
+	let previous = sqlx::query!(
+		"SELECT revision_time, content FROM document_revisions
+		 WHERE document_id = ? AND id != ? AND revision_time <= ?
+		 ORDER BY revision_time DESC, id DESC
+		 LIMIT 1",
+		document_id,
+		revision_id,
+		revision_time
+	)
+	.fetch_optional(pool)
+	.await?;
+
+	let ctx = RevisionContext {
+		document_id,
+		revision_id,
+		revision_time,
+		previous_time: previous.as_ref().map(|p| p.revision_time.as_str()),
+		old_content: previous.as_ref().map(|p| p.content.as_str()).unwrap_or(""),
+		new_content: &fetched.text,
+		diff,
+		hidden_runs: &fetched.hidden_runs,
+	};
+	run_analyzers(pool, &ctx).await?;
+
+	Ok(())
+}
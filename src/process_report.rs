@@ -0,0 +1,330 @@
+use chrono::DateTime;
+use serde::Serialize;
+use sqlx::SqlitePool;
+
+use crate::dashboard::{count_sessions, SESSION_GAP};
+use crate::lineage;
+use crate::templates::{template_words_in_text, user_template_hashes, user_template_words};
+
+// A transparent alternative to a classifier: each rule looks at one property of the revision history,
+// says why it fired and which revisions it is based on. The score is the sum of the fired rules' weights.
+// A high score is a reason to look at the diffs, not a verdict.
+
+// The README's rules table repeats these thresholds; update it when tuning them.
+// Student words added in a single revision that count as a bulk insertion
+const BULK_INSERTION_WORDS: i64 = 150;
+// Sustained composition faster than this is implausible for typing
+const MAX_WORDS_PER_MINUTE: f64 = 80.0;
+// Short bursts are too noisy to judge a writing speed from
+const MIN_WPM_WORDS: i64 = 30;
+// Documents at least this long are expected to be written over more than one session
+const FEW_SESSIONS_MIN_WORDS: i64 = 500;
+// Student words already present when Docwatch first saw the document
+pub const LARGE_BASELINE_WORDS: i64 = 300;
+
+#[derive(Debug, Serialize)]
+pub struct RuleInfo {
+	pub id: &'static str,
+	pub description: String,
+	pub weight: i64,
+}
+
+// Descriptions are built from the thresholds, so they can't drift from what the checks do
+fn rules() -> Vec<RuleInfo> {
+	vec![
+		RuleInfo {
+			id: "bulk_insertion",
+			description: format!("A single revision added at least {} words of student text.", BULK_INSERTION_WORDS),
+			weight: 3,
+		},
+		RuleInfo {
+			id: "implausible_speed",
+			description: format!(
+				"A revision added at least {} words at over {} words per minute since the previous revision.",
+				MIN_WPM_WORDS, MAX_WORDS_PER_MINUTE
+			),
+			weight: 3,
+		},
+		RuleInfo {
+			id: "large_baseline",
+			description: format!(
+				"The first version Docwatch saw already had at least {} words of student text.",
+				LARGE_BASELINE_WORDS
+			),
+			weight: 2,
+		},
+		RuleInfo {
+			id: "copied_document",
+			description: "The first version Docwatch saw matched the text of an earlier watched document, as after \"Make a copy\".".to_string(),
+			weight: 2,
+		},
+		RuleInfo {
+			id: "few_sessions",
+			description: format!(
+				"A document of at least {} words was written in a single session (no gap over {} minutes).",
+				FEW_SESSIONS_MIN_WORDS,
+				SESSION_GAP.num_minutes()
+			),
+			weight: 1,
+		},
+		RuleInfo {
+			id: "late_edits",
+			description: "Revisions were made after the due time of an assignment the document is linked to.".to_string(),
+			weight: 1,
+		},
+	]
+}
+
+#[derive(Debug, Serialize)]
+pub struct Evidence {
+	pub revision_id: i64,
+	pub revision_time: String,
+	pub detail: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TriggeredRule {
+	pub rule: &'static str,
+	pub weight: i64,
+	pub explanation: String,
+	pub evidence: Vec<Evidence>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProcessReport {
+	pub doc_id: String,
+	pub name: String,
+	pub owner_username: String,
+	pub score: i64,
+	pub max_score: i64,
+	pub triggered: Vec<TriggeredRule>,
+	pub rules: Vec<RuleInfo>, // every rule evaluated, so the report explains itself
+}
+
+struct Revision {
+	id: i64,
+	time: String,
+	student_words: i64, // added words minus template words
+	is_late: bool,
+}
+
+struct CopiedFrom {
+	doc_id: String,
+	name: String,
+	similarity: f64,
+}
+
+struct History {
+	revisions: Vec<Revision>, // oldest first
+	baseline_words: i64,
+	total_words: i64,
+	parent: Option<CopiedFrom>,
+}
+
+fn triggered(id: &str, explanation: String, evidence: Vec<Evidence>) -> TriggeredRule {
+	let rule = rules().into_iter().find(|r| r.id == id).expect("rule is defined in rules()");
+	TriggeredRule { rule: rule.id, weight: rule.weight, explanation, evidence }
+}
+
+fn evidence(r: &Revision, detail: String) -> Evidence {
+	Evidence { revision_id: r.id, revision_time: r.time.clone(), detail }
+}
+
+fn bulk_insertion(history: &History) -> Option<TriggeredRule> {
+	let evidence: Vec<_> = history
+		.revisions
+		.iter()
+		.skip(1) // the baseline is judged by large_baseline
+		.filter(|r| r.student_words >= BULK_INSERTION_WORDS)
+		.map(|r| evidence(r, format!("{} words added", r.student_words)))
+		.collect();
+
+	(!evidence.is_empty()).then(|| {
+		triggered(
+			"bulk_insertion",
+			format!("{} revision(s) each added {} or more words at once", evidence.len(), BULK_INSERTION_WORDS),
+			evidence,
+		)
+	})
+}
+
+fn implausible_speed(history: &History) -> Option<TriggeredRule> {
+	let evidence: Vec<_> = history
+		.revisions
+		.windows(2)
+		.filter(|w| w[1].student_words >= MIN_WPM_WORDS)
+		.filter_map(|w| {
+			let before = DateTime::parse_from_rfc3339(&w[0].time).ok()?;
+			let after = DateTime::parse_from_rfc3339(&w[1].time).ok()?;
+			// Revision times are only as precise as polling; never divide by less than a minute
+			let minutes = ((after - before).num_seconds() as f64 / 60.0).max(1.0);
+			let wpm = w[1].student_words as f64 / minutes;
+			(wpm > MAX_WORDS_PER_MINUTE).then(|| {
+				evidence(&w[1], format!("{} words in {:.0} minute(s), {:.0} words per minute", w[1].student_words, minutes, wpm))
+			})
+		})
+		.collect();
+
+	(!evidence.is_empty()).then(|| {
+		triggered(
+			"implausible_speed",
+			format!("{} revision(s) were written faster than {} words per minute", evidence.len(), MAX_WORDS_PER_MINUTE),
+			evidence,
+		)
+	})
+}
+
+fn large_baseline(history: &History) -> Option<TriggeredRule> {
+	let first = history.revisions.first()?;
+	(history.baseline_words >= LARGE_BASELINE_WORDS).then(|| {
+		triggered(
+			"large_baseline",
+			format!("The document started with {} words of student text", history.baseline_words),
+			vec![evidence(first, format!("{} words in the first version", history.baseline_words))],
+		)
+	})
+}
+
+fn copied_document(history: &History) -> Option<TriggeredRule> {
+	let first = history.revisions.first()?;
+	let parent = history.parent.as_ref()?;
+	Some(triggered(
+		"copied_document",
+		format!("The document started as a copy of \"{}\" ({})", parent.name, parent.doc_id),
+		vec![evidence(first, format!("{:.0}% similar to the earlier document", parent.similarity * 100.0))],
+	))
+}
+
+fn few_sessions(history: &History) -> Option<TriggeredRule> {
+	let times: Vec<String> = history.revisions.iter().map(|r| r.time.clone()).collect();
+	let sessions = count_sessions(&times);
+
+	(history.total_words >= FEW_SESSIONS_MIN_WORDS && sessions <= 1).then(|| {
+		let evidence = history.revisions.iter().map(|r| evidence(r, format!("{} words added", r.student_words))).collect();
+		triggered(
+			"few_sessions",
+			format!("All {} words were written in {} session(s)", history.total_words, sessions),
+			evidence,
+		)
+	})
+}
+
+fn late_edits(history: &History) -> Option<TriggeredRule> {
+	let evidence: Vec<_> = history
+		.revisions
+		.iter()
+		.filter(|r| r.is_late)
+		.map(|r| evidence(r, format!("{} words added after the due time", r.student_words)))
+		.collect();
+
+	(!evidence.is_empty()).then(|| {
+		let words: i64 = history.revisions.iter().filter(|r| r.is_late).map(|r| r.student_words).sum();
+		triggered("late_edits", format!("{} revision(s) after the due time added {} words", evidence.len(), words), evidence)
+	})
+}
+
+// Checks in the same order as rules()
+const CHECKS: [fn(&History) -> Option<TriggeredRule>; 6] =
+	[bulk_insertion, implausible_speed, large_baseline, copied_document, few_sessions, late_edits];
+
+async fn load_history(
+	pool: &SqlitePool,
+	user_id: i64,
+	document_id: i64,
+	latest_content: Option<&str>,
+) -> Result<History, sqlx::Error> {
+	let rows = sqlx::query!(
+		r#"
+		SELECT id AS "id!", revision_time, added_words, template_words, is_late
+		FROM document_revisions
+		WHERE document_id = ?
+		ORDER BY revision_time, id
+		"#,
+		document_id
+	)
+	.fetch_all(pool)
+	.await?;
+
+	// The user's own folder templates, on top of the stored assignment template counts
+	let user_template_words = user_template_words(pool, user_id, document_id).await?;
+	let revisions: Vec<Revision> = rows
+		.into_iter()
+		.map(|r| {
+			let template_words = user_template_words
+				.as_ref()
+				.and_then(|words| words.get(&r.id).copied())
+				.unwrap_or(r.template_words);
+			Revision {
+				id: r.id,
+				time: r.revision_time,
+				student_words: r.added_words.unwrap_or(0) - template_words,
+				is_late: r.is_late,
+			}
+		})
+		.collect();
+
+	let hashes = user_template_hashes(pool, user_id, document_id).await?;
+	let student_words = |content: &str| content.split_whitespace().count() as i64 - template_words_in_text(content, &hashes);
+
+	// Recorded by the lineage analyzer, which also raises the large_baseline finding.
+	// Documents it hasn't seen yet are counted the same way here.
+	let stored_baseline = sqlx::query_scalar!("SELECT baseline_words FROM documents WHERE id = ?", document_id)
+		.fetch_one(pool)
+		.await?;
+	let baseline_words = match (stored_baseline, revisions.first()) {
+		(Some(words), _) => words,
+		(None, Some(first)) => {
+			let content = sqlx::query_scalar!("SELECT content FROM document_revisions WHERE id = ?", first.id)
+				.fetch_one(pool)
+				.await?;
+			lineage::baseline_words(pool, document_id, &content).await?
+		}
+		(None, None) => 0,
+	};
+
+	let parent = sqlx::query!(
+		r#"
+		SELECT p.doc_id, p.name, d.parent_similarity AS "similarity!"
+		FROM documents d
+		JOIN documents p ON p.id = d.parent_document_id
+		WHERE d.id = ?
+		"#,
+		document_id
+	)
+	.fetch_optional(pool)
+	.await?
+	.map(|p| CopiedFrom { doc_id: p.doc_id, name: p.name, similarity: p.similarity });
+
+	Ok(History {
+		revisions,
+		baseline_words,
+		parent,
+		total_words: latest_content.map(student_words).unwrap_or(0),
+	})
+}
+
+pub async fn build_report(pool: &SqlitePool, user_id: i64, document_id: i64) -> Result<Option<ProcessReport>, sqlx::Error> {
+	let Some(doc) = sqlx::query!(
+		"SELECT doc_id, name, owner_username, latest_content FROM documents WHERE id = ?",
+		document_id
+	)
+	.fetch_optional(pool)
+	.await?
+	else {
+		return Ok(None);
+	};
+
+	let history = load_history(pool, user_id, document_id, doc.latest_content.as_deref()).await?;
+	let triggered: Vec<TriggeredRule> = CHECKS.iter().filter_map(|check| check(&history)).collect();
+
+	let rules = rules();
+	Ok(Some(ProcessReport {
+		doc_id: doc.doc_id,
+		name: doc.name,
+		owner_username: doc.owner_username,
+		score: triggered.iter().map(|t| t.weight).sum(),
+		max_score: rules.iter().map(|r| r.weight).sum(),
+		triggered,
+		rules,
+	}))
+}
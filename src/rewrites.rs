@@ -0,0 +1,306 @@
+use serde::Serialize;
+use sqlx::SqlitePool;
+
+// Word edit distance over sentence length at or above which a paired sentence counts as rewritten
+const REWRITE_RATIO: f64 = 0.3;
+// Above this a removed and an added sentence are unrelated, unless they replace each other one for one
+const UNRELATED_RATIO: f64 = 0.9;
+// A rewrite event needs at least this much existing text to judge
+const EVENT_MIN_SENTENCES: i64 = 5;
+const EVENT_MIN_REWRITTEN: i64 = 3;
+// ...and either this share of its sentences rewritten, or this many in a row
+const EVENT_SHARE: f64 = 0.3;
+const EVENT_RUN: i64 = 5;
+// ...while the word count changes by no more than this fraction
+const EVENT_MAX_WORD_CHANGE: f64 = 0.15;
+
+#[derive(Debug, Default, Serialize)]
+pub struct RewriteMetrics {
+	pub sentences_before: i64,
+	pub unchanged: i64,
+	pub edited: i64,
+	pub rewritten: i64,
+	pub deleted: i64,
+	pub added: i64,
+	pub rewritten_share: f64,
+	pub mean_edit_ratio: Option<f64>,
+	pub longest_rewrite_run: i64,
+	pub word_change_ratio: f64,
+	pub is_event: bool,
+}
+
+// Sentences end at . ! ? followed by whitespace, and at line breaks
+fn split_sentences(text: &str) -> Vec<&str> {
+	let mut sentences = Vec::new();
+	let mut start = 0;
+	let mut chars = text.char_indices().peekable();
+
+	while let Some((i, c)) = chars.next() {
+		let next_is_space = chars.peek().is_none_or(|(_, n)| n.is_whitespace());
+		let end = match c {
+			'\n' => Some(i),
+			'.' | '!' | '?' if next_is_space => Some(i + c.len_utf8()),
+			_ => None,
+		};
+		if let Some(end) = end {
+			sentences.push(&text[start..end]);
+			start = end;
+		}
+	}
+	sentences.push(&text[start..]);
+
+	sentences.into_iter().map(str::trim).filter(|s| s.chars().any(char::is_alphanumeric)).collect()
+}
+
+fn sentence_words(sentence: &str) -> Vec<String> {
+	sentence
+		.split(|c: char| !c.is_alphanumeric())
+		.filter(|w| !w.is_empty())
+		.map(str::to_lowercase)
+		.collect()
+}
+
+// Levenshtein distance over words, divided by the longer sentence's length
+fn edit_ratio(a: &[String], b: &[String]) -> f64 {
+	let longest = a.len().max(b.len());
+	if longest == 0 {
+		return 0.0;
+	}
+
+	let mut previous: Vec<usize> = (0..=b.len()).collect();
+	for (i, wa) in a.iter().enumerate() {
+		let mut current = vec![i + 1; b.len() + 1];
+		for (j, wb) in b.iter().enumerate() {
+			let substitution = previous[j] + usize::from(wa != wb);
+			current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+		}
+		previous = current;
+	}
+
+	previous[b.len()] as f64 / longest as f64
+}
+
+enum Fate {
+	Unchanged,
+	Paired(f64), // edit ratio to the new sentence replacing it
+	Deleted,
+}
+
+// Pairs the removed and added sentences of one changed stretch.
+// Equal counts are taken as replacing each other in order; otherwise each removed sentence
+// takes its closest unclaimed added sentence, if they are related at all.
+fn pair_hunk(removed: &[Vec<String>], added: &[Vec<String>]) -> (Vec<Fate>, usize) {
+	if removed.len() == added.len() {
+		let fates = removed.iter().zip(added).map(|(r, a)| Fate::Paired(edit_ratio(r, a))).collect();
+		return (fates, 0);
+	}
+
+	let mut claimed = vec![false; added.len()];
+	let fates = removed
+		.iter()
+		.map(|r| {
+			let best = added
+				.iter()
+				.enumerate()
+				.filter(|(j, _)| !claimed[*j])
+				.map(|(j, a)| (j, edit_ratio(r, a)))
+				.min_by(|x, y| x.1.total_cmp(&y.1));
+			match best {
+				Some((j, ratio)) if ratio <= UNRELATED_RATIO => {
+					claimed[j] = true;
+					Fate::Paired(ratio)
+				}
+				_ => Fate::Deleted,
+			}
+		})
+		.collect();
+
+	(fates, claimed.iter().filter(|c| !**c).count())
+}
+
+pub fn rewrite_metrics(old: &str, new: &str) -> RewriteMetrics {
+	let old_sentences: Vec<Vec<String>> = split_sentences(old).into_iter().map(sentence_words).collect();
+	let new_sentences: Vec<Vec<String>> = split_sentences(new).into_iter().map(sentence_words).collect();
+
+	// Exact matches first; what's left between them are the changed stretches
+	let mut fates = Vec::with_capacity(old_sentences.len());
+	let mut added = 0;
+	let mut removed_hunk: Vec<Vec<String>> = Vec::new();
+	let mut added_hunk: Vec<Vec<String>> = Vec::new();
+
+	let mut flush = |removed_hunk: &mut Vec<Vec<String>>, added_hunk: &mut Vec<Vec<String>>, fates: &mut Vec<Fate>| {
+		let (hunk_fates, unpaired) = pair_hunk(removed_hunk, added_hunk);
+		fates.extend(hunk_fates);
+		added += unpaired;
+		removed_hunk.clear();
+		added_hunk.clear();
+	};
+
+	for change in diff::slice(&old_sentences, &new_sentences) {
+		match change {
+			diff::Result::Left(s) => removed_hunk.push(s.clone()),
+			diff::Result::Right(s) => added_hunk.push(s.clone()),
+			diff::Result::Both(_, _) => {
+				flush(&mut removed_hunk, &mut added_hunk, &mut fates);
+				fates.push(Fate::Unchanged);
+			}
+		}
+	}
+	flush(&mut removed_hunk, &mut added_hunk, &mut fates);
+
+	let mut metrics = RewriteMetrics {
+		sentences_before: old_sentences.len() as i64,
+		added: added as i64,
+		..Default::default()
+	};
+
+	let mut ratios = Vec::new();
+	let mut run = 0;
+	for fate in &fates {
+		match fate {
+			Fate::Unchanged => metrics.unchanged += 1,
+			Fate::Deleted => metrics.deleted += 1,
+			Fate::Paired(ratio) => {
+				ratios.push(*ratio);
+				if *ratio >= REWRITE_RATIO {
+					metrics.rewritten += 1;
+				} else {
+					metrics.edited += 1;
+				}
+			}
+		}
+		run = match fate {
+			Fate::Paired(ratio) if *ratio >= REWRITE_RATIO => run + 1,
+			_ => 0,
+		};
+		metrics.longest_rewrite_run = metrics.longest_rewrite_run.max(run);
+	}
+
+	let old_words: usize = old_sentences.iter().map(Vec::len).sum();
+	let new_words: usize = new_sentences.iter().map(Vec::len).sum();
+
+	if metrics.sentences_before > 0 {
+		metrics.rewritten_share = metrics.rewritten as f64 / metrics.sentences_before as f64;
+	}
+	if !ratios.is_empty() {
+		metrics.mean_edit_ratio = Some(ratios.iter().sum::<f64>() / ratios.len() as f64);
+	}
+	if old_words > 0 {
+		metrics.word_change_ratio = (new_words as f64 - old_words as f64).abs() / old_words as f64;
+	}
+
+	metrics.is_event = metrics.sentences_before >= EVENT_MIN_SENTENCES
+		&& metrics.rewritten >= EVENT_MIN_REWRITTEN
+		&& (metrics.rewritten_share >= EVENT_SHARE || metrics.longest_rewrite_run >= EVENT_RUN)
+		&& metrics.word_change_ratio <= EVENT_MAX_WORD_CHANGE;
+
+	metrics
+}
+
+pub async fn record_rewrite(pool: &SqlitePool, revision_id: i64, m: &RewriteMetrics) -> Result<(), sqlx::Error> {
+	sqlx::query!(
+		"INSERT OR REPLACE INTO revision_rewrites (
+			revision_id, sentences_before, unchanged, edited, rewritten, deleted, added,
+			rewritten_share, mean_edit_ratio, longest_rewrite_run, word_change_ratio, is_event
+		) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+		revision_id,
+		m.sentences_before,
+		m.unchanged,
+		m.edited,
+		m.rewritten,
+		m.deleted,
+		m.added,
+		m.rewritten_share,
+		m.mean_edit_ratio,
+		m.longest_rewrite_run,
+		m.word_change_ratio,
+		m.is_event
+	)
+	.execute(pool)
+	.await?;
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const ORIGINAL: &str = "The industrial revolution began in Britain. Steam engines powered new factories. \
+		Workers moved from farms to growing cities. Child labour was common in the mills. \
+		Reformers later pushed for shorter working hours. Railways spread goods across the country.";
+
+	#[test]
+	fn sentences_split_on_terminators_and_line_breaks() {
+		assert_eq!(split_sentences("One. Two! Three?\nFour"), ["One.", "Two!", "Three?", "Four"]);
+		// No break inside numbers or abbreviations without a following space
+		assert_eq!(split_sentences("It cost 3.50 today.Really"), ["It cost 3.50 today.Really"]);
+	}
+
+	#[test]
+	fn empty_and_punctuation_only_text_has_no_sentences() {
+		assert!(split_sentences("").is_empty());
+		assert!(split_sentences(" ... \n\n !? ").is_empty());
+	}
+
+	#[test]
+	fn multibyte_sentences_split_on_char_boundaries() {
+		assert_eq!(split_sentences("Ça va? Très bien… Merci! 東京です。"), ["Ça va?", "Très bien… Merci!", "東京です。"]);
+	}
+
+	#[test]
+	fn unchanged_text_has_no_rewrites() {
+		let m = rewrite_metrics(ORIGINAL, ORIGINAL);
+		assert_eq!(m.sentences_before, 6);
+		assert_eq!(m.unchanged, 6);
+		assert_eq!(m.rewritten, 0);
+		assert!(!m.is_event);
+	}
+
+	#[test]
+	fn empty_old_text_counts_only_additions() {
+		let m = rewrite_metrics("", ORIGINAL);
+		assert_eq!(m.sentences_before, 0);
+		assert_eq!(m.added, 6);
+		assert_eq!(m.rewritten_share, 0.0);
+		assert!(!m.is_event);
+	}
+
+	#[test]
+	fn appended_text_is_not_a_rewrite() {
+		let m = rewrite_metrics(ORIGINAL, &format!("{} Canals were dug before railways.", ORIGINAL));
+		assert_eq!(m.unchanged, 6);
+		assert_eq!(m.added, 1);
+		assert_eq!(m.rewritten, 0);
+	}
+
+	#[test]
+	fn paraphrase_of_the_same_length_is_an_event() {
+		let paraphrased = "Britain was where the industrial revolution started. New factories were driven by steam engines. \
+			People left farms for cities that kept growing. Mills commonly employed child labourers. \
+			Shorter working days were later demanded by reformers. Goods travelled the country by railway.";
+		let m = rewrite_metrics(ORIGINAL, paraphrased);
+		assert_eq!(m.sentences_before, 6);
+		assert!(m.rewritten >= 5, "{:?}", m);
+		assert!(m.word_change_ratio <= EVENT_MAX_WORD_CHANGE);
+		assert!(m.is_event);
+	}
+
+	#[test]
+	fn deleted_sentences_are_not_rewrites() {
+		let m = rewrite_metrics(ORIGINAL, "The industrial revolution began in Britain.");
+		assert_eq!(m.unchanged, 1);
+		assert_eq!(m.deleted, 5);
+		assert_eq!(m.rewritten, 0);
+		assert!(!m.is_event);
+	}
+
+	#[test]
+	fn edit_ratio_is_word_levenshtein_over_longer_length() {
+		let words = |s: &str| sentence_words(s);
+		assert_eq!(edit_ratio(&words("a b c d"), &words("a b c d")), 0.0);
+		assert_eq!(edit_ratio(&words("a b c d"), &words("a x c d")), 0.25);
+		assert_eq!(edit_ratio(&words("a b"), &words("a b c d")), 0.5);
+		assert_eq!(edit_ratio(&[], &[]), 0.0);
+	}
+}
@@ -0,0 +1,148 @@
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+pub struct RosterEntry {
+	pub name: String,
+	pub email: String,
+	pub student_id: Option<String>,
+}
+
+// Column positions of name, email and student id
+struct Columns {
+	name: Option<usize>,
+	email: usize,
+	student_id: Option<usize>,
+}
+
+impl Columns {
+	// Files without a header row are read as name,email[,student id]
+	const POSITIONAL: Columns = Columns { name: Some(0), email: 1, student_id: Some(2) };
+
+	fn from_header(header: &csv::StringRecord) -> Option<Columns> {
+		let find = |names: &[&str]| {
+			header.iter().position(|h| {
+				let h = h.trim().to_lowercase().replace(['_', '-'], " ");
+				names.contains(&h.as_str())
+			})
+		};
+
+		let email = find(&["email", "e mail", "email address"])?;
+		let name = find(&["name", "full name", "student name", "student"]);
+		let student_id = find(&["student id", "id", "student number"]);
+
+		Some(Columns { name, email, student_id })
+	}
+}
+
+// Parses a roster CSV exported from an LMS or spreadsheet.
+// Returns the valid rows and a message for every row that was skipped.
+pub fn parse_roster_csv(data: &str) -> (Vec<RosterEntry>, Vec<String>) {
+	let mut reader = csv::ReaderBuilder::new()
+		.has_headers(false)
+		.flexible(true)
+		.trim(csv::Trim::All)
+		// Spreadsheet exports often start with a byte order mark, which would hide the first header
+		.from_reader(data.trim_start_matches('\u{feff}').as_bytes());
+
+	let mut entries = Vec::new();
+	let mut errors = Vec::new();
+	let mut columns = None;
+
+	for (index, record) in reader.records().enumerate() {
+		let line = index + 1;
+		let record = match record {
+			Ok(record) => record,
+			Err(e) => {
+				errors.push(format!("Line {}: {}", line, e));
+				continue;
+			}
+		};
+
+		// The first row decides whether columns are named or positional
+		if columns.is_none() {
+			match Columns::from_header(&record) {
+				Some(header) => {
+					columns = Some(header);
+					continue;
+				}
+				None => columns = Some(Columns::POSITIONAL),
+			}
+		}
+		let Some(cols) = &columns else {
+			continue;
+		};
+
+		let field = |i: usize| record.get(i).map(str::trim).filter(|v| !v.is_empty());
+
+		let Some(email) = field(cols.email).map(str::to_lowercase) else {
+			errors.push(format!("Line {}: missing email", line));
+			continue;
+		};
+		if !email.contains('@') {
+			errors.push(format!("Line {}: invalid email {}", line, email));
+			continue;
+		}
+
+		entries.push(RosterEntry {
+			name: cols.name.and_then(field).unwrap_or(&email).to_string(),
+			student_id: cols.student_id.and_then(field).map(str::to_string),
+			email,
+		});
+	}
+
+	(entries, errors)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn positional_rows_without_header() {
+		let (entries, errors) = parse_roster_csv("Ada Lovelace, ADA@School.edu, 1001\nAlan Turing,alan@school.edu\n");
+		assert!(errors.is_empty());
+		assert_eq!(entries.len(), 2);
+		assert_eq!(entries[0].name, "Ada Lovelace");
+		assert_eq!(entries[0].email, "ada@school.edu");
+		assert_eq!(entries[0].student_id.as_deref(), Some("1001"));
+		assert_eq!(entries[1].student_id, None);
+	}
+
+	#[test]
+	fn header_names_columns_in_any_order() {
+		let (entries, errors) = parse_roster_csv("Student ID,E-mail,Full_Name\n42,grace@school.edu,Grace Hopper\n");
+		assert!(errors.is_empty());
+		assert_eq!(entries[0].name, "Grace Hopper");
+		assert_eq!(entries[0].email, "grace@school.edu");
+		assert_eq!(entries[0].student_id.as_deref(), Some("42"));
+	}
+
+	#[test]
+	fn header_after_byte_order_mark() {
+		let (entries, errors) = parse_roster_csv("\u{feff}Name,Email\nÉmile Zola,emile@école.fr\n");
+		assert!(errors.is_empty());
+		assert_eq!(entries[0].name, "Émile Zola");
+		assert_eq!(entries[0].email, "emile@école.fr");
+	}
+
+	#[test]
+	fn email_only_header_uses_email_as_name() {
+		let (entries, errors) = parse_roster_csv("email\nkatherine@school.edu\n");
+		assert!(errors.is_empty());
+		assert_eq!(entries[0].name, "katherine@school.edu");
+	}
+
+	#[test]
+	fn bad_rows_are_reported_by_line() {
+		let (entries, errors) = parse_roster_csv("name,email\nNo Email,\nBad,not-an-email\nOk,ok@school.edu\n");
+		assert_eq!(entries.len(), 1);
+		assert_eq!(errors, ["Line 2: missing email", "Line 3: invalid email not-an-email"]);
+	}
+
+	#[test]
+	fn empty_input() {
+		let (entries, errors) = parse_roster_csv("");
+		assert!(entries.is_empty());
+		assert!(errors.is_empty());
+	}
+}
@@ -3,6 +3,17 @@ use axum::Router;
 mod auth;
 mod docs;
 mod admin;
+mod inbox;
+mod rules;
+mod courses;
+mod roster;
+mod dashboard;
+mod similarity;
+mod corpus;
+mod templates;
+mod report;
+mod stylometry;
+mod search;
 
 use axum::routing::get;
 use axum::routing::post;
@@ -15,14 +26,27 @@ pub fn routes() -> Router<AppState> {
 	Router::new()
 		.nest("/auth", auth::routes())
 		.nest("/admin", admin::routes())
+		.nest("/inbox", inbox::routes())
+		.nest("/rules", rules::routes())
+		.nest("/dashboard", dashboard::routes())
+		.nest("/corpus", corpus::routes())
+		.nest("/templates", templates::routes())
+		.merge(courses::routes())
+		.merge(roster::routes())
 		.route("/docs", get(docs::get_docs).post(docs::add_doc))
 		.route("/docs/:doc_id/revisions", get(docs::get_revisions))
+		.route("/docs/:doc_id/annotations", get(docs::get_annotations))
+		.route("/docs/:doc_id/similarity", get(similarity::get_similarity))
+		.route("/docs/:doc_id/report", get(report::get_report))
+		.route("/docs/:doc_id/style", get(stylometry::get_style_drift))
 		.route("/diffs/:rev_id", get(docs::get_diff))
+		.route("/search", get(search::search))
 		
 		// Folder & watchlist organization
 		.route("/user_documents/create_folder", post(docs::create_folder))
 		.route("/user_documents/edit", patch(docs::edit_user_documents))
 		.route("/user_documents/:user_doc_id", get(docs::get_user_docs))
 		.route("/user_documents/:user_doc_id", delete(docs::delete_user_doc))
+		.route("/user_documents/:user_doc_id/drive_folder", post(docs::bind_drive_folder).delete(docs::unbind_drive_folder))
 }
 
@@ -1,13 +1,15 @@
 use axum::{
 	Json, Router,
-	routing::{post, get, delete},
+	routing::{post, get, delete, patch},
 	response::{IntoResponse},
 	extract::{State, Path},
 	http::{StatusCode, HeaderMap}
 };
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
 
 use crate::{state::AppState, users::UserManager};
+use crate::analyzers::{analyzer_statuses, plan_rerun, rerun_analyzers, set_analyzer_enabled, RerunPlan, SettingChange};
 
 #[derive(Deserialize)]
 pub struct CreateUserPayload {
@@ -22,6 +24,12 @@ pub fn routes() -> Router<AppState> {
 		.route("/update-password", post(update_password))
 		.route("/exists/:username", get(user_exists))
 		.route("/list", get(list_users))
+		.route("/auth-status", get(auth_status))
+		.route("/drives", get(list_drives))
+		.route("/drives/:drive_id", patch(update_drive))
+		.route("/analyzers", get(list_analyzers))
+		.route("/analyzers/rerun", post(rerun))
+		.route("/analyzers/:name", patch(update_analyzer))
 }
 
 async fn create_user(
@@ -115,9 +123,172 @@ async fn list_users(
 	}
 }
 
-fn is_authorized(headers: &HeaderMap) -> bool {
-	match headers.get("Authorization").and_then(|v| v.to_str().ok()) {
-		Some(value) if value == format!("Bearer {}", std::env::var("ADMIN_TOKEN").unwrap_or_default()) => true,
-		_ => false,
+// Reports whether the Google credentials still work, e.g. "reauthorization_required" after a revoked token
+async fn auth_status(
+	State(state): State<AppState>,
+	headers: HeaderMap,
+) -> impl IntoResponse {
+	if !is_authorized(&headers) {
+		return (StatusCode::UNAUTHORIZED, "Unauthorized").into_response();
+	}
+
+	Json(state.tokens.status().await).into_response()
+}
+
+#[derive(Serialize)]
+struct SharedDriveRecord {
+	drive_id: String,
+	name: String,
+	enabled: bool,
+	last_polled: Option<String>,
+	last_error: Option<String>,
+}
+
+// Shared drives discovered by the poller, with whether each is allow-listed
+async fn list_drives(
+	State(state): State<AppState>,
+	headers: HeaderMap,
+) -> impl IntoResponse {
+	if !is_authorized(&headers) {
+		return (StatusCode::UNAUTHORIZED, "Unauthorized").into_response();
+	}
+
+	let drives = sqlx::query_as!(
+		SharedDriveRecord,
+		"SELECT drive_id, name, enabled, last_polled, last_error FROM shared_drives ORDER BY name"
+	)
+	.fetch_all(&state.db)
+	.await;
+
+	match drives {
+		Ok(drives) => Json(drives).into_response(),
+		Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Failed to list drives").into_response(),
+	}
+}
+
+#[derive(Deserialize)]
+pub struct UpdateDrivePayload {
+	pub enabled: bool,
+}
+
+async fn update_drive(
+	State(state): State<AppState>,
+	headers: HeaderMap,
+	Path(drive_id): Path<String>,
+	Json(payload): Json<UpdateDrivePayload>,
+) -> (StatusCode, &'static str) {
+	if !is_authorized(&headers) {
+		return (StatusCode::UNAUTHORIZED, "Unauthorized");
+	}
+
+	let result = sqlx::query!(
+		"UPDATE shared_drives SET enabled = ? WHERE drive_id = ?",
+		payload.enabled,
+		drive_id
+	)
+	.execute(&state.db)
+	.await;
+
+	match result {
+		Ok(r) if r.rows_affected() > 0 => (StatusCode::OK, "Drive updated"),
+		Ok(_) => (StatusCode::NOT_FOUND, "Drive not found"),
+		Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Failed to update drive"),
+	}
+}
+
+async fn list_analyzers(
+	State(state): State<AppState>,
+	headers: HeaderMap,
+) -> impl IntoResponse {
+	if !is_authorized(&headers) {
+		return (StatusCode::UNAUTHORIZED, "Unauthorized").into_response();
+	}
+
+	match analyzer_statuses(&state.db).await {
+		Ok(analyzers) => Json(analyzers).into_response(),
+		Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Failed to list analyzers").into_response(),
+	}
+}
+
+#[derive(Deserialize)]
+pub struct UpdateAnalyzerPayload {
+	pub enabled: bool,
+}
+
+async fn update_analyzer(
+	State(state): State<AppState>,
+	headers: HeaderMap,
+	Path(name): Path<String>,
+	Json(payload): Json<UpdateAnalyzerPayload>,
+) -> impl IntoResponse {
+	if !is_authorized(&headers) {
+		return (StatusCode::UNAUTHORIZED, "Unauthorized").into_response();
+	}
+
+	match set_analyzer_enabled(&state.db, &name, payload.enabled).await {
+		Ok(SettingChange::Updated) => (StatusCode::OK, "Analyzer updated").into_response(),
+		Ok(SettingChange::UnknownAnalyzer) => (StatusCode::NOT_FOUND, "Analyzer not found").into_response(),
+		Ok(SettingChange::Blocked(reason)) => (StatusCode::CONFLICT, reason).into_response(),
+		Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Failed to update analyzer").into_response(),
 	}
 }
+
+#[derive(Deserialize)]
+pub struct RerunPayload {
+	pub analyzer: Option<String>, // every enabled analyzer if missing
+	pub doc_id: Option<String>,   // every document if missing
+}
+
+// Re-runs analyzers over stored revisions, replacing their earlier results.
+// Long histories take a while, so the run continues in the background and reports to the server log.
+async fn rerun(
+	State(state): State<AppState>,
+	headers: HeaderMap,
+	Json(payload): Json<RerunPayload>,
+) -> impl IntoResponse {
+	if !is_authorized(&headers) {
+		return (StatusCode::UNAUTHORIZED, "Unauthorized").into_response();
+	}
+
+	let analyzers = match plan_rerun(&state.db, payload.analyzer.as_deref()).await {
+		Ok(RerunPlan::Ready(analyzers)) => analyzers,
+		Ok(RerunPlan::UnknownAnalyzer) => return (StatusCode::NOT_FOUND, "Analyzer not found").into_response(),
+		Ok(RerunPlan::Blocked(reason)) => return (StatusCode::CONFLICT, reason).into_response(),
+		Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+	};
+
+	let document_id = match &payload.doc_id {
+		Some(doc_id) => match sqlx::query_scalar!(r#"SELECT id AS "id!" FROM documents WHERE doc_id = ?"#, doc_id)
+			.fetch_optional(&state.db)
+			.await
+		{
+			Ok(Some(id)) => Some(id),
+			Ok(None) => return (StatusCode::NOT_FOUND, "Document not found").into_response(),
+			Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+		},
+		None => None,
+	};
+
+	let started = json!({ "analyzers": analyzers, "doc_id": payload.doc_id });
+	tokio::spawn(async move {
+		match rerun_analyzers(&state, &analyzers, document_id).await {
+			Ok(summary) => println!(
+				"🔁 Re-ran {} over {} revision(s) of {} document(s), {} failure(s)",
+				summary.analyzers.join(", "),
+				summary.revisions,
+				summary.documents,
+				summary.failures
+			),
+			Err(e) => eprintln!("Error re-running analyzers: {:?}", e),
+		}
+	});
+
+	(StatusCode::ACCEPTED, Json(started)).into_response()
+}
+
+fn is_authorized(headers: &HeaderMap) -> bool {
+	matches!(
+		headers.get("Authorization").and_then(|v| v.to_str().ok()),
+		Some(value) if value == format!("Bearer {}", std::env::var("ADMIN_TOKEN").unwrap_or_default())
+	)
+}
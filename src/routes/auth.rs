@@ -8,6 +8,7 @@ use tower_cookies::{Cookies, Cookie};
 use serde::Deserialize;
 use serde::Serialize;
 
+#[allow(unused_imports)]
 use axum_macros::debug_handler;
 
 use crate::{state::AppState};
@@ -58,6 +59,7 @@ pub async fn get_user_id_from_cookie(
 }
 
 
+#[allow(clippy::single_match)]
 pub async fn login(
 	State(state): State<AppState>,
 	cookies: Cookies,
@@ -0,0 +1,149 @@
+use axum::{
+	body::Bytes,
+	extract::{State, Json, Path, Query},
+	http::{HeaderMap, StatusCode},
+	response::IntoResponse,
+	routing::{get, delete},
+	Router,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tower_cookies::Cookies;
+
+use crate::corpus::{add_reference, REFERENCE_KINDS};
+use crate::google_api::{extract_docx_text, MIME_DOCX};
+use crate::routes::auth::get_user_id_from_cookie;
+use crate::state::AppState;
+
+pub fn routes() -> Router<AppState> {
+	Router::new()
+		.route("/", get(list_references).post(upload_reference))
+		.route("/:reference_id", delete(delete_reference))
+}
+
+#[derive(Deserialize)]
+pub struct UploadQuery {
+	title: Option<String>,
+	kind: Option<String>,      // one of REFERENCE_KINDS, "other" if missing
+	file_name: Option<String>, // a .docx name marks the body as DOCX
+}
+
+#[derive(Serialize)]
+struct ReferenceSummary {
+	id: i64,
+	title: String,
+	kind: String,
+	file_name: Option<String>,
+	uploaded_by: String,
+	created_at: Option<String>,
+}
+
+async fn upload_reference(
+	State(state): State<AppState>,
+	cookies: Cookies,
+	Query(params): Query<UploadQuery>,
+	headers: HeaderMap,
+	body: Bytes,
+) -> impl IntoResponse {
+	let user_id = match get_user_id_from_cookie(&state.db, &cookies).await {
+		Ok(id) => id,
+		Err(code) => return (code, "Unauthorized").into_response(),
+	};
+
+	let kind = params.kind.unwrap_or_else(|| "other".to_string());
+	if !REFERENCE_KINDS.contains(&kind.as_str()) {
+		return (StatusCode::BAD_REQUEST, format!("kind must be one of: {}", REFERENCE_KINDS.join(", "))).into_response();
+	}
+
+	let content = match uploaded_text(&headers, params.file_name.as_deref(), body) {
+		Ok(text) => text,
+		Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+	};
+
+	let title = params
+		.title
+		.filter(|t| !t.trim().is_empty())
+		.or_else(|| params.file_name.clone())
+		.unwrap_or_else(|| "Untitled reference".to_string());
+
+	match add_reference(&state.db, user_id, &title, &kind, params.file_name.as_deref(), &content).await {
+		Ok(id) => Json(json!({ "id": id, "words": content.split_whitespace().count() })).into_response(),
+		Err(e) => {
+			eprintln!("Error adding reference text: {:?}", e);
+			(StatusCode::INTERNAL_SERVER_ERROR, "Failed to add reference text").into_response()
+		}
+	}
+}
+
+// Text of an uploaded file, sent as the raw request body: plain text (UTF-8) or DOCX
+pub(super) fn uploaded_text(headers: &HeaderMap, file_name: Option<&str>, body: Bytes) -> Result<String, String> {
+	let is_docx = headers
+		.get("Content-Type")
+		.and_then(|v| v.to_str().ok())
+		.is_some_and(|v| v.starts_with(MIME_DOCX))
+		|| file_name.is_some_and(|name| name.to_lowercase().ends_with(".docx"));
+
+	let content = if is_docx {
+		extract_docx_text(body.to_vec())?
+	} else {
+		String::from_utf8(body.to_vec()).map_err(|_| "Text files must be UTF-8".to_string())?
+	};
+
+	if content.trim().is_empty() {
+		return Err("The file contains no text".to_string());
+	}
+	Ok(content)
+}
+
+async fn list_references(
+	State(state): State<AppState>,
+	cookies: Cookies,
+) -> impl IntoResponse {
+	let _user_id = match get_user_id_from_cookie(&state.db, &cookies).await {
+		Ok(id) => id,
+		Err(code) => return (code, "Unauthorized").into_response(),
+	};
+
+	let references = sqlx::query_as!(
+		ReferenceSummary,
+		r#"
+		SELECT r.id AS "id!", r.title, r.kind, r.file_name, u.username AS uploaded_by, r.created_at AS "created_at: String"
+		FROM reference_texts r
+		JOIN users u ON u.id = r.user_id
+		ORDER BY r.created_at DESC
+		"#
+	)
+	.fetch_all(&state.db)
+	.await;
+
+	match references {
+		Ok(references) => Json(references).into_response(),
+		Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch reference texts").into_response(),
+	}
+}
+
+// Only the uploader can remove a reference text; its matches go with it
+async fn delete_reference(
+	State(state): State<AppState>,
+	cookies: Cookies,
+	Path(reference_id): Path<i64>,
+) -> impl IntoResponse {
+	let user_id = match get_user_id_from_cookie(&state.db, &cookies).await {
+		Ok(id) => id,
+		Err(code) => return (code, "Unauthorized").into_response(),
+	};
+
+	let result = sqlx::query!(
+		"DELETE FROM reference_texts WHERE id = ? AND user_id = ?",
+		reference_id,
+		user_id
+	)
+	.execute(&state.db)
+	.await;
+
+	match result {
+		Ok(r) if r.rows_affected() > 0 => StatusCode::NO_CONTENT.into_response(),
+		Ok(_) => (StatusCode::NOT_FOUND, "Reference text not found").into_response(),
+		Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete reference text").into_response(),
+	}
+}
@@ -0,0 +1,700 @@
+use std::io::{Cursor, Write};
+
+use axum::{
+	body::Body,
+	extract::{State, Json, Path},
+	response::{IntoResponse, Response},
+	routing::{get, post, delete},
+	http::StatusCode,
+	Router,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::SqlitePool;
+use tower_cookies::Cookies;
+use zip::{write::FileOptions, ZipWriter};
+
+use crate::assignments::{freeze_document, normalize_timestamp, refresh_assignment_late_flags, refresh_assignment_links};
+use crate::routes::auth::get_user_id_from_cookie;
+use crate::state::AppState;
+
+pub fn routes() -> Router<AppState> {
+	Router::new()
+		.route("/courses", get(list_courses).post(create_course))
+		.route("/courses/:course_id", delete(delete_course))
+		.route("/courses/:course_id/assignments", get(list_assignments).post(create_assignment))
+		.route("/assignments/:assignment_id", delete(delete_assignment).patch(update_assignment))
+		.route("/assignments/:assignment_id/documents", post(link_documents))
+		.route("/assignments/:assignment_id/documents/:doc_id", delete(unlink_document))
+		.route("/assignments/:assignment_id/late_edits", get(late_edits))
+		.route("/assignments/:assignment_id/snapshots", get(list_snapshots))
+		.route("/assignments/:assignment_id/snapshots/download", get(download_snapshots))
+}
+
+#[derive(Serialize)]
+struct Course {
+	id: i64,
+	name: String,
+}
+
+#[derive(Serialize)]
+struct Assignment {
+	id: i64,
+	course_id: i64,
+	name: String,
+	open_time: Option<String>,
+	due_time: String,
+	document_count: i64,
+}
+
+#[derive(Deserialize)]
+pub struct CoursePayload {
+	name: String,
+}
+
+#[derive(Deserialize)]
+pub struct AssignmentPayload {
+	name: Option<String>,
+	open_time: Option<String>, // RFC 3339
+	due_time: Option<String>,  // RFC 3339, required when creating
+}
+
+#[derive(Deserialize)]
+pub struct LinkPayload {
+	doc_ids: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct LateEdit {
+	doc_id: String,
+	name: String,
+	owner_username: String,
+	late_revisions: i64,
+	late_added_words: i64,
+	late_deleted_words: i64,
+	first_late_edit: String,
+	last_late_edit: String,
+}
+
+#[derive(Serialize)]
+struct Snapshot {
+	doc_id: String,
+	name: String,
+	owner_username: String,
+	revision_id: Option<i64>,
+	revision_time: Option<String>,
+	frozen_at: String,
+}
+
+pub(super) async fn course_owned_by(db: &SqlitePool, user_id: i64, course_id: i64) -> Result<(), StatusCode> {
+	let course = sqlx::query!("SELECT id FROM courses WHERE id = ? AND user_id = ?", course_id, user_id)
+		.fetch_optional(db)
+		.await
+		.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+	course.map(|_| ()).ok_or(StatusCode::NOT_FOUND)
+}
+
+pub(super) async fn assignment_owned_by(db: &SqlitePool, user_id: i64, assignment_id: i64) -> Result<(), StatusCode> {
+	let assignment = sqlx::query!(
+		"SELECT a.id FROM assignments a JOIN courses c ON c.id = a.course_id WHERE a.id = ? AND c.user_id = ?",
+		assignment_id,
+		user_id
+	)
+	.fetch_optional(db)
+	.await
+	.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+	assignment.map(|_| ()).ok_or(StatusCode::NOT_FOUND)
+}
+
+// Normalizes both times and checks that the assignment opens before it is due
+fn validate_times(open_time: Option<&str>, due_time: &str) -> Result<(Option<String>, String), String> {
+	let due_time = normalize_timestamp(due_time)?;
+	let open_time = open_time.map(normalize_timestamp).transpose()?;
+
+	if open_time.as_deref().is_some_and(|open| open >= due_time.as_str()) {
+		return Err("open_time must be before due_time".to_string());
+	}
+
+	Ok((open_time, due_time))
+}
+
+async fn list_courses(
+	State(state): State<AppState>,
+	cookies: Cookies,
+) -> impl IntoResponse {
+	let user_id = match get_user_id_from_cookie(&state.db, &cookies).await {
+		Ok(id) => id,
+		Err(code) => return (code, "Unauthorized").into_response(),
+	};
+
+	let courses = sqlx::query_as!(
+		Course,
+		r#"SELECT id AS "id!", name FROM courses WHERE user_id = ? ORDER BY name"#,
+		user_id
+	)
+	.fetch_all(&state.db)
+	.await;
+
+	match courses {
+		Ok(courses) => Json(courses).into_response(),
+		Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch courses").into_response(),
+	}
+}
+
+async fn create_course(
+	State(state): State<AppState>,
+	cookies: Cookies,
+	Json(payload): Json<CoursePayload>,
+) -> impl IntoResponse {
+	let user_id = match get_user_id_from_cookie(&state.db, &cookies).await {
+		Ok(id) => id,
+		Err(code) => return (code, "Unauthorized").into_response(),
+	};
+
+	if payload.name.trim().is_empty() {
+		return (StatusCode::BAD_REQUEST, "Course name is required").into_response();
+	}
+
+	let id = sqlx::query_scalar!(
+		r#"INSERT INTO courses (user_id, name) VALUES (?, ?) RETURNING id AS "id!""#,
+		user_id,
+		payload.name
+	)
+	.fetch_one(&state.db)
+	.await;
+
+	match id {
+		Ok(id) => Json(json!({ "id": id })).into_response(),
+		Err(e) => {
+			eprintln!("Error creating course: {:?}", e);
+			(StatusCode::INTERNAL_SERVER_ERROR, "Failed to create course").into_response()
+		}
+	}
+}
+
+async fn delete_course(
+	State(state): State<AppState>,
+	cookies: Cookies,
+	Path(course_id): Path<i64>,
+) -> impl IntoResponse {
+	let user_id = match get_user_id_from_cookie(&state.db, &cookies).await {
+		Ok(id) => id,
+		Err(code) => return (code, "Unauthorized").into_response(),
+	};
+
+	let documents = sqlx::query_scalar!(
+		"SELECT DISTINCT ad.document_id
+		FROM assignment_documents ad
+		JOIN assignments a ON a.id = ad.assignment_id
+		JOIN courses c ON c.id = a.course_id
+		WHERE c.id = ? AND c.user_id = ?",
+		course_id,
+		user_id
+	)
+	.fetch_all(&state.db)
+	.await
+	.unwrap_or_default();
+
+	let result = sqlx::query!("DELETE FROM courses WHERE id = ? AND user_id = ?", course_id, user_id)
+		.execute(&state.db)
+		.await;
+
+	match result {
+		Ok(r) if r.rows_affected() > 0 => {}
+		Ok(_) => return (StatusCode::NOT_FOUND, "Course not found").into_response(),
+		Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete course").into_response(),
+	}
+
+	// Revisions are no longer late for assignments that don't exist anymore, nor covered by their templates
+	for document_id in documents {
+		if let Err(e) = refresh_assignment_links(&state.db, document_id).await {
+			eprintln!("⚠️ Failed to refresh document {} after deleting its course: {:?}", document_id, e);
+		}
+	}
+
+	StatusCode::NO_CONTENT.into_response()
+}
+
+async fn list_assignments(
+	State(state): State<AppState>,
+	cookies: Cookies,
+	Path(course_id): Path<i64>,
+) -> impl IntoResponse {
+	let user_id = match get_user_id_from_cookie(&state.db, &cookies).await {
+		Ok(id) => id,
+		Err(code) => return (code, "Unauthorized").into_response(),
+	};
+
+	if let Err(code) = course_owned_by(&state.db, user_id, course_id).await {
+		return (code, "Course not found").into_response();
+	}
+
+	let assignments = sqlx::query_as!(
+		Assignment,
+		r#"
+		SELECT a.id AS "id!", a.course_id, a.name, a.open_time, a.due_time,
+			(SELECT COUNT(*) FROM assignment_documents ad WHERE ad.assignment_id = a.id) AS "document_count!: i64"
+		FROM assignments a
+		WHERE a.course_id = ?
+		ORDER BY a.due_time
+		"#,
+		course_id
+	)
+	.fetch_all(&state.db)
+	.await;
+
+	match assignments {
+		Ok(assignments) => Json(assignments).into_response(),
+		Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch assignments").into_response(),
+	}
+}
+
+async fn create_assignment(
+	State(state): State<AppState>,
+	cookies: Cookies,
+	Path(course_id): Path<i64>,
+	Json(payload): Json<AssignmentPayload>,
+) -> impl IntoResponse {
+	let user_id = match get_user_id_from_cookie(&state.db, &cookies).await {
+		Ok(id) => id,
+		Err(code) => return (code, "Unauthorized").into_response(),
+	};
+
+	if let Err(code) = course_owned_by(&state.db, user_id, course_id).await {
+		return (code, "Course not found").into_response();
+	}
+
+	let Some(name) = payload.name.filter(|n| !n.trim().is_empty()) else {
+		return (StatusCode::BAD_REQUEST, "Assignment name is required").into_response();
+	};
+	let Some(due_time) = payload.due_time else {
+		return (StatusCode::BAD_REQUEST, "due_time is required").into_response();
+	};
+
+	let (open_time, due_time) = match validate_times(payload.open_time.as_deref(), &due_time) {
+		Ok(times) => times,
+		Err(msg) => return (StatusCode::BAD_REQUEST, msg).into_response(),
+	};
+
+	let id = sqlx::query_scalar!(
+		r#"INSERT INTO assignments (course_id, name, open_time, due_time) VALUES (?, ?, ?, ?) RETURNING id AS "id!""#,
+		course_id,
+		name,
+		open_time,
+		due_time
+	)
+	.fetch_one(&state.db)
+	.await;
+
+	match id {
+		Ok(id) => Json(json!({ "id": id })).into_response(),
+		Err(e) => {
+			eprintln!("Error creating assignment: {:?}", e);
+			(StatusCode::INTERNAL_SERVER_ERROR, "Failed to create assignment").into_response()
+		}
+	}
+}
+
+// Changes name and/or times; moving the due time re-flags every linked document
+async fn update_assignment(
+	State(state): State<AppState>,
+	cookies: Cookies,
+	Path(assignment_id): Path<i64>,
+	Json(payload): Json<AssignmentPayload>,
+) -> impl IntoResponse {
+	let user_id = match get_user_id_from_cookie(&state.db, &cookies).await {
+		Ok(id) => id,
+		Err(code) => return (code, "Unauthorized").into_response(),
+	};
+
+	if let Err(code) = assignment_owned_by(&state.db, user_id, assignment_id).await {
+		return (code, "Assignment not found").into_response();
+	}
+
+	let current = match sqlx::query!("SELECT name, open_time, due_time FROM assignments WHERE id = ?", assignment_id)
+		.fetch_one(&state.db)
+		.await
+	{
+		Ok(row) => row,
+		Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+	};
+
+	let name = payload.name.filter(|n| !n.trim().is_empty()).unwrap_or(current.name);
+	let open_time = payload.open_time.or(current.open_time);
+	let due_time = payload.due_time.unwrap_or(current.due_time.clone());
+
+	let (open_time, due_time) = match validate_times(open_time.as_deref(), &due_time) {
+		Ok(times) => times,
+		Err(msg) => return (StatusCode::BAD_REQUEST, msg).into_response(),
+	};
+
+	let result = sqlx::query!(
+		"UPDATE assignments SET name = ?, open_time = ?, due_time = ? WHERE id = ?",
+		name,
+		open_time,
+		due_time,
+		assignment_id
+	)
+	.execute(&state.db)
+	.await;
+
+	if result.is_err() {
+		return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to update assignment").into_response();
+	}
+
+	// A moved deadline gets new snapshots once it passes
+	if due_time != current.due_time {
+		let cleared = sqlx::query!("DELETE FROM assignment_snapshots WHERE assignment_id = ?", assignment_id)
+			.execute(&state.db)
+			.await;
+		let reset = sqlx::query!("UPDATE assignments SET frozen_at = NULL WHERE id = ?", assignment_id)
+			.execute(&state.db)
+			.await;
+
+		if cleared.is_err() || reset.is_err() {
+			return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to reset snapshots").into_response();
+		}
+	}
+
+	match refresh_assignment_late_flags(&state.db, assignment_id).await {
+		Ok(()) => StatusCode::NO_CONTENT.into_response(),
+		Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Failed to refresh late flags").into_response(),
+	}
+}
+
+async fn delete_assignment(
+	State(state): State<AppState>,
+	cookies: Cookies,
+	Path(assignment_id): Path<i64>,
+) -> impl IntoResponse {
+	let user_id = match get_user_id_from_cookie(&state.db, &cookies).await {
+		Ok(id) => id,
+		Err(code) => return (code, "Unauthorized").into_response(),
+	};
+
+	if let Err(code) = assignment_owned_by(&state.db, user_id, assignment_id).await {
+		return (code, "Assignment not found").into_response();
+	}
+
+	let documents = sqlx::query_scalar!(
+		"SELECT document_id FROM assignment_documents WHERE assignment_id = ?",
+		assignment_id
+	)
+	.fetch_all(&state.db)
+	.await
+	.unwrap_or_default();
+
+	if sqlx::query!("DELETE FROM assignments WHERE id = ?", assignment_id)
+		.execute(&state.db)
+		.await
+		.is_err()
+	{
+		return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete assignment").into_response();
+	}
+
+	for document_id in documents {
+		if let Err(e) = refresh_assignment_links(&state.db, document_id).await {
+			eprintln!("⚠️ Failed to refresh document {} after deleting its assignment: {:?}", document_id, e);
+		}
+	}
+
+	StatusCode::NO_CONTENT.into_response()
+}
+
+async fn link_documents(
+	State(state): State<AppState>,
+	cookies: Cookies,
+	Path(assignment_id): Path<i64>,
+	Json(payload): Json<LinkPayload>,
+) -> impl IntoResponse {
+	let user_id = match get_user_id_from_cookie(&state.db, &cookies).await {
+		Ok(id) => id,
+		Err(code) => return (code, "Unauthorized").into_response(),
+	};
+
+	if let Err(code) = assignment_owned_by(&state.db, user_id, assignment_id).await {
+		return (code, "Assignment not found").into_response();
+	}
+
+	let frozen = match sqlx::query!("SELECT due_time, frozen_at FROM assignments WHERE id = ?", assignment_id)
+		.fetch_one(&state.db)
+		.await
+	{
+		Ok(row) => row.frozen_at.map(|_| row.due_time),
+		Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+	};
+
+	let mut linked = 0;
+	for doc_id in &payload.doc_ids {
+		let document_id = match sqlx::query_scalar!(r#"SELECT id AS "id!" FROM documents WHERE doc_id = ?"#, doc_id)
+			.fetch_optional(&state.db)
+			.await
+		{
+			Ok(Some(id)) => id,
+			Ok(None) => continue,
+			Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+		};
+
+		let result = sqlx::query!(
+			"INSERT OR IGNORE INTO assignment_documents (assignment_id, document_id) VALUES (?, ?)",
+			assignment_id,
+			document_id
+		)
+		.execute(&state.db)
+		.await;
+
+		match result {
+			Ok(r) => linked += r.rows_affected(),
+			Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to link documents").into_response(),
+		}
+
+		// Revisions made before the link existed are flagged too
+		if refresh_assignment_links(&state.db, document_id).await.is_err() {
+			return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to refresh late flags").into_response();
+		}
+
+		// Linked after the deadline passed: snapshot from the revisions already stored
+		if let Some(due_time) = &frozen {
+			if freeze_document(&state.db, assignment_id, document_id, due_time).await.is_err() {
+				return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to freeze snapshot").into_response();
+			}
+		}
+	}
+
+	Json(json!({ "linked": linked })).into_response()
+}
+
+async fn unlink_document(
+	State(state): State<AppState>,
+	cookies: Cookies,
+	Path((assignment_id, doc_id)): Path<(i64, String)>,
+) -> impl IntoResponse {
+	let user_id = match get_user_id_from_cookie(&state.db, &cookies).await {
+		Ok(id) => id,
+		Err(code) => return (code, "Unauthorized").into_response(),
+	};
+
+	if let Err(code) = assignment_owned_by(&state.db, user_id, assignment_id).await {
+		return (code, "Assignment not found").into_response();
+	}
+
+	let document_id = match sqlx::query_scalar!(r#"SELECT id AS "id!" FROM documents WHERE doc_id = ?"#, doc_id)
+		.fetch_optional(&state.db)
+		.await
+	{
+		Ok(Some(id)) => id,
+		Ok(None) => return (StatusCode::NOT_FOUND, "Document not found").into_response(),
+		Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+	};
+
+	if sqlx::query!(
+		"DELETE FROM assignment_snapshots WHERE assignment_id = ? AND document_id = ?",
+		assignment_id,
+		document_id
+	)
+	.execute(&state.db)
+	.await
+	.is_err()
+	{
+		return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to remove snapshot").into_response();
+	}
+
+	let result = sqlx::query!(
+		"DELETE FROM assignment_documents WHERE assignment_id = ? AND document_id = ?",
+		assignment_id,
+		document_id
+	)
+	.execute(&state.db)
+	.await;
+
+	match result {
+		Ok(r) if r.rows_affected() > 0 => {}
+		Ok(_) => return (StatusCode::NOT_FOUND, "Document is not linked").into_response(),
+		Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to unlink document").into_response(),
+	}
+
+	match refresh_assignment_links(&state.db, document_id).await {
+		Ok(()) => StatusCode::NO_CONTENT.into_response(),
+		Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Failed to refresh late flags").into_response(),
+	}
+}
+
+// Linked documents with revisions after this assignment's due time, and how much changed late
+async fn late_edits(
+	State(state): State<AppState>,
+	cookies: Cookies,
+	Path(assignment_id): Path<i64>,
+) -> impl IntoResponse {
+	let user_id = match get_user_id_from_cookie(&state.db, &cookies).await {
+		Ok(id) => id,
+		Err(code) => return (code, "Unauthorized").into_response(),
+	};
+
+	if let Err(code) = assignment_owned_by(&state.db, user_id, assignment_id).await {
+		return (code, "Assignment not found").into_response();
+	}
+
+	let rows = sqlx::query_as!(
+		LateEdit,
+		r#"
+		SELECT d.doc_id AS "doc_id!", d.name AS "name!", d.owner_username AS "owner_username!",
+			COUNT(r.id) AS "late_revisions!: i64",
+			COALESCE(SUM(r.added_words - r.template_words), 0) AS "late_added_words!: i64",
+			COALESCE(SUM(r.deleted_words), 0) AS "late_deleted_words!: i64",
+			MIN(r.revision_time) AS "first_late_edit!: String",
+			MAX(r.revision_time) AS "last_late_edit!: String"
+		FROM assignment_documents ad
+		JOIN assignments a ON a.id = ad.assignment_id
+		JOIN documents d ON d.id = ad.document_id
+		JOIN document_revisions r ON r.document_id = d.id AND r.revision_time > a.due_time
+		WHERE ad.assignment_id = ?
+		GROUP BY d.id
+		ORDER BY d.name
+		"#,
+		assignment_id
+	)
+	.fetch_all(&state.db)
+	.await;
+
+	match rows {
+		Ok(rows) => Json(rows).into_response(),
+		Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch late edits").into_response(),
+	}
+}
+
+async fn frozen_snapshots(db: &SqlitePool, assignment_id: i64) -> Result<Vec<(Snapshot, Option<String>)>, sqlx::Error> {
+	let rows = sqlx::query!(
+		r#"
+		SELECT d.doc_id, d.name, d.owner_username, s.revision_id, s.frozen_at,
+			r.revision_time AS "revision_time?", r.content AS "content?"
+		FROM assignment_snapshots s
+		JOIN documents d ON d.id = s.document_id
+		LEFT JOIN document_revisions r ON r.id = s.revision_id
+		WHERE s.assignment_id = ?
+		ORDER BY d.owner_username, d.name
+		"#,
+		assignment_id
+	)
+	.fetch_all(db)
+	.await?;
+
+	Ok(rows
+		.into_iter()
+		.map(|r| {
+			let snapshot = Snapshot {
+				doc_id: r.doc_id,
+				name: r.name,
+				owner_username: r.owner_username,
+				revision_id: r.revision_id,
+				revision_time: r.revision_time,
+				frozen_at: r.frozen_at,
+			};
+			(snapshot, r.content)
+		})
+		.collect())
+}
+
+// Which revision of each linked document counts as submitted
+async fn list_snapshots(
+	State(state): State<AppState>,
+	cookies: Cookies,
+	Path(assignment_id): Path<i64>,
+) -> impl IntoResponse {
+	let user_id = match get_user_id_from_cookie(&state.db, &cookies).await {
+		Ok(id) => id,
+		Err(code) => return (code, "Unauthorized").into_response(),
+	};
+
+	if let Err(code) = assignment_owned_by(&state.db, user_id, assignment_id).await {
+		return (code, "Assignment not found").into_response();
+	}
+
+	match frozen_snapshots(&state.db, assignment_id).await {
+		Ok(rows) => Json(rows.into_iter().map(|(snapshot, _)| snapshot).collect::<Vec<_>>()).into_response(),
+		Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch snapshots").into_response(),
+	}
+}
+
+// Zip with one text file per snapshot plus a manifest of every linked document
+async fn download_snapshots(
+	State(state): State<AppState>,
+	cookies: Cookies,
+	Path(assignment_id): Path<i64>,
+) -> impl IntoResponse {
+	let user_id = match get_user_id_from_cookie(&state.db, &cookies).await {
+		Ok(id) => id,
+		Err(code) => return (code, "Unauthorized").into_response(),
+	};
+
+	if let Err(code) = assignment_owned_by(&state.db, user_id, assignment_id).await {
+		return (code, "Assignment not found").into_response();
+	}
+
+	let assignment = match sqlx::query!("SELECT name, frozen_at FROM assignments WHERE id = ?", assignment_id)
+		.fetch_one(&state.db)
+		.await
+	{
+		Ok(row) => row,
+		Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+	};
+
+	if assignment.frozen_at.is_none() {
+		return (StatusCode::CONFLICT, "Snapshots are taken when the assignment is due").into_response();
+	}
+
+	let rows = match frozen_snapshots(&state.db, assignment_id).await {
+		Ok(rows) => rows,
+		Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch snapshots").into_response(),
+	};
+
+	match build_snapshot_zip(rows) {
+		Ok(bytes) => Response::builder()
+			.status(StatusCode::OK)
+			.header("Content-Type", "application/zip")
+			.header(
+				"Content-Disposition",
+				format!("attachment; filename=\"snapshots-{}.zip\"", assignment_id),
+			)
+			.body(Body::from(bytes))
+			.unwrap()
+			.into_response(),
+		Err(e) => {
+			eprintln!("Error building snapshot zip: {:?}", e);
+			(StatusCode::INTERNAL_SERVER_ERROR, "Failed to build zip").into_response()
+		}
+	}
+}
+
+fn build_snapshot_zip(rows: Vec<(Snapshot, Option<String>)>) -> zip::result::ZipResult<Vec<u8>> {
+	let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+	let options = FileOptions::default();
+
+	let mut manifest = Vec::new();
+	for (snapshot, content) in rows {
+		if let Some(content) = content {
+			// The doc id keeps files apart when students share a name or reuse a title
+			let file_name = format!(
+				"{} - {} ({}).txt",
+				safe_file_name(&snapshot.owner_username),
+				safe_file_name(&snapshot.name),
+				snapshot.doc_id
+			);
+			zip.start_file(file_name, options)?;
+			zip.write_all(content.as_bytes())?;
+		}
+		manifest.push(snapshot);
+	}
+
+	zip.start_file("manifest.json", options)?;
+	zip.write_all(serde_json::to_string_pretty(&manifest).unwrap_or_default().as_bytes())?;
+
+	Ok(zip.finish()?.into_inner())
+}
+
+fn safe_file_name(name: &str) -> String {
+	name.chars()
+		.map(|c| if c.is_control() || "/\\:*?\"<>|".contains(c) { '_' } else { c })
+		.collect()
+}
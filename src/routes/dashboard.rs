@@ -0,0 +1,116 @@
+use axum::{
+	extract::{State, Json, Path},
+	response::IntoResponse,
+	routing::get,
+	http::StatusCode,
+	Router,
+};
+use tower_cookies::Cookies;
+
+use crate::dashboard::build_dashboard;
+use crate::routes::auth::get_user_id_from_cookie;
+use crate::state::AppState;
+
+use super::courses::assignment_owned_by;
+
+// Class-level views over every document in a folder (including subfolders) or linked to an assignment
+
+pub fn routes() -> Router<AppState> {
+	Router::new()
+		.route("/folder/:user_doc_id", get(folder_dashboard))
+		.route("/assignment/:assignment_id", get(assignment_dashboard))
+}
+
+async fn folder_dashboard(
+	State(state): State<AppState>,
+	cookies: Cookies,
+	Path(user_doc_id): Path<String>,
+) -> impl IntoResponse {
+	let user_id = match get_user_id_from_cookie(&state.db, &cookies).await {
+		Ok(id) => id,
+		Err(code) => return (code, "Unauthorized").into_response(),
+	};
+
+	// "home" covers the user's whole watchlist
+	let document_ids = if user_doc_id == "home" {
+		sqlx::query_scalar!(
+			r#"SELECT document_id AS "document_id!" FROM user_documents WHERE user_id = ? AND document_id IS NOT NULL"#,
+			user_id
+		)
+		.fetch_all(&state.db)
+		.await
+	} else {
+		let folder = sqlx::query!(
+			"SELECT id FROM user_documents WHERE id = ? AND user_id = ? AND is_folder = TRUE",
+			user_doc_id,
+			user_id
+		)
+		.fetch_optional(&state.db)
+		.await;
+
+		match folder {
+			Ok(Some(_)) => {}
+			Ok(None) => return (StatusCode::NOT_FOUND, "Folder not found").into_response(),
+			Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+		}
+
+		sqlx::query_scalar!(
+			r#"
+			WITH RECURSIVE tree(id) AS (
+				SELECT id FROM user_documents WHERE id = ?
+				UNION
+				SELECT ud.id FROM user_documents ud JOIN tree ON ud.id_parent = tree.id
+			)
+			SELECT DISTINCT ud.document_id AS "document_id!"
+			FROM user_documents ud
+			JOIN tree ON ud.id = tree.id
+			WHERE ud.document_id IS NOT NULL
+			"#,
+			user_doc_id
+		)
+		.fetch_all(&state.db)
+		.await
+	};
+
+	let document_ids = match document_ids {
+		Ok(ids) => ids,
+		Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch documents").into_response(),
+	};
+
+	match build_dashboard(&state.db, user_id, &document_ids).await {
+		Ok(dashboard) => Json(dashboard).into_response(),
+		Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Failed to build dashboard").into_response(),
+	}
+}
+
+async fn assignment_dashboard(
+	State(state): State<AppState>,
+	cookies: Cookies,
+	Path(assignment_id): Path<i64>,
+) -> impl IntoResponse {
+	let user_id = match get_user_id_from_cookie(&state.db, &cookies).await {
+		Ok(id) => id,
+		Err(code) => return (code, "Unauthorized").into_response(),
+	};
+
+	if let Err(code) = assignment_owned_by(&state.db, user_id, assignment_id).await {
+		return (code, "Assignment not found").into_response();
+	}
+
+	let document_ids = sqlx::query_scalar!(
+		"SELECT document_id FROM assignment_documents WHERE assignment_id = ?",
+		assignment_id
+	)
+	.fetch_all(&state.db)
+	.await;
+
+	let document_ids = match document_ids {
+		Ok(ids) => ids,
+		Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch documents").into_response(),
+	};
+
+	match build_dashboard(&state.db, user_id, &document_ids).await {
+		Ok(dashboard) => Json(dashboard).into_response(),
+		Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Failed to build dashboard").into_response(),
+	}
+}
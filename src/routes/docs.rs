@@ -4,10 +4,13 @@ use crate::state::AppState;
 use tower_cookies::{Cookies};
 use axum::extract::Path;
 
-use crate::google_api::{add_docwatch_property}; //unused but keep for later in case useful
 use crate::routes::auth::get_user_id_from_cookie;
+use crate::google_api::get_folder_name;
+use crate::poller::{diff_words, OwnedWordChange};
+use crate::templates::{template_mask, user_template_hashes, user_template_words};
 
 use serde_json::json;
+use std::collections::HashMap;
 
 use uuid::Uuid;
 
@@ -36,6 +39,10 @@ struct DocRecord {
 	name: String,
 	last_updated: String,
 	owner_username: String,
+	no_extractable_text: bool,
+	drive_name: Option<String>,
+	baseline_words: Option<i64>,
+	parent_doc_id: Option<String>, // the document this one was copied from, if any
 }
 
 #[derive(sqlx::FromRow, Serialize)]
@@ -45,7 +52,9 @@ struct UserDocRecord {
 	name: Option<String>,
 	is_folder: bool,
 	last_updated: Option<String>,
-	owner_username: Option<String>
+	owner_username: Option<String>,
+	no_extractable_text: Option<bool>,
+	drive_folder_id: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -60,18 +69,25 @@ struct UserDocResponse {
 	docs: Vec<UserDocRecord>,
 }
 
-#[derive(Deserialize)]
-struct RawWordChange {
-	#[serde(rename = "type")]
-	change_type: String,
-	text: String,
-}
-
 #[derive(Serialize)]
 struct DiffBlock {
 	#[serde(rename = "type")]
-	block_type: String, // "add", "del", or "neutral"
+	block_type: String, // "add", "del", "neutral", or "template" for text from an instructor template
 	text: String,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	source: Option<CorpusSource>, // reference text an added block also appears in
+}
+
+#[derive(Serialize, Clone)]
+struct CorpusSource {
+	reference_id: i64,
+	title: String,
+	kind: String,
+}
+
+#[derive(Deserialize)]
+pub struct BindDriveFolderRequest {
+	drive_folder_id: String,
 }
 
 #[derive(Deserialize)]
@@ -92,8 +108,10 @@ pub async fn get_docs(
 				sqlx::query_as!(
 					DocRecord,
 					r#"
-					SELECT d.id, d.doc_id, d.name, d.last_updated, d.owner_username
+					SELECT d.id, d.doc_id, d.name, d.last_updated, d.owner_username, d.no_extractable_text, d.drive_name,
+						d.baseline_words, p.doc_id AS "parent_doc_id?"
 					FROM documents d
+					LEFT JOIN documents p ON p.id = d.parent_document_id
 					WHERE LOWER(d.doc_id) LIKE ? OR LOWER(d.name) LIKE ? OR LOWER(d.owner_username) LIKE ?
 					ORDER BY d.last_updated DESC
 					LIMIT 20
@@ -162,7 +180,9 @@ pub async fn get_user_docs(
 				COALESCE(ud.folder_name, d.name) AS name,
 				ud.is_folder,
 				d.last_updated,
-				d.owner_username
+				d.owner_username,
+				d.no_extractable_text AS "no_extractable_text?: bool",
+				ud.drive_folder_id
 			FROM user_documents ud
 			LEFT JOIN documents d ON ud.document_id = d.id
 			WHERE ud.user_id = ? AND ud.id_parent = ?
@@ -183,7 +203,9 @@ pub async fn get_user_docs(
 				COALESCE(ud.folder_name, d.name) AS name,
 				ud.is_folder,
 				d.last_updated,
-				d.owner_username
+				d.owner_username,
+				d.no_extractable_text AS "no_extractable_text?: bool",
+				ud.drive_folder_id
 			FROM user_documents ud
 			LEFT JOIN documents d ON ud.document_id = d.id
 			WHERE ud.user_id = ? AND ud.id_parent IS NULL
@@ -341,51 +363,82 @@ pub async fn get_diff(
 	Path(rev_id): Path<i64>,
 ) -> impl IntoResponse {
 	
-	let _user_id = match get_user_id_from_cookie(&state.db, &cookies).await {
+	let user_id = match get_user_id_from_cookie(&state.db, &cookies).await {
 		Ok(id) => id,
 		Err(code) => return (code, "Unauthorized").into_response(),
 	};
 	
 	let result = sqlx::query!(
 		r#"
-		SELECT diff FROM document_revisions WHERE id = ?
+		SELECT document_id, diff, content FROM document_revisions WHERE id = ?
 		"#,
 		rev_id
 	)
 	.fetch_optional(&state.db)
 	.await;
 
-	match result {
-		Ok(Some(row)) => {
-			let diff_str = row.diff.unwrap_or_else(|| "[]".to_string());
-
-			let parsed: Result<Vec<RawWordChange>, _> = serde_json::from_str(&diff_str);
-			let transformed = match parsed {
-				Ok(items) => {
-					let result: Vec<DiffBlock> = items
-						.into_iter()
-						.map(|item| {
-							let block_type = match item.change_type.as_str() {
-								"Added" => "add",
-								"Removed" => "del",
-								"Unchanged" => "neutral",
-								_ => "neutral",
-							};
-							DiffBlock {
-								block_type: block_type.to_string(),
-								text: item.text,
-							}
-						})
-						.collect();
-					Json(result).into_response()
-				}
-				Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Invalid diff format").into_response(),
-			};
-			transformed
+	let row = match result {
+		Ok(Some(row)) => row,
+		Ok(None) => return (StatusCode::NOT_FOUND, "Revision not found").into_response(),
+		Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+	};
+
+	let diff_str = row.diff.unwrap_or_default();
+
+	// A baseline revision has no stored diff; all of its text is new
+	let items: Vec<OwnedWordChange> = if diff_str.is_empty() {
+		let content = row.content;
+		diff_words("", &content).into_iter().map(Into::into).collect()
+	} else {
+		match serde_json::from_str(&diff_str) {
+			Ok(items) => items,
+			Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Invalid diff format").into_response(),
 		}
-		Ok(None) => (StatusCode::NOT_FOUND, "Revision not found").into_response(),
-		Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
-	}
+	};
+
+	let corpus_matches = sqlx::query!(
+		r#"
+		SELECT m.diff_start, m.diff_end, r.id AS "reference_id!", r.title, r.kind
+		FROM corpus_matches m
+		JOIN reference_texts r ON r.id = m.reference_id
+		WHERE m.revision_id = ?
+		"#,
+		rev_id
+	)
+	.fetch_all(&state.db)
+	.await
+	.unwrap_or_default();
+
+	let template_mask = match user_template_hashes(&state.db, user_id, row.document_id).await {
+		Ok(hashes) => template_mask(&items, &hashes),
+		Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to load templates").into_response(),
+	};
+
+	let result: Vec<DiffBlock> = items
+		.into_iter()
+		.zip(template_mask)
+		.enumerate()
+		.map(|(index, (item, is_template))| {
+			let (block_type, text) = match item {
+				_ if is_template => ("template", item.into_text()),
+				OwnedWordChange::Added(text) => ("add", text),
+				OwnedWordChange::Removed(text) => ("del", text),
+				OwnedWordChange::Unchanged(text) => ("neutral", text),
+			};
+			let index = index as i64;
+			let source = corpus_matches
+				.iter()
+				.find(|m| block_type == "add" && (m.diff_start..=m.diff_end).contains(&index))
+				.map(|m| CorpusSource { reference_id: m.reference_id, title: m.title.clone(), kind: m.kind.clone() });
+			DiffBlock {
+				block_type: block_type.to_string(),
+				text,
+				source,
+			}
+		})
+		.collect();
+
+	Json(result).into_response()
 }
 
 pub async fn delete_user_doc(
@@ -398,7 +451,7 @@ pub async fn delete_user_doc(
 		Err(code) => return (code, "Unauthorized").into_response(),
 	};
 
-	if let Err(_) = sqlx::query!(
+	if sqlx::query!(
 		r#"
 		DELETE FROM user_documents
 		WHERE id_parent = ? AND user_id = ?
@@ -408,11 +461,12 @@ pub async fn delete_user_doc(
 	)
 	.execute(&state.db)
 	.await
+	.is_err()
 	{
 		return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete children").into_response();
 	}
 
-	if let Err(_) = sqlx::query!(
+	if sqlx::query!(
 		r#"
 		DELETE FROM user_documents
 		WHERE id = ? AND user_id = ?
@@ -422,6 +476,7 @@ pub async fn delete_user_doc(
 	)
 	.execute(&state.db)
 	.await
+	.is_err()
 	{
 		return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete document").into_response();
 	}
@@ -435,17 +490,40 @@ pub async fn get_revisions(
 	Path(doc_id): Path<String>,
 ) -> impl IntoResponse {
 	
-	let _user_id = match get_user_id_from_cookie(&state.db, &cookies).await {
+	let user_id = match get_user_id_from_cookie(&state.db, &cookies).await {
 		Ok(id) => id,
 		Err(code) => return (code, "Unauthorized").into_response(),
 	};
-	
+
+	let document_id = match sqlx::query_scalar!(r#"SELECT id AS "id!" FROM documents WHERE doc_id = ?"#, doc_id)
+		.fetch_optional(&state.db)
+		.await
+	{
+		Ok(Some(id)) => id,
+		Ok(None) => return Json(Vec::<serde_json::Value>::new()).into_response(),
+		Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+	};
+
+	// The user's own folder templates, on top of the stored assignment template counts
+	let user_template_words = match user_template_words(&state.db, user_id, document_id).await {
+		Ok(words) => words,
+		Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to load templates").into_response(),
+	};
+
 	let revisions = sqlx::query!(
 		r#"
-		SELECT r.id, r.revision_time, r.added_words, r.deleted_words
+		SELECT r.id, r.revision_time, r.added_words, r.deleted_words, r.image_count, r.table_count, r.is_late, r.template_words,
+			w.rewritten AS "rewritten?", w.sentences_before AS "sentences_before?", w.rewritten_share AS "rewritten_share?",
+			w.mean_edit_ratio, w.is_event AS "rewrite_event?",
+			r.outline,
+			-- The first revision inserted everything it has; later ones are compared with the revision before
+			CASE WHEN ROW_NUMBER() OVER win = 1 THEN r.image_count ELSE r.image_count - LAG(r.image_count) OVER win END AS "image_delta?: i64",
+			CASE WHEN ROW_NUMBER() OVER win = 1 THEN r.table_count ELSE r.table_count - LAG(r.table_count) OVER win END AS "table_delta?: i64"
 		FROM document_revisions r
 		JOIN documents d ON r.document_id = d.id
+		LEFT JOIN revision_rewrites w ON w.revision_id = r.id
 		WHERE d.doc_id = ?
+		WINDOW win AS (ORDER BY r.revision_time, r.id)
 		ORDER BY r.revision_time DESC
 		"#,
 		doc_id
@@ -453,16 +531,105 @@ pub async fn get_revisions(
 	.fetch_all(&state.db)
 	.await;
 
+	let hidden_findings = sqlx::query!(
+		r#"
+		SELECT f.revision_id, f.kind, f.char_offset, f.matched, f.context, f.is_new
+		FROM hidden_text_findings f
+		JOIN document_revisions r ON r.id = f.revision_id
+		JOIN documents d ON r.document_id = d.id
+		WHERE d.doc_id = ?
+		ORDER BY f.revision_id, f.char_offset
+		"#,
+		doc_id
+	)
+	.fetch_all(&state.db)
+	.await;
+
+	let mut hidden_text: HashMap<i64, Vec<serde_json::Value>> = HashMap::new();
+	match hidden_findings {
+		Ok(rows) => {
+			for f in rows {
+				hidden_text.entry(f.revision_id).or_default().push(json!({
+					"kind": f.kind,
+					"char_offset": f.char_offset,
+					"matched": f.matched,
+					"context": f.context,
+					"is_new": f.is_new,
+				}));
+			}
+		}
+		Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch hidden text findings").into_response(),
+	}
+
+	let analyzer_findings = sqlx::query!(
+		r#"
+		SELECT f.revision_id, f.analyzer, f.kind, f.score, f.detail
+		FROM revision_findings f
+		JOIN document_revisions r ON r.id = f.revision_id
+		JOIN documents d ON r.document_id = d.id
+		WHERE d.doc_id = ?
+		ORDER BY f.revision_id, f.id
+		"#,
+		doc_id
+	)
+	.fetch_all(&state.db)
+	.await;
+
+	let mut findings: HashMap<i64, Vec<serde_json::Value>> = HashMap::new();
+	match analyzer_findings {
+		Ok(rows) => {
+			for f in rows {
+				let detail: serde_json::Value = serde_json::from_str(&f.detail).unwrap_or_default();
+				findings.entry(f.revision_id).or_default().push(json!({
+					"analyzer": f.analyzer,
+					"kind": f.kind,
+					"score": f.score,
+					"detail": detail,
+				}));
+			}
+		}
+		Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch revision findings").into_response(),
+	}
+
 	match revisions {
 		Ok(rows) => {
 			let summaries = rows
 				.into_iter()
 				.map(|r| {
+					// Flagged when the revision introduced hidden text, not merely kept what was already there
+					let hidden_text = hidden_text.remove(&r.id).unwrap_or_default();
+					let hidden_text_flagged = hidden_text.iter().any(|f| f["is_new"] == json!(true));
+					let template_words = user_template_words
+						.as_ref()
+						.and_then(|words| words.get(&r.id).copied())
+						.unwrap_or(r.template_words);
 					serde_json::json!({
 						"id": r.id,
 						"revision_time": r.revision_time,
-						"added_words": r.added_words.unwrap_or(0),
+						// Template text isn't the student's writing
+						"added_words": r.added_words.unwrap_or(0) - template_words,
+						"template_words": template_words,
 						"deleted_words": r.deleted_words.unwrap_or(0),
+						"image_count": r.image_count,
+						"table_count": r.table_count,
+						// Net change against the previous revision, negative when images or tables were removed.
+						// Removing one image and adding another nets to 0.
+						"image_delta": r.image_delta,
+						"table_delta": r.table_delta,
+						// Headings, list items and tabs by line, for structured fetches
+						"outline": r.outline.as_deref().and_then(|o| serde_json::from_str::<serde_json::Value>(o).ok()),
+						"is_late": r.is_late,
+						"hidden_text_flagged": hidden_text_flagged,
+						"hidden_text": hidden_text,
+						"findings": findings.remove(&r.id).unwrap_or_default(),
+						// Sentences replaced in place; a concentrated rewrite with little change in length is an event
+						"rewrite": r.sentences_before.map(|sentences_before| json!({
+							"sentences_before": sentences_before,
+							"rewritten": r.rewritten,
+							"rewritten_share": r.rewritten_share,
+							"mean_edit_ratio": r.mean_edit_ratio,
+							"is_event": r.rewrite_event,
+						})),
 					})
 				})
 				.collect::<Vec<_>>();
@@ -473,3 +640,191 @@ pub async fn get_revisions(
 	}
 }
 
+
+// Comments (with replies) and suggestions for a document, dated so they can be laid over the revision timeline
+pub async fn get_annotations(
+	State(state): State<AppState>,
+	cookies: Cookies,
+	Path(doc_id): Path<String>,
+) -> impl IntoResponse {
+	
+	let _user_id = match get_user_id_from_cookie(&state.db, &cookies).await {
+		Ok(id) => id,
+		Err(code) => return (code, "Unauthorized").into_response(),
+	};
+	
+	let comments = sqlx::query!(
+		r#"
+		SELECT c.comment_id, c.parent_comment_id, c.author_name, c.author_email, c.content, c.quoted_text,
+			c.created_time, c.modified_time, c.resolved, c.deleted
+		FROM document_comments c
+		JOIN documents d ON c.document_id = d.id
+		WHERE d.doc_id = ?
+		ORDER BY c.created_time ASC
+		"#,
+		doc_id
+	)
+	.fetch_all(&state.db)
+	.await;
+
+	let suggestions = sqlx::query!(
+		r#"
+		SELECT s.suggestion_id, s.kind, s.text, s.first_seen, s.resolved_at
+		FROM document_suggestions s
+		JOIN documents d ON s.document_id = d.id
+		WHERE d.doc_id = ?
+		ORDER BY s.first_seen ASC
+		"#,
+		doc_id
+	)
+	.fetch_all(&state.db)
+	.await;
+
+	let (Ok(comments), Ok(suggestions)) = (comments, suggestions) else {
+		return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch annotations").into_response();
+	};
+
+	let mut threads: Vec<serde_json::Value> = vec![];
+	for c in comments.iter().filter(|c| c.parent_comment_id.is_none()) {
+		let replies = comments
+			.iter()
+			.filter(|r| r.parent_comment_id.as_deref() == Some(c.comment_id.as_str()))
+			.map(|r| {
+				json!({
+					"id": r.comment_id,
+					"author_name": r.author_name,
+					"author_email": r.author_email,
+					"content": r.content,
+					"created_time": r.created_time,
+					"modified_time": r.modified_time,
+					"deleted": r.deleted,
+				})
+			})
+			.collect::<Vec<_>>();
+
+		threads.push(json!({
+			"id": c.comment_id,
+			"author_name": c.author_name,
+			"author_email": c.author_email,
+			"content": c.content,
+			"quoted_text": c.quoted_text,
+			"created_time": c.created_time,
+			"modified_time": c.modified_time,
+			"resolved": c.resolved,
+			"deleted": c.deleted,
+			"replies": replies,
+		}));
+	}
+
+	let suggestions = suggestions
+		.into_iter()
+		.map(|s| {
+			json!({
+				"id": s.suggestion_id,
+				"kind": s.kind,
+				"text": s.text,
+				"first_seen": s.first_seen,
+				"resolved_at": s.resolved_at,
+			})
+		})
+		.collect::<Vec<_>>();
+
+	Json(json!({
+		"comments": threads,
+		"suggestions": suggestions,
+	}))
+	.into_response()
+}
+
+// Binds one of the user's folders to a Drive folder; the poller then mirrors its contents into it
+pub async fn bind_drive_folder(
+	State(state): State<AppState>,
+	cookies: Cookies,
+	Path(id): Path<String>,
+	Json(payload): Json<BindDriveFolderRequest>,
+) -> impl IntoResponse {
+	let user_id = match get_user_id_from_cookie(&state.db, &cookies).await {
+		Ok(id) => id,
+		Err(code) => return (code, "Unauthorized").into_response(),
+	};
+
+	// Only folders the user created can be bound; mirrored subfolders belong to their root
+	let folder = sqlx::query!(
+		r#"
+		SELECT id FROM user_documents
+		WHERE id = ? AND user_id = ? AND is_folder = TRUE AND mirror_root_id IS NULL
+		"#,
+		id,
+		user_id
+	)
+	.fetch_optional(&state.db)
+	.await;
+
+	match folder {
+		Ok(Some(_)) => {}
+		Ok(None) => return (StatusCode::NOT_FOUND, "Folder not found").into_response(),
+		Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+	}
+
+	let drive_folder_name = match get_folder_name(&state.tokens, &payload.drive_folder_id).await {
+		Ok(name) => name,
+		Err(e) => {
+			eprintln!("Error looking up Drive folder: {}", e);
+			return (StatusCode::BAD_REQUEST, "Drive folder not found or not shared with Docwatch").into_response();
+		}
+	};
+
+	let result = sqlx::query!(
+		"UPDATE user_documents SET drive_folder_id = ? WHERE id = ? AND user_id = ?",
+		payload.drive_folder_id,
+		id,
+		user_id
+	)
+	.execute(&state.db)
+	.await;
+
+	if result.is_err() {
+		return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to bind folder").into_response();
+	}
+
+	// Contents appear on the next poll
+	Json(json!({ "drive_folder_name": drive_folder_name })).into_response()
+}
+
+// Stops mirroring; rows the mirror created stay in place as ordinary folders and watchlist entries
+pub async fn unbind_drive_folder(
+	State(state): State<AppState>,
+	cookies: Cookies,
+	Path(id): Path<String>,
+) -> impl IntoResponse {
+	let user_id = match get_user_id_from_cookie(&state.db, &cookies).await {
+		Ok(id) => id,
+		Err(code) => return (code, "Unauthorized").into_response(),
+	};
+
+	let detached = sqlx::query!(
+		r#"
+		UPDATE user_documents
+		SET mirror_root_id = NULL, drive_folder_id = NULL
+		WHERE mirror_root_id = ? AND user_id = ?
+		"#,
+		id,
+		user_id
+	)
+	.execute(&state.db)
+	.await;
+
+	let unbound = sqlx::query!(
+		"UPDATE user_documents SET drive_folder_id = NULL WHERE id = ? AND user_id = ? AND is_folder = TRUE",
+		id,
+		user_id
+	)
+	.execute(&state.db)
+	.await;
+
+	if detached.is_err() || unbound.is_err() {
+		return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to unbind folder").into_response();
+	}
+
+	StatusCode::NO_CONTENT.into_response()
+}
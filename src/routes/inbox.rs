@@ -0,0 +1,278 @@
+use axum::{
+	extract::{State, Json, Query},
+	response::IntoResponse,
+	routing::{get, post},
+	http::StatusCode,
+	Router,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::SqlitePool;
+use tower_cookies::Cookies;
+use uuid::Uuid;
+
+use crate::matching::glob_match;
+use crate::routes::auth::get_user_id_from_cookie;
+use crate::state::AppState;
+
+// The inbox lists documents shared with Docwatch that nobody has put on a watchlist yet
+// and that the current user hasn't ignored.
+
+pub fn routes() -> Router<AppState> {
+	Router::new()
+		.route("/", get(get_inbox))
+		.route("/seen", post(mark_seen))
+		.route("/accept", post(accept))
+		.route("/ignore", post(ignore))
+		.route("/accept_matching", post(accept_matching))
+}
+
+#[derive(Deserialize)]
+pub struct InboxQuery {
+	new_only: Option<bool>,
+}
+
+#[derive(Serialize)]
+struct InboxDoc {
+	id: i64,
+	doc_id: String,
+	name: String,
+	owner_username: String,
+	owner_email: Option<String>,
+	last_updated: String,
+	created_at: Option<String>,
+	is_new: bool,
+}
+
+#[derive(Deserialize)]
+pub struct InboxActionPayload {
+	doc_ids: Vec<String>,
+	id_parent: Option<String>, // folder to accept into; "home" or missing for the top level
+}
+
+#[derive(Deserialize)]
+pub struct AcceptMatchingPayload {
+	owner: Option<String>, // glob, e.g. "*@school.edu"
+	name: Option<String>,  // glob, e.g. "Essay 2*"
+	id_parent: Option<String>,
+}
+
+async fn inbox_docs(db: &SqlitePool, user_id: i64) -> Result<Vec<InboxDoc>, sqlx::Error> {
+	let rows = sqlx::query!(
+		r#"
+		SELECT d.id AS "id!", d.doc_id, d.name, d.owner_username, d.owner_email, d.last_updated, d.created_at AS "created_at: String",
+			(u.inbox_seen_at IS NULL OR d.created_at > u.inbox_seen_at) AS "is_new!: bool"
+		FROM documents d
+		JOIN users u ON u.id = ?
+		WHERE NOT EXISTS (SELECT 1 FROM user_documents ud WHERE ud.document_id = d.id)
+			AND NOT EXISTS (SELECT 1 FROM inbox_ignored i WHERE i.document_id = d.id AND i.user_id = u.id)
+		ORDER BY d.created_at DESC
+		"#,
+		user_id
+	)
+	.fetch_all(db)
+	.await?;
+
+	Ok(rows
+		.into_iter()
+		.map(|r| InboxDoc {
+			id: r.id,
+			doc_id: r.doc_id,
+			name: r.name,
+			owner_username: r.owner_username,
+			owner_email: r.owner_email,
+			last_updated: r.last_updated,
+			created_at: r.created_at,
+			is_new: r.is_new,
+		})
+		.collect())
+}
+
+async fn get_inbox(
+	State(state): State<AppState>,
+	cookies: Cookies,
+	Query(params): Query<InboxQuery>,
+) -> impl IntoResponse {
+	let user_id = match get_user_id_from_cookie(&state.db, &cookies).await {
+		Ok(id) => id,
+		Err(code) => return (code, "Unauthorized").into_response(),
+	};
+
+	match inbox_docs(&state.db, user_id).await {
+		Ok(mut docs) => {
+			if params.new_only.unwrap_or(false) {
+				docs.retain(|d| d.is_new);
+			}
+			Json(docs).into_response()
+		}
+		Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch inbox").into_response(),
+	}
+}
+
+// Records that the user has looked at the inbox, so only later discoveries count as new
+async fn mark_seen(
+	State(state): State<AppState>,
+	cookies: Cookies,
+) -> impl IntoResponse {
+	let user_id = match get_user_id_from_cookie(&state.db, &cookies).await {
+		Ok(id) => id,
+		Err(code) => return (code, "Unauthorized").into_response(),
+	};
+
+	let result = sqlx::query!(
+		"UPDATE users SET inbox_seen_at = CURRENT_TIMESTAMP WHERE id = ?",
+		user_id
+	)
+	.execute(&state.db)
+	.await;
+
+	match result {
+		Ok(_) => StatusCode::NO_CONTENT.into_response(),
+		Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Failed to update inbox").into_response(),
+	}
+}
+
+// Resolves "home"/missing to the top level and checks that any other target is one of the user's folders
+pub(super) async fn resolve_target_folder(db: &SqlitePool, user_id: i64, id_parent: Option<String>) -> Result<Option<String>, StatusCode> {
+	let Some(id) = id_parent.filter(|id| id != "home") else {
+		return Ok(None);
+	};
+
+	let folder = sqlx::query!(
+		"SELECT id FROM user_documents WHERE id = ? AND user_id = ? AND is_folder = TRUE",
+		id,
+		user_id
+	)
+	.fetch_optional(db)
+	.await
+	.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+	match folder {
+		Some(_) => Ok(Some(id)),
+		None => Err(StatusCode::NOT_FOUND),
+	}
+}
+
+async fn accept_documents(
+	db: &SqlitePool,
+	user_id: i64,
+	document_ids: &[i64],
+	id_parent: Option<&str>,
+) -> Result<u64, sqlx::Error> {
+	let mut accepted = 0;
+	for document_id in document_ids {
+		let row_id = Uuid::new_v4().to_string();
+		let res = sqlx::query!(
+			"INSERT OR IGNORE INTO user_documents (id, user_id, document_id, id_parent) VALUES (?, ?, ?, ?)",
+			row_id,
+			user_id,
+			document_id,
+			id_parent
+		)
+		.execute(db)
+		.await?;
+		accepted += res.rows_affected();
+	}
+	Ok(accepted)
+}
+
+async fn accept(
+	State(state): State<AppState>,
+	cookies: Cookies,
+	Json(payload): Json<InboxActionPayload>,
+) -> impl IntoResponse {
+	let user_id = match get_user_id_from_cookie(&state.db, &cookies).await {
+		Ok(id) => id,
+		Err(code) => return (code, "Unauthorized").into_response(),
+	};
+
+	let id_parent = match resolve_target_folder(&state.db, user_id, payload.id_parent).await {
+		Ok(id) => id,
+		Err(code) => return (code, "Folder not found").into_response(),
+	};
+
+	let docs = match inbox_docs(&state.db, user_id).await {
+		Ok(docs) => docs,
+		Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch inbox").into_response(),
+	};
+
+	// Only documents still in the inbox can be accepted from it
+	let ids: Vec<i64> = docs
+		.iter()
+		.filter(|d| payload.doc_ids.contains(&d.doc_id))
+		.map(|d| d.id)
+		.collect();
+
+	match accept_documents(&state.db, user_id, &ids, id_parent.as_deref()).await {
+		Ok(accepted) => Json(json!({ "accepted": accepted })).into_response(),
+		Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Failed to accept documents").into_response(),
+	}
+}
+
+async fn ignore(
+	State(state): State<AppState>,
+	cookies: Cookies,
+	Json(payload): Json<InboxActionPayload>,
+) -> impl IntoResponse {
+	let user_id = match get_user_id_from_cookie(&state.db, &cookies).await {
+		Ok(id) => id,
+		Err(code) => return (code, "Unauthorized").into_response(),
+	};
+
+	for doc_id in &payload.doc_ids {
+		let result = sqlx::query!(
+			"INSERT OR IGNORE INTO inbox_ignored (user_id, document_id)
+			SELECT ?, id FROM documents WHERE doc_id = ?",
+			user_id,
+			doc_id
+		)
+		.execute(&state.db)
+		.await;
+
+		if result.is_err() {
+			return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to ignore documents").into_response();
+		}
+	}
+
+	StatusCode::NO_CONTENT.into_response()
+}
+
+// Accepts every inbox document whose owner and name match the given patterns
+async fn accept_matching(
+	State(state): State<AppState>,
+	cookies: Cookies,
+	Json(payload): Json<AcceptMatchingPayload>,
+) -> impl IntoResponse {
+	let user_id = match get_user_id_from_cookie(&state.db, &cookies).await {
+		Ok(id) => id,
+		Err(code) => return (code, "Unauthorized").into_response(),
+	};
+
+	if payload.owner.is_none() && payload.name.is_none() {
+		return (StatusCode::BAD_REQUEST, "Give an owner or name pattern").into_response();
+	}
+
+	let id_parent = match resolve_target_folder(&state.db, user_id, payload.id_parent).await {
+		Ok(id) => id,
+		Err(code) => return (code, "Folder not found").into_response(),
+	};
+
+	let docs = match inbox_docs(&state.db, user_id).await {
+		Ok(docs) => docs,
+		Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch inbox").into_response(),
+	};
+
+	let ids: Vec<i64> = docs
+		.iter()
+		.filter(|d| payload.owner.as_deref().is_none_or(|p| {
+			glob_match(p, &d.owner_username) || d.owner_email.as_deref().is_some_and(|email| glob_match(p, email))
+		}))
+		.filter(|d| payload.name.as_deref().is_none_or(|p| glob_match(p, &d.name)))
+		.map(|d| d.id)
+		.collect();
+
+	match accept_documents(&state.db, user_id, &ids, id_parent.as_deref()).await {
+		Ok(accepted) => Json(json!({ "accepted": accepted })).into_response(),
+		Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Failed to accept documents").into_response(),
+	}
+}
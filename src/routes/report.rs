@@ -0,0 +1,40 @@
+use axum::{
+	extract::{State, Json, Path},
+	response::IntoResponse,
+	http::StatusCode,
+};
+use tower_cookies::Cookies;
+
+use crate::process_report::build_report;
+use crate::routes::auth::get_user_id_from_cookie;
+use crate::state::AppState;
+
+// Rule-based process report: which rules the document's revision history triggers, and the revisions behind each
+pub async fn get_report(
+	State(state): State<AppState>,
+	cookies: Cookies,
+	Path(doc_id): Path<String>,
+) -> impl IntoResponse {
+	let user_id = match get_user_id_from_cookie(&state.db, &cookies).await {
+		Ok(id) => id,
+		Err(code) => return (code, "Unauthorized").into_response(),
+	};
+
+	let document_id = match sqlx::query_scalar!(r#"SELECT id AS "id!" FROM documents WHERE doc_id = ?"#, doc_id)
+		.fetch_optional(&state.db)
+		.await
+	{
+		Ok(Some(id)) => id,
+		Ok(None) => return (StatusCode::NOT_FOUND, "Document not found").into_response(),
+		Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+	};
+
+	match build_report(&state.db, user_id, document_id).await {
+		Ok(Some(report)) => Json(report).into_response(),
+		Ok(None) => (StatusCode::NOT_FOUND, "Document not found").into_response(),
+		Err(e) => {
+			eprintln!("Error building process report: {:?}", e);
+			(StatusCode::INTERNAL_SERVER_ERROR, "Failed to build report").into_response()
+		}
+	}
+}
@@ -0,0 +1,257 @@
+use std::collections::HashMap;
+
+use axum::{
+	extract::{State, Json, Path},
+	response::IntoResponse,
+	routing::{get, delete},
+	http::StatusCode,
+	Router,
+};
+use serde::Serialize;
+use serde_json::json;
+use tower_cookies::Cookies;
+
+use crate::roster::parse_roster_csv;
+use crate::routes::auth::get_user_id_from_cookie;
+use crate::state::AppState;
+
+use super::courses::{assignment_owned_by, course_owned_by};
+
+// Roster students are matched to documents by the Drive owner's email,
+// which stays stable when display names change or are shared by several students.
+
+pub fn routes() -> Router<AppState> {
+	Router::new()
+		.route("/courses/:course_id/roster", get(get_roster).post(import_roster))
+		.route("/courses/:course_id/roster/:student_id", delete(delete_student))
+		.route("/assignments/:assignment_id/roster", get(assignment_roster))
+}
+
+#[derive(Serialize)]
+struct StudentDoc {
+	doc_id: String,
+	name: String,
+	last_updated: String,
+}
+
+#[derive(Serialize)]
+struct RosterStudent {
+	id: i64,
+	name: String,
+	email: String,
+	student_id: Option<String>,
+	has_shared: bool,
+	documents: Vec<StudentDoc>,
+}
+
+// Expects CSV with name, email and student id; a header row may name the columns in any order.
+// Students already on the roster (same email) are updated.
+async fn import_roster(
+	State(state): State<AppState>,
+	cookies: Cookies,
+	Path(course_id): Path<i64>,
+	body: String,
+) -> impl IntoResponse {
+	let user_id = match get_user_id_from_cookie(&state.db, &cookies).await {
+		Ok(id) => id,
+		Err(code) => return (code, "Unauthorized").into_response(),
+	};
+
+	if let Err(code) = course_owned_by(&state.db, user_id, course_id).await {
+		return (code, "Course not found").into_response();
+	}
+
+	let (entries, errors) = parse_roster_csv(&body);
+
+	let mut imported = 0;
+	for entry in &entries {
+		let result = sqlx::query!(
+			"INSERT INTO roster_students (course_id, name, email, student_id) VALUES (?, ?, ?, ?)
+			ON CONFLICT(course_id, email) DO UPDATE SET name = excluded.name, student_id = excluded.student_id",
+			course_id,
+			entry.name,
+			entry.email,
+			entry.student_id
+		)
+		.execute(&state.db)
+		.await;
+
+		match result {
+			Ok(_) => imported += 1,
+			Err(e) => {
+				eprintln!("Error importing roster entry {}: {:?}", entry.email, e);
+				return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to import roster").into_response();
+			}
+		}
+	}
+
+	Json(json!({ "imported": imported, "errors": errors })).into_response()
+}
+
+async fn get_roster(
+	State(state): State<AppState>,
+	cookies: Cookies,
+	Path(course_id): Path<i64>,
+) -> impl IntoResponse {
+	let user_id = match get_user_id_from_cookie(&state.db, &cookies).await {
+		Ok(id) => id,
+		Err(code) => return (code, "Unauthorized").into_response(),
+	};
+
+	if let Err(code) = course_owned_by(&state.db, user_id, course_id).await {
+		return (code, "Course not found").into_response();
+	}
+
+	// Every document Docwatch can see that a roster student owns
+	let docs = sqlx::query!(
+		r#"
+		SELECT s.id AS "student!", d.doc_id, d.name, d.last_updated
+		FROM roster_students s
+		JOIN documents d ON d.owner_email = s.email COLLATE NOCASE
+		WHERE s.course_id = ?
+		ORDER BY d.name
+		"#,
+		course_id
+	)
+	.fetch_all(&state.db)
+	.await;
+
+	let docs = match docs {
+		Ok(rows) => rows
+			.into_iter()
+			.map(|r| (r.student, StudentDoc { doc_id: r.doc_id, name: r.name, last_updated: r.last_updated }))
+			.collect(),
+		Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch documents").into_response(),
+	};
+
+	match roster_with_docs(&state, course_id, docs).await {
+		Ok(students) => Json(students).into_response(),
+		Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch roster").into_response(),
+	}
+}
+
+async fn roster_with_docs(
+	state: &AppState,
+	course_id: i64,
+	docs: Vec<(i64, StudentDoc)>,
+) -> Result<Vec<RosterStudent>, sqlx::Error> {
+	let mut by_student: HashMap<i64, Vec<StudentDoc>> = HashMap::new();
+	for (student, doc) in docs {
+		by_student.entry(student).or_default().push(doc);
+	}
+
+	let students = sqlx::query!(
+		r#"SELECT id AS "id!", name, email, student_id FROM roster_students WHERE course_id = ? ORDER BY name"#,
+		course_id
+	)
+	.fetch_all(&state.db)
+	.await?;
+
+	Ok(students
+		.into_iter()
+		.map(|s| {
+			let documents = by_student.remove(&s.id).unwrap_or_default();
+			RosterStudent {
+				id: s.id,
+				name: s.name,
+				email: s.email,
+				student_id: s.student_id,
+				has_shared: !documents.is_empty(),
+				documents,
+			}
+		})
+		.collect())
+}
+
+async fn delete_student(
+	State(state): State<AppState>,
+	cookies: Cookies,
+	Path((course_id, student_id)): Path<(i64, i64)>,
+) -> impl IntoResponse {
+	let user_id = match get_user_id_from_cookie(&state.db, &cookies).await {
+		Ok(id) => id,
+		Err(code) => return (code, "Unauthorized").into_response(),
+	};
+
+	if let Err(code) = course_owned_by(&state.db, user_id, course_id).await {
+		return (code, "Course not found").into_response();
+	}
+
+	let result = sqlx::query!(
+		"DELETE FROM roster_students WHERE id = ? AND course_id = ?",
+		student_id,
+		course_id
+	)
+	.execute(&state.db)
+	.await;
+
+	match result {
+		Ok(r) if r.rows_affected() > 0 => StatusCode::NO_CONTENT.into_response(),
+		Ok(_) => (StatusCode::NOT_FOUND, "Student not found").into_response(),
+		Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete student").into_response(),
+	}
+}
+
+// Who has a document linked to this assignment, who doesn't, and linked documents owned by nobody on the roster
+async fn assignment_roster(
+	State(state): State<AppState>,
+	cookies: Cookies,
+	Path(assignment_id): Path<i64>,
+) -> impl IntoResponse {
+	let user_id = match get_user_id_from_cookie(&state.db, &cookies).await {
+		Ok(id) => id,
+		Err(code) => return (code, "Unauthorized").into_response(),
+	};
+
+	if let Err(code) = assignment_owned_by(&state.db, user_id, assignment_id).await {
+		return (code, "Assignment not found").into_response();
+	}
+
+	let course_id = match sqlx::query_scalar!("SELECT course_id FROM assignments WHERE id = ?", assignment_id)
+		.fetch_one(&state.db)
+		.await
+	{
+		Ok(id) => id,
+		Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+	};
+
+	let linked = sqlx::query!(
+		r#"
+		SELECT d.doc_id, d.name, d.last_updated, d.owner_username, d.owner_email,
+			(SELECT s.id FROM roster_students s WHERE s.course_id = ? AND s.email = d.owner_email COLLATE NOCASE) AS "student?: i64"
+		FROM assignment_documents ad
+		JOIN documents d ON d.id = ad.document_id
+		WHERE ad.assignment_id = ?
+		ORDER BY d.name
+		"#,
+		course_id,
+		assignment_id
+	)
+	.fetch_all(&state.db)
+	.await;
+
+	let linked = match linked {
+		Ok(rows) => rows,
+		Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch documents").into_response(),
+	};
+
+	let mut matched = Vec::new();
+	let mut unmatched = Vec::new();
+	for r in linked {
+		let doc = StudentDoc { doc_id: r.doc_id, name: r.name, last_updated: r.last_updated };
+		match r.student {
+			Some(student) => matched.push((student, doc)),
+			None => unmatched.push(json!({
+				"doc_id": doc.doc_id,
+				"name": doc.name,
+				"owner_username": r.owner_username,
+				"owner_email": r.owner_email,
+			})),
+		}
+	}
+
+	match roster_with_docs(&state, course_id, matched).await {
+		Ok(students) => Json(json!({ "students": students, "unmatched_documents": unmatched })).into_response(),
+		Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch roster").into_response(),
+	}
+}
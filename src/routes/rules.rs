@@ -0,0 +1,195 @@
+use axum::{
+	extract::{State, Json, Path},
+	response::IntoResponse,
+	routing::{get, post, delete},
+	http::StatusCode,
+	Router,
+};
+use serde::Deserialize;
+use serde_json::json;
+use tower_cookies::Cookies;
+
+use crate::routes::auth::get_user_id_from_cookie;
+use crate::state::AppState;
+use crate::watch_rules::{apply_rule_retroactively, preview_rule, WatchRule};
+
+use super::inbox::resolve_target_folder;
+
+pub fn routes() -> Router<AppState> {
+	Router::new()
+		.route("/", get(list_rules).post(create_rule))
+		.route("/preview", post(preview))
+		.route("/:rule_id", delete(delete_rule))
+		.route("/:rule_id/apply", post(apply))
+}
+
+#[derive(Deserialize)]
+pub struct RulePayload {
+	name_pattern: Option<String>,
+	owner_pattern: Option<String>,
+	id_parent: Option<String>, // "home" or missing for the top level
+}
+
+// Turns a payload into an unsaved rule, rejecting rules that would match every document
+async fn rule_from_payload(state: &AppState, user_id: i64, payload: RulePayload) -> Result<WatchRule, (StatusCode, &'static str)> {
+	let non_empty = |p: Option<String>| p.filter(|p| !p.trim().is_empty());
+	let name_pattern = non_empty(payload.name_pattern);
+	let owner_pattern = non_empty(payload.owner_pattern);
+
+	if name_pattern.is_none() && owner_pattern.is_none() {
+		return Err((StatusCode::BAD_REQUEST, "Give a name or owner pattern"));
+	}
+
+	let id_parent = resolve_target_folder(&state.db, user_id, payload.id_parent)
+		.await
+		.map_err(|code| (code, "Folder not found"))?;
+
+	Ok(WatchRule {
+		id: 0,
+		user_id,
+		name_pattern,
+		owner_pattern,
+		id_parent,
+		enabled: true,
+	})
+}
+
+async fn list_rules(
+	State(state): State<AppState>,
+	cookies: Cookies,
+) -> impl IntoResponse {
+	let user_id = match get_user_id_from_cookie(&state.db, &cookies).await {
+		Ok(id) => id,
+		Err(code) => return (code, "Unauthorized").into_response(),
+	};
+
+	let rules = sqlx::query_as!(
+		WatchRule,
+		r#"SELECT id AS "id!", user_id, name_pattern, owner_pattern, id_parent, enabled FROM watch_rules WHERE user_id = ? ORDER BY id"#,
+		user_id
+	)
+	.fetch_all(&state.db)
+	.await;
+
+	match rules {
+		Ok(rules) => Json(rules).into_response(),
+		Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch rules").into_response(),
+	}
+}
+
+async fn create_rule(
+	State(state): State<AppState>,
+	cookies: Cookies,
+	Json(payload): Json<RulePayload>,
+) -> impl IntoResponse {
+	let user_id = match get_user_id_from_cookie(&state.db, &cookies).await {
+		Ok(id) => id,
+		Err(code) => return (code, "Unauthorized").into_response(),
+	};
+
+	let rule = match rule_from_payload(&state, user_id, payload).await {
+		Ok(rule) => rule,
+		Err(err) => return err.into_response(),
+	};
+
+	let id = sqlx::query_scalar!(
+		r#"
+		INSERT INTO watch_rules (user_id, name_pattern, owner_pattern, id_parent)
+		VALUES (?, ?, ?, ?)
+		RETURNING id AS "id!"
+		"#,
+		rule.user_id,
+		rule.name_pattern,
+		rule.owner_pattern,
+		rule.id_parent
+	)
+	.fetch_one(&state.db)
+	.await;
+
+	match id {
+		Ok(id) => Json(json!({ "id": id })).into_response(),
+		Err(e) => {
+			eprintln!("Error creating rule: {:?}", e);
+			(StatusCode::INTERNAL_SERVER_ERROR, "Failed to create rule").into_response()
+		}
+	}
+}
+
+async fn delete_rule(
+	State(state): State<AppState>,
+	cookies: Cookies,
+	Path(rule_id): Path<i64>,
+) -> impl IntoResponse {
+	let user_id = match get_user_id_from_cookie(&state.db, &cookies).await {
+		Ok(id) => id,
+		Err(code) => return (code, "Unauthorized").into_response(),
+	};
+
+	let result = sqlx::query!(
+		"DELETE FROM watch_rules WHERE id = ? AND user_id = ?",
+		rule_id,
+		user_id
+	)
+	.execute(&state.db)
+	.await;
+
+	match result {
+		Ok(r) if r.rows_affected() > 0 => StatusCode::NO_CONTENT.into_response(),
+		Ok(_) => (StatusCode::NOT_FOUND, "Rule not found").into_response(),
+		Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete rule").into_response(),
+	}
+}
+
+// Dry run: which existing documents a rule would file, without saving or applying it
+async fn preview(
+	State(state): State<AppState>,
+	cookies: Cookies,
+	Json(payload): Json<RulePayload>,
+) -> impl IntoResponse {
+	let user_id = match get_user_id_from_cookie(&state.db, &cookies).await {
+		Ok(id) => id,
+		Err(code) => return (code, "Unauthorized").into_response(),
+	};
+
+	let rule = match rule_from_payload(&state, user_id, payload).await {
+		Ok(rule) => rule,
+		Err(err) => return err.into_response(),
+	};
+
+	match preview_rule(&state.db, &rule).await {
+		Ok(preview) => Json(preview).into_response(),
+		Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Failed to preview rule").into_response(),
+	}
+}
+
+// Applies a saved rule to documents discovered before it existed
+async fn apply(
+	State(state): State<AppState>,
+	cookies: Cookies,
+	Path(rule_id): Path<i64>,
+) -> impl IntoResponse {
+	let user_id = match get_user_id_from_cookie(&state.db, &cookies).await {
+		Ok(id) => id,
+		Err(code) => return (code, "Unauthorized").into_response(),
+	};
+
+	let rule = sqlx::query_as!(
+		WatchRule,
+		r#"SELECT id AS "id!", user_id, name_pattern, owner_pattern, id_parent, enabled FROM watch_rules WHERE id = ? AND user_id = ?"#,
+		rule_id,
+		user_id
+	)
+	.fetch_optional(&state.db)
+	.await;
+
+	let rule = match rule {
+		Ok(Some(rule)) => rule,
+		Ok(None) => return (StatusCode::NOT_FOUND, "Rule not found").into_response(),
+		Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+	};
+
+	match apply_rule_retroactively(&state.db, &rule).await {
+		Ok(added) => Json(json!({ "added": added })).into_response(),
+		Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Failed to apply rule").into_response(),
+	}
+}
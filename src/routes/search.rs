@@ -0,0 +1,46 @@
+use axum::{
+	extract::{State, Json, Query},
+	response::IntoResponse,
+	http::StatusCode,
+};
+use serde::Deserialize;
+use tower_cookies::Cookies;
+
+use crate::routes::auth::get_user_id_from_cookie;
+use crate::search::search_revisions;
+use crate::state::AppState;
+
+const DEFAULT_LIMIT: i64 = 50;
+const MAX_LIMIT: i64 = 200;
+
+#[derive(Deserialize)]
+pub struct SearchQuery {
+	q: String, // searched as a phrase
+	limit: Option<i64>,
+}
+
+// Full-text search over every revision of the documents on the user's watchlist
+pub async fn search(
+	State(state): State<AppState>,
+	cookies: Cookies,
+	Query(params): Query<SearchQuery>,
+) -> impl IntoResponse {
+	let user_id = match get_user_id_from_cookie(&state.db, &cookies).await {
+		Ok(id) => id,
+		Err(code) => return (code, "Unauthorized").into_response(),
+	};
+
+	let text = params.q.trim();
+	if text.is_empty() {
+		return (StatusCode::BAD_REQUEST, "Search text is empty").into_response();
+	}
+	let limit = params.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+
+	match search_revisions(&state.db, user_id, text, limit).await {
+		Ok(results) => Json(results).into_response(),
+		Err(e) => {
+			eprintln!("Error searching revisions: {:?}", e);
+			(StatusCode::INTERNAL_SERVER_ERROR, "Search failed").into_response()
+		}
+	}
+}
@@ -0,0 +1,149 @@
+use axum::{
+	extract::{State, Json, Path},
+	response::IntoResponse,
+	http::StatusCode,
+};
+use serde::Serialize;
+use serde_json::json;
+use tower_cookies::Cookies;
+
+use crate::routes::auth::get_user_id_from_cookie;
+use crate::similarity::estimated_similarity;
+use crate::state::AppState;
+
+// Only documents at least this similar overall are listed
+const MIN_DOCUMENT_SIMILARITY: f64 = 0.1;
+
+#[derive(Serialize)]
+struct SimilarDocument {
+	doc_id: String,
+	name: String,
+	owner_username: String,
+	similarity: f64,
+}
+
+#[derive(Serialize)]
+struct PassageSide {
+	doc_id: String,
+	name: String,
+	revision_id: i64,
+	start: Option<i64>,
+	end: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct PassageMatch {
+	words: i64,
+	this: PassageSide,
+	other: PassageSide,
+	first_doc_id: String, // the document that contained the passage first
+}
+
+// Documents with similar text overall, and passages this document shares with others.
+// Offsets are character offsets into the content of the given revision.
+pub async fn get_similarity(
+	State(state): State<AppState>,
+	cookies: Cookies,
+	Path(doc_id): Path<String>,
+) -> impl IntoResponse {
+	let _user_id = match get_user_id_from_cookie(&state.db, &cookies).await {
+		Ok(id) => id,
+		Err(code) => return (code, "Unauthorized").into_response(),
+	};
+
+	let document_id = match sqlx::query_scalar!(r#"SELECT id AS "id!" FROM documents WHERE doc_id = ?"#, doc_id)
+		.fetch_optional(&state.db)
+		.await
+	{
+		Ok(Some(id)) => id,
+		Ok(None) => return (StatusCode::NOT_FOUND, "Document not found").into_response(),
+		Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+	};
+
+	let signatures = sqlx::query!(
+		r#"
+		SELECT m.document_id, m.signature, d.doc_id, d.name, d.owner_username
+		FROM document_minhash m
+		JOIN documents d ON d.id = m.document_id
+		"#
+	)
+	.fetch_all(&state.db)
+	.await;
+
+	let signatures = match signatures {
+		Ok(rows) => rows,
+		Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch signatures").into_response(),
+	};
+
+	let mut similar_documents = Vec::new();
+	if let Some(own) = signatures.iter().find(|s| s.document_id == document_id) {
+		for other in signatures.iter().filter(|s| s.document_id != document_id) {
+			let similarity = estimated_similarity(&own.signature, &other.signature);
+			if similarity >= MIN_DOCUMENT_SIMILARITY {
+				similar_documents.push(SimilarDocument {
+					doc_id: other.doc_id.clone(),
+					name: other.name.clone(),
+					owner_username: other.owner_username.clone(),
+					similarity,
+				});
+			}
+		}
+	}
+	similar_documents.sort_by(|a, b| b.similarity.total_cmp(&a.similarity));
+
+	let rows = sqlx::query!(
+		r#"
+		SELECT m.document_id, m.revision_id, m.start_offset, m.end_offset,
+			m.other_revision_id, m.other_start_offset, m.other_end_offset, m.words,
+			d.doc_id, d.name, o.doc_id AS other_doc_id, o.name AS other_name, s.doc_id AS source_doc_id
+		FROM similarity_matches m
+		JOIN documents d ON d.id = m.document_id
+		JOIN documents o ON o.id = m.other_document_id
+		JOIN documents s ON s.id = m.source_document_id
+		WHERE m.document_id = ? OR m.other_document_id = ?
+		ORDER BY m.created_at DESC
+		"#,
+		document_id,
+		document_id
+	)
+	.fetch_all(&state.db)
+	.await;
+
+	let rows = match rows {
+		Ok(rows) => rows,
+		Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch matches").into_response(),
+	};
+
+	// Matches are stored once per pair; show them from this document's side
+	let matches: Vec<PassageMatch> = rows
+		.into_iter()
+		.map(|r| {
+			let recorded = PassageSide {
+				doc_id: r.doc_id,
+				name: r.name,
+				revision_id: r.revision_id,
+				start: Some(r.start_offset),
+				end: Some(r.end_offset),
+			};
+			let counterpart = PassageSide {
+				doc_id: r.other_doc_id,
+				name: r.other_name,
+				revision_id: r.other_revision_id,
+				start: r.other_start_offset,
+				end: r.other_end_offset,
+			};
+			let (this, other) = if r.document_id == document_id {
+				(recorded, counterpart)
+			} else {
+				(counterpart, recorded)
+			};
+			PassageMatch { words: r.words, this, other, first_doc_id: r.source_doc_id }
+		})
+		.collect();
+
+	Json(json!({
+		"similar_documents": similar_documents,
+		"matches": matches,
+	}))
+	.into_response()
+}
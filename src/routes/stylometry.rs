@@ -0,0 +1,44 @@
+use axum::{
+	extract::{State, Json, Path},
+	response::IntoResponse,
+	http::StatusCode,
+};
+use serde_json::json;
+use tower_cookies::Cookies;
+
+use crate::routes::auth::get_user_id_from_cookie;
+use crate::state::AppState;
+use crate::stylometry::document_drift;
+
+// Style of the text each revision added, compared with the same student's earlier writing in other documents.
+// Revisions whose style deviates strongly from that baseline are flagged.
+pub async fn get_style_drift(
+	State(state): State<AppState>,
+	cookies: Cookies,
+	Path(doc_id): Path<String>,
+) -> impl IntoResponse {
+	let _user_id = match get_user_id_from_cookie(&state.db, &cookies).await {
+		Ok(id) => id,
+		Err(code) => return (code, "Unauthorized").into_response(),
+	};
+
+	let document_id = match sqlx::query_scalar!(r#"SELECT id AS "id!" FROM documents WHERE doc_id = ?"#, doc_id)
+		.fetch_optional(&state.db)
+		.await
+	{
+		Ok(Some(id)) => id,
+		Ok(None) => return (StatusCode::NOT_FOUND, "Document not found").into_response(),
+		Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+	};
+
+	match document_drift(&state.db, document_id).await {
+		Ok(revisions) => {
+			let flagged: Vec<i64> = revisions.iter().filter(|r| r.flagged).map(|r| r.revision_id).collect();
+			Json(json!({ "revisions": revisions, "flagged_revisions": flagged })).into_response()
+		}
+		Err(e) => {
+			eprintln!("Error computing style drift: {:?}", e);
+			(StatusCode::INTERNAL_SERVER_ERROR, "Failed to compute style drift").into_response()
+		}
+	}
+}
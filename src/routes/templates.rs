@@ -0,0 +1,185 @@
+use axum::{
+	body::Bytes,
+	extract::{State, Json, Path, Query},
+	http::{HeaderMap, StatusCode},
+	response::IntoResponse,
+	routing::{get, delete},
+	Router,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::SqlitePool;
+use tower_cookies::Cookies;
+
+use crate::routes::auth::get_user_id_from_cookie;
+use crate::state::AppState;
+use crate::templates::refresh_template_words;
+
+use super::corpus::uploaded_text;
+use super::courses::assignment_owned_by;
+use super::inbox::resolve_target_folder;
+
+pub fn routes() -> Router<AppState> {
+	Router::new()
+		.route("/", get(list_templates).post(upload_template))
+		.route("/:template_id", delete(delete_template))
+}
+
+#[derive(Deserialize)]
+pub struct TemplateQuery {
+	title: Option<String>,
+	assignment_id: Option<i64>, // give exactly one of assignment_id and folder_id
+	folder_id: Option<String>,
+	file_name: Option<String>,
+}
+
+#[derive(Serialize)]
+struct TemplateSummary {
+	id: i64,
+	title: String,
+	assignment_id: Option<i64>,
+	folder_id: Option<String>,
+	created_at: Option<String>,
+}
+
+// Recounts the stored template words of an assignment's documents.
+// Folder templates are applied when their owner reads a document, so they have nothing stored to recount.
+async fn refresh_scope(db: &SqlitePool, assignment_id: Option<i64>) -> Result<(), sqlx::Error> {
+	if let Some(assignment_id) = assignment_id {
+		let documents = sqlx::query_scalar!(
+			"SELECT document_id FROM assignment_documents WHERE assignment_id = ?",
+			assignment_id
+		)
+		.fetch_all(db)
+		.await?;
+
+		for document_id in documents {
+			refresh_template_words(db, document_id).await?;
+		}
+	}
+
+	Ok(())
+}
+
+// The template file is sent as the raw request body: plain text (UTF-8) or DOCX
+async fn upload_template(
+	State(state): State<AppState>,
+	cookies: Cookies,
+	Query(params): Query<TemplateQuery>,
+	headers: HeaderMap,
+	body: Bytes,
+) -> impl IntoResponse {
+	let user_id = match get_user_id_from_cookie(&state.db, &cookies).await {
+		Ok(id) => id,
+		Err(code) => return (code, "Unauthorized").into_response(),
+	};
+
+	let (assignment_id, folder_id) = match (params.assignment_id, params.folder_id) {
+		(Some(assignment_id), None) => {
+			if let Err(code) = assignment_owned_by(&state.db, user_id, assignment_id).await {
+				return (code, "Assignment not found").into_response();
+			}
+			(Some(assignment_id), None)
+		}
+		(None, Some(folder_id)) if folder_id != "home" => {
+			match resolve_target_folder(&state.db, user_id, Some(folder_id)).await {
+				Ok(folder) => (None, folder),
+				Err(code) => return (code, "Folder not found").into_response(),
+			}
+		}
+		_ => return (StatusCode::BAD_REQUEST, "Give either an assignment_id or a folder_id").into_response(),
+	};
+
+	let content = match uploaded_text(&headers, params.file_name.as_deref(), body) {
+		Ok(text) => text,
+		Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+	};
+
+	let title = params
+		.title
+		.filter(|t| !t.trim().is_empty())
+		.or(params.file_name)
+		.unwrap_or_else(|| "Template".to_string());
+
+	let id = sqlx::query_scalar!(
+		r#"INSERT INTO templates (user_id, assignment_id, folder_id, title, content) VALUES (?, ?, ?, ?, ?) RETURNING id AS "id!""#,
+		user_id,
+		assignment_id,
+		folder_id,
+		title,
+		content
+	)
+	.fetch_one(&state.db)
+	.await;
+
+	let id = match id {
+		Ok(id) => id,
+		Err(e) => {
+			eprintln!("Error creating template: {:?}", e);
+			return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to create template").into_response();
+		}
+	};
+
+	match refresh_scope(&state.db, assignment_id).await {
+		Ok(()) => Json(json!({ "id": id })).into_response(),
+		Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Failed to recount template words").into_response(),
+	}
+}
+
+async fn list_templates(
+	State(state): State<AppState>,
+	cookies: Cookies,
+) -> impl IntoResponse {
+	let user_id = match get_user_id_from_cookie(&state.db, &cookies).await {
+		Ok(id) => id,
+		Err(code) => return (code, "Unauthorized").into_response(),
+	};
+
+	let templates = sqlx::query_as!(
+		TemplateSummary,
+		r#"
+		SELECT id AS "id!", title, assignment_id, folder_id, created_at AS "created_at: String"
+		FROM templates
+		WHERE user_id = ?
+		ORDER BY created_at DESC
+		"#,
+		user_id
+	)
+	.fetch_all(&state.db)
+	.await;
+
+	match templates {
+		Ok(templates) => Json(templates).into_response(),
+		Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch templates").into_response(),
+	}
+}
+
+async fn delete_template(
+	State(state): State<AppState>,
+	cookies: Cookies,
+	Path(template_id): Path<i64>,
+) -> impl IntoResponse {
+	let user_id = match get_user_id_from_cookie(&state.db, &cookies).await {
+		Ok(id) => id,
+		Err(code) => return (code, "Unauthorized").into_response(),
+	};
+
+	let template = sqlx::query!(
+		"DELETE FROM templates WHERE id = ? AND user_id = ? RETURNING assignment_id",
+		template_id,
+		user_id
+	)
+	.fetch_optional(&state.db)
+	.await;
+
+	let template = match template {
+		Ok(Some(row)) => row,
+		Ok(None) => return (StatusCode::NOT_FOUND, "Template not found").into_response(),
+		Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete template").into_response(),
+	};
+
+	match refresh_scope(&state.db, template.assignment_id).await {
+		Ok(()) => StatusCode::NO_CONTENT.into_response(),
+		Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Failed to recount template words").into_response(),
+	}
+}
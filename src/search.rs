@@ -0,0 +1,97 @@
+use serde::Serialize;
+use sqlx::SqlitePool;
+
+// Adds a new revision's content to the full-text index
+pub async fn index_revision_text(pool: &SqlitePool, revision_id: i64, content: &str) -> Result<(), sqlx::Error> {
+	sqlx::query!("INSERT INTO revision_fts (rowid, content) VALUES (?, ?)", revision_id, content)
+		.execute(pool)
+		.await?;
+	Ok(())
+}
+
+// The user's text as a single FTS5 phrase, so punctuation and operators in it are matched literally
+fn phrase_query(text: &str) -> String {
+	format!("\"{}\"", text.replace('"', "\"\""))
+}
+
+#[derive(Debug, Serialize)]
+pub struct FirstAppearance {
+	pub revision_id: i64,
+	pub revision_time: String,
+	pub snippet: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchResult {
+	pub doc_id: String,
+	pub name: String,
+	pub owner_username: String,
+	pub matching_revisions: i64,
+	pub first_appearance: FirstAppearance,
+}
+
+// Documents on the user's watchlist whose text contains the phrase, with the earliest revision containing it.
+// Documents are ordered by how well their best revision matches.
+pub async fn search_revisions(
+	pool: &SqlitePool,
+	user_id: i64,
+	text: &str,
+	limit: i64,
+) -> Result<Vec<SearchResult>, sqlx::Error> {
+	let query = phrase_query(text);
+
+	// Ranking, counting and picking each document's first revision all happen in SQL,
+	// and the snippet is only built for the revisions that are returned
+	let rows = sqlx::query!(
+		r#"
+		WITH matches AS (
+			SELECT r.id, r.document_id, r.revision_time, bm25(revision_fts) AS rank
+			FROM revision_fts
+			JOIN document_revisions r ON r.id = revision_fts.rowid
+			WHERE revision_fts MATCH ?
+				AND r.document_id IN (SELECT document_id FROM user_documents WHERE user_id = ? AND document_id IS NOT NULL)
+		),
+		per_document AS (
+			SELECT id, document_id, revision_time,
+				ROW_NUMBER() OVER (PARTITION BY document_id ORDER BY revision_time, id) AS n,
+				COUNT(*) OVER (PARTITION BY document_id) AS matching_revisions,
+				MIN(rank) OVER (PARTITION BY document_id) AS best_rank
+			FROM matches
+		),
+		firsts AS (
+			-- bm25 ranks better matches lower
+			SELECT * FROM per_document WHERE n = 1 ORDER BY best_rank LIMIT ?
+		)
+		SELECT f.id AS "revision_id!", f.revision_time AS "revision_time!: String",
+			f.matching_revisions AS "matching_revisions!: i64",
+			snippet(revision_fts, 0, '[', ']', '…', 12) AS "snippet!: String",
+			d.doc_id, d.name, d.owner_username
+		FROM firsts f
+		JOIN revision_fts ON revision_fts.rowid = f.id
+		JOIN documents d ON d.id = f.document_id
+		WHERE revision_fts MATCH ?
+		ORDER BY f.best_rank
+		"#,
+		query,
+		user_id,
+		limit,
+		query
+	)
+	.fetch_all(pool)
+	.await?;
+
+	Ok(rows
+		.into_iter()
+		.map(|row| SearchResult {
+			doc_id: row.doc_id,
+			name: row.name,
+			owner_username: row.owner_username,
+			matching_revisions: row.matching_revisions,
+			first_appearance: FirstAppearance {
+				revision_id: row.revision_id,
+				revision_time: row.revision_time,
+				snippet: row.snippet,
+			},
+		})
+		.collect())
+}
@@ -0,0 +1,384 @@
+use std::collections::{HashMap, HashSet};
+
+use sqlx::SqlitePool;
+
+// Passages are compared as overlapping runs of SHINGLE_WORDS normalized words.
+// Only runs of at least MIN_PASSAGE_WORDS shared words are reported, so stock phrases don't match.
+pub const SHINGLE_WORDS: usize = 7;
+pub const MIN_PASSAGE_WORDS: usize = 12;
+
+// Number of hash functions in a document's MinHash signature
+const MINHASH_SIZE: usize = 64;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Span {
+	pub start: usize, // char offsets into the revision content
+	pub end: usize,
+}
+
+#[derive(Debug)]
+pub struct Shingle {
+	pub hash: i64,
+	pub span: Span,
+}
+
+struct Word {
+	text: String,
+	span: Span,
+}
+
+// Lowercased alphanumeric words, so punctuation and case changes don't hide copied text
+fn words(content: &str) -> Vec<Word> {
+	let mut words = Vec::new();
+	let mut current = String::new();
+	let mut start = 0;
+
+	for (i, c) in content.chars().enumerate() {
+		if c.is_alphanumeric() {
+			if current.is_empty() {
+				start = i;
+			}
+			current.extend(c.to_lowercase());
+		} else if !current.is_empty() {
+			words.push(Word { text: std::mem::take(&mut current), span: Span { start, end: i } });
+		}
+	}
+	if !current.is_empty() {
+		let end = content.chars().count();
+		words.push(Word { text: current, span: Span { start, end } });
+	}
+
+	words
+}
+
+// FNV-1a, stable across builds so stored hashes stay valid
+fn fnv1a(text: &str) -> u64 {
+	let mut hash: u64 = 0xcbf29ce484222325;
+	for byte in text.bytes() {
+		hash ^= byte as u64;
+		hash = hash.wrapping_mul(0x100000001b3);
+	}
+	hash
+}
+
+pub fn shingles(content: &str) -> Vec<Shingle> {
+	let words = words(content);
+
+	words
+		.windows(SHINGLE_WORDS)
+		.map(|window| {
+			let text = window.iter().map(|w| w.text.as_str()).collect::<Vec<_>>().join(" ");
+			Shingle {
+				hash: fnv1a(&text) as i64,
+				span: Span { start: window[0].span.start, end: window[SHINGLE_WORDS - 1].span.end },
+			}
+		})
+		.collect()
+}
+
+fn splitmix64(mut x: u64) -> u64 {
+	x = x.wrapping_add(0x9e3779b97f4a7c15);
+	x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+	x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+	x ^ (x >> 31)
+}
+
+pub fn minhash_signature(shingles: &[Shingle]) -> Vec<u8> {
+	let mut signature = [u64::MAX; MINHASH_SIZE];
+	for shingle in shingles {
+		for (i, min) in signature.iter_mut().enumerate() {
+			*min = (*min).min(splitmix64(shingle.hash as u64 ^ (i as u64).wrapping_mul(0x2545f4914f6cdd1d)));
+		}
+	}
+	signature.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+// Fraction of equal signature slots, an estimate of the Jaccard similarity of the two shingle sets
+pub fn estimated_similarity(a: &[u8], b: &[u8]) -> f64 {
+	if a.len() != b.len() || a.is_empty() {
+		return 0.0;
+	}
+	let equal = a.chunks(8).zip(b.chunks(8)).filter(|(x, y)| x == y).count();
+	equal as f64 / (a.len() / 8) as f64
+}
+
+// A matched shingle of the new revision and where the other document first had it
+struct Hit {
+	other_document_id: i64,
+	span: Span,
+	hash: i64,
+	other_revision_id: i64,
+	other_first_seen: String,
+}
+
+// Adds a new revision to the index and records passages it shares with other documents.
+// Only shingles that are new to this document are compared, so each passage is recorded once,
+// on the revision that introduced it.
+pub async fn index_revision(
+	pool: &SqlitePool,
+	document_id: i64,
+	revision_id: i64,
+	revision_time: &str,
+	content: &str,
+) -> Result<(), sqlx::Error> {
+	let shingles = shingles(content);
+	let mut tx = pool.begin().await?;
+
+	let known: HashSet<i64> = sqlx::query_scalar!("SELECT hash FROM shingles WHERE document_id = ?", document_id)
+		.fetch_all(&mut *tx)
+		.await?
+		.into_iter()
+		.collect();
+
+	let mut hits = Vec::new();
+	let mut added = HashSet::new();
+	for shingle in &shingles {
+		if known.contains(&shingle.hash) || !added.insert(shingle.hash) {
+			continue;
+		}
+
+		let others = sqlx::query!(
+			"SELECT document_id, revision_id, first_seen FROM shingles WHERE hash = ? AND document_id != ?",
+			shingle.hash,
+			document_id
+		)
+		.fetch_all(&mut *tx)
+		.await?;
+
+		for other in others {
+			hits.push(Hit {
+				other_document_id: other.document_id,
+				span: shingle.span,
+				hash: shingle.hash,
+				other_revision_id: other.revision_id,
+				other_first_seen: other.first_seen,
+			});
+		}
+
+		sqlx::query!(
+			"INSERT OR IGNORE INTO shingles (hash, document_id, revision_id, first_seen) VALUES (?, ?, ?, ?)",
+			shingle.hash,
+			document_id,
+			revision_id,
+			revision_time
+		)
+		.execute(&mut *tx)
+		.await?;
+	}
+
+	let signature = minhash_signature(&shingles);
+	sqlx::query!(
+		"INSERT INTO document_minhash (document_id, revision_id, signature) VALUES (?, ?, ?)
+		ON CONFLICT(document_id) DO UPDATE SET revision_id = excluded.revision_id, signature = excluded.signature",
+		document_id,
+		revision_id,
+		signature
+	)
+	.execute(&mut *tx)
+	.await?;
+
+	tx.commit().await?;
+
+	for passage in merge_hits(hits) {
+		record_passage(pool, document_id, revision_id, revision_time, passage).await?;
+	}
+
+	Ok(())
+}
+
+struct Passage {
+	other_document_id: i64,
+	span: Span,
+	hashes: Vec<i64>,
+	other_revision_id: i64,  // the other document's revision that contained the whole passage
+	other_first_seen: String, // when the other document first had any of it
+}
+
+// Consecutive shingles overlap, so n of them cover n + SHINGLE_WORDS - 1 words
+fn passage_words(passage: &Passage) -> usize {
+	passage.hashes.len() + SHINGLE_WORDS - 1
+}
+
+// Joins overlapping shingle hits against the same document into passages
+fn merge_hits(mut hits: Vec<Hit>) -> Vec<Passage> {
+	hits.sort_by_key(|h| (h.other_document_id, h.span.start));
+
+	let mut passages: Vec<Passage> = Vec::new();
+	for hit in hits {
+		if let Some(last) = passages.last_mut() {
+			if last.other_document_id == hit.other_document_id && hit.span.start < last.span.end {
+				last.span.end = last.span.end.max(hit.span.end);
+				last.hashes.push(hit.hash);
+				last.other_revision_id = last.other_revision_id.max(hit.other_revision_id);
+				if hit.other_first_seen < last.other_first_seen {
+					last.other_first_seen = hit.other_first_seen;
+				}
+				continue;
+			}
+		}
+		passages.push(Passage {
+			other_document_id: hit.other_document_id,
+			span: hit.span,
+			hashes: vec![hit.hash],
+			other_revision_id: hit.other_revision_id,
+			other_first_seen: hit.other_first_seen,
+		});
+	}
+
+	passages.retain(|p| passage_words(p) >= MIN_PASSAGE_WORDS);
+	passages
+}
+
+async fn record_passage(
+	pool: &SqlitePool,
+	document_id: i64,
+	revision_id: i64,
+	revision_time: &str,
+	passage: Passage,
+) -> Result<(), sqlx::Error> {
+	// Locate the passage in the other document's text
+	let other_content = sqlx::query_scalar!("SELECT content FROM document_revisions WHERE id = ?", passage.other_revision_id)
+		.fetch_optional(pool)
+		.await?
+		.unwrap_or_default();
+
+	let wanted: HashSet<i64> = passage.hashes.iter().copied().collect();
+	let positions: HashMap<i64, Span> = shingles(&other_content)
+		.into_iter()
+		.filter(|s| wanted.contains(&s.hash))
+		.map(|s| (s.hash, s.span))
+		.collect();
+	let other_start = positions.values().map(|s| s.start).min().map(|v| v as i64);
+	let other_end = positions.values().map(|s| s.end).max().map(|v| v as i64);
+
+	let source_document_id = if passage.other_first_seen.as_str() <= revision_time {
+		passage.other_document_id
+	} else {
+		document_id
+	};
+
+	let start = passage.span.start as i64;
+	let end = passage.span.end as i64;
+	let words = passage_words(&passage) as i64;
+
+	sqlx::query!(
+		"INSERT INTO similarity_matches (
+			document_id, revision_id, start_offset, end_offset,
+			other_document_id, other_revision_id, other_start_offset, other_end_offset,
+			words, source_document_id
+		) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+		document_id,
+		revision_id,
+		start,
+		end,
+		passage.other_document_id,
+		passage.other_revision_id,
+		other_start,
+		other_end,
+		words,
+		source_document_id
+	)
+	.execute(pool)
+	.await?;
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn char_slice(text: &str, span: Span) -> String {
+		text.chars().skip(span.start).take(span.end - span.start).collect()
+	}
+
+	fn hits(content: &str, other_document_id: i64, range: std::ops::Range<usize>) -> Vec<Hit> {
+		shingles(content)[range]
+			.iter()
+			.map(|s| Hit {
+				other_document_id,
+				span: s.span,
+				hash: s.hash,
+				other_revision_id: 1,
+				other_first_seen: "2026-01-01T00:00:00Z".to_string(),
+			})
+			.collect()
+	}
+
+	const TEXT: &str = "one two three four five six seven eight nine ten eleven twelve thirteen fourteen fifteen sixteen";
+
+	#[test]
+	fn words_are_lowercased_without_punctuation() {
+		let texts: Vec<String> = words("Hello, WORLD! It's 2026.").into_iter().map(|w| w.text).collect();
+		assert_eq!(texts, ["hello", "world", "it", "s", "2026"]);
+	}
+
+	#[test]
+	fn word_spans_are_char_offsets() {
+		let text = "Café, naïve résumé — 東京";
+		let words = words(text);
+		let spans: Vec<String> = words.iter().map(|w| char_slice(text, w.span)).collect();
+		assert_eq!(spans, ["Café", "naïve", "résumé", "東京"]);
+		assert_eq!(words[3].span.end, text.chars().count());
+	}
+
+	#[test]
+	fn no_words_in_empty_or_punctuation_only_text() {
+		assert!(words("").is_empty());
+		assert!(words(" ... -- !? ").is_empty());
+		assert!(shingles("").is_empty());
+	}
+
+	#[test]
+	fn shingles_need_a_full_window() {
+		assert!(shingles("one two three four five six").is_empty());
+
+		let shingles = shingles("  Ünïcode one two three four five six, ");
+		assert_eq!(shingles.len(), 1);
+		assert_eq!(char_slice("  Ünïcode one two three four five six, ", shingles[0].span), "Ünïcode one two three four five six");
+	}
+
+	#[test]
+	fn shingles_ignore_case_and_punctuation() {
+		let a = shingles("The quick brown fox jumps over dogs");
+		let b = shingles("the QUICK, brown fox; jumps over (dogs)!");
+		assert_eq!(a[0].hash, b[0].hash);
+	}
+
+	#[test]
+	fn overlapping_hits_merge_into_one_passage() {
+		let passages = merge_hits(hits(TEXT, 2, 0..8));
+		assert_eq!(passages.len(), 1);
+		assert_eq!(passage_words(&passages[0]), 14);
+		assert_eq!(char_slice(TEXT, passages[0].span), "one two three four five six seven eight nine ten eleven twelve thirteen fourteen");
+	}
+
+	#[test]
+	fn short_and_separate_runs_are_dropped_or_kept_apart() {
+		// Three shingles cover nine words, under MIN_PASSAGE_WORDS
+		assert!(merge_hits(hits(TEXT, 2, 0..3)).is_empty());
+
+		// The same run against two documents is two passages
+		let mut both = hits(TEXT, 2, 0..8);
+		both.extend(hits(TEXT, 3, 0..8));
+		let passages = merge_hits(both);
+		assert_eq!(passages.iter().map(|p| p.other_document_id).collect::<Vec<_>>(), [2, 3]);
+	}
+
+	#[test]
+	fn merged_passage_keeps_earliest_first_seen_and_latest_revision() {
+		let mut hits = hits(TEXT, 2, 0..8);
+		hits[3].other_first_seen = "2025-06-01T00:00:00Z".to_string();
+		hits[5].other_revision_id = 9;
+		let passages = merge_hits(hits);
+		assert_eq!(passages[0].other_first_seen, "2025-06-01T00:00:00Z");
+		assert_eq!(passages[0].other_revision_id, 9);
+	}
+
+	#[test]
+	fn identical_signatures_are_fully_similar() {
+		let a = minhash_signature(&shingles(TEXT));
+		assert_eq!(estimated_similarity(&a, &a), 1.0);
+		assert_eq!(estimated_similarity(&a, &[]), 0.0);
+	}
+}
@@ -1,8 +1,17 @@
+use std::sync::Arc;
+
 use sqlx::SqlitePool;
+use tokio::sync::Mutex;
+
+use crate::token_manager::TokenManager;
 
 #[derive(Clone)]
 pub struct AppState {
 	pub db: SqlitePool,
+	pub tokens: TokenManager,
+	// Held while a document is checked and its revision stored, so the poll and deadline loops
+	// can't both see the same stale version and insert it twice
+	pub poll_lock: Arc<Mutex<()>>,
 }
 
 impl AppState {
@@ -21,6 +30,6 @@ impl AppState {
 			.await
 			.expect("Failed to run database migrations");
 
-		Self { db }
+		Self { db, tokens: TokenManager::new(), poll_lock: Arc::new(Mutex::new(())) }
 	}
 }
\ No newline at end of file
@@ -0,0 +1,333 @@
+use std::collections::HashSet;
+
+use serde::Serialize;
+use sqlx::SqlitePool;
+
+use crate::corpus::added_runs;
+use crate::poller::{diff_words, OwnedWordChange};
+use crate::templates::{template_hashes, template_mask};
+
+// Style is only measured on revisions that added at least this many words; shorter additions are too noisy
+pub const MIN_STYLE_WORDS: usize = 50;
+// Window for the moving-average type-token ratio, which unlike plain TTR doesn't fall as texts get longer
+const TTR_WINDOW: usize = 50;
+// Earlier samples of the student's writing needed before a revision is compared against them
+const MIN_BASELINE_SAMPLES: usize = 3;
+// A feature this many standard deviations from the student's own mean counts as a strong deviation
+const DRIFT_Z: f64 = 2.5;
+// Cosine distance between function-word profiles that counts as a strong deviation
+const DRIFT_FUNCTION_WORDS: f64 = 0.3;
+
+// Common English function words; how often a writer uses them is hard to change deliberately
+pub const FUNCTION_WORDS: [&str; 40] = [
+	"the", "of", "and", "a", "to", "in", "is", "that", "it", "was",
+	"for", "on", "as", "with", "be", "by", "this", "are", "or", "but",
+	"not", "from", "at", "which", "have", "an", "they", "their", "has", "were",
+	"can", "would", "there", "been", "also", "however", "because", "so", "if", "than",
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StyleFeatures {
+	pub words: i64,
+	pub avg_sentence_length: f64,
+	pub vocabulary_richness: f64,
+	pub readability_grade: f64, // Flesch-Kincaid grade level
+	pub function_words: Vec<f64>,
+}
+
+fn normalized_words(text: &str) -> Vec<String> {
+	text.split(|c: char| !c.is_alphanumeric() && c != '\'')
+		.filter(|w| w.chars().any(char::is_alphabetic))
+		.map(|w| w.trim_matches('\'').to_lowercase())
+		.collect()
+}
+
+fn sentence_count(text: &str) -> usize {
+	let chars: Vec<char> = text.chars().collect();
+	let ends = chars
+		.iter()
+		.enumerate()
+		.filter(|(i, c)| matches!(c, '.' | '!' | '?') && chars.get(i + 1).is_none_or(|n| n.is_whitespace()))
+		.count();
+	// Text that doesn't end with punctuation still ends a sentence
+	let trailing = text.trim_end().chars().last().is_some_and(|c| !matches!(c, '.' | '!' | '?'));
+	(ends + trailing as usize).max(1)
+}
+
+// Vowel groups, minus a silent final e; every word has at least one
+fn syllables(word: &str) -> usize {
+	let mut count = 0;
+	let mut previous_vowel = false;
+	for c in word.chars() {
+		let vowel = matches!(c, 'a' | 'e' | 'i' | 'o' | 'u' | 'y');
+		if vowel && !previous_vowel {
+			count += 1;
+		}
+		previous_vowel = vowel;
+	}
+	if word.ends_with('e') && !word.ends_with("le") && count > 1 {
+		count -= 1;
+	}
+	count.max(1)
+}
+
+fn moving_ttr(words: &[String]) -> f64 {
+	let ttr = |window: &[String]| {
+		let distinct: HashSet<&String> = window.iter().collect();
+		distinct.len() as f64 / window.len() as f64
+	};
+
+	if words.len() <= TTR_WINDOW {
+		return ttr(words);
+	}
+	let windows: Vec<f64> = words.windows(TTR_WINDOW).map(ttr).collect();
+	windows.iter().sum::<f64>() / windows.len() as f64
+}
+
+// None when the text is too short to say anything about style
+pub fn style_features(text: &str) -> Option<StyleFeatures> {
+	let words = normalized_words(text);
+	if words.len() < MIN_STYLE_WORDS {
+		return None;
+	}
+
+	let n = words.len() as f64;
+	let sentences = sentence_count(text) as f64;
+	let syllables: usize = words.iter().map(|w| syllables(w)).sum();
+
+	let function_words = FUNCTION_WORDS
+		.iter()
+		.map(|f| words.iter().filter(|w| w == f).count() as f64 / n)
+		.collect();
+
+	Some(StyleFeatures {
+		words: words.len() as i64,
+		avg_sentence_length: n / sentences,
+		vocabulary_richness: moving_ttr(&words),
+		readability_grade: 0.39 * (n / sentences) + 11.8 * (syllables as f64 / n) - 15.59,
+		function_words,
+	})
+}
+
+// The text a revision added, without template passages. A baseline revision added all of its text.
+pub async fn added_student_text(
+	pool: &SqlitePool,
+	document_id: i64,
+	content: &str,
+	diff: &[OwnedWordChange],
+) -> Result<String, sqlx::Error> {
+	let baseline: Vec<OwnedWordChange>;
+	let diff = if diff.is_empty() {
+		baseline = diff_words("", content).into_iter().map(Into::into).collect();
+		&baseline
+	} else {
+		diff
+	};
+
+	// Template text becomes unchanged text, which splits the added runs around it
+	let hashes = template_hashes(pool, document_id).await?;
+	let student_diff: Vec<OwnedWordChange> = diff
+		.iter()
+		.zip(template_mask(diff, &hashes))
+		.map(|(change, is_template)| match change {
+			OwnedWordChange::Added(text) if is_template => OwnedWordChange::Unchanged(text.clone()),
+			other => other.clone(),
+		})
+		.collect();
+
+	Ok(added_runs(&student_diff).into_iter().map(|run| run.text).collect::<Vec<_>>().join("\n"))
+}
+
+pub async fn record_style(
+	pool: &SqlitePool,
+	document_id: i64,
+	revision_id: i64,
+	revision_time: &str,
+	features: &StyleFeatures,
+) -> Result<(), sqlx::Error> {
+	let function_words = serde_json::to_string(&features.function_words).unwrap_or_else(|_| "[]".to_string());
+
+	sqlx::query!(
+		"INSERT OR REPLACE INTO revision_style (
+			revision_id, document_id, revision_time, words,
+			avg_sentence_length, vocabulary_richness, readability_grade, function_words
+		) VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+		revision_id,
+		document_id,
+		revision_time,
+		features.words,
+		features.avg_sentence_length,
+		features.vocabulary_richness,
+		features.readability_grade,
+		function_words
+	)
+	.execute(pool)
+	.await?;
+
+	Ok(())
+}
+
+#[derive(Debug, Serialize)]
+pub struct StyleSample {
+	pub revision_id: i64,
+	pub revision_time: String,
+	pub features: StyleFeatures,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FeatureBaseline {
+	pub mean: f64,
+	pub std_dev: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StyleBaseline {
+	pub samples: usize,
+	pub documents: i64,
+	pub avg_sentence_length: FeatureBaseline,
+	pub vocabulary_richness: FeatureBaseline,
+	pub readability_grade: FeatureBaseline,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Deviation {
+	pub feature: &'static str,
+	pub value: f64,
+	pub baseline: Option<f64>, // the student's mean; none for the function-word profile
+	pub score: f64,            // z-score, or cosine distance for function words
+}
+
+#[derive(Debug, Serialize)]
+pub struct RevisionDrift {
+	pub revision_id: i64,
+	pub revision_time: String,
+	pub features: StyleFeatures,
+	pub baseline: Option<StyleBaseline>, // None until the student has enough earlier writing elsewhere
+	pub deviations: Vec<Deviation>,      // only the strong ones
+	pub flagged: bool,
+}
+
+// Mean weighted by words, so long samples count for more; the spread is floored so a very
+// consistent writer isn't flagged over tiny differences
+fn feature_baseline(samples: &[&StyleSample], value: fn(&StyleFeatures) -> f64) -> FeatureBaseline {
+	let total: f64 = samples.iter().map(|s| s.features.words as f64).sum();
+	let mean = samples.iter().map(|s| value(&s.features) * s.features.words as f64).sum::<f64>() / total;
+	let variance = samples.iter().map(|s| (value(&s.features) - mean).powi(2)).sum::<f64>() / samples.len() as f64;
+	FeatureBaseline { mean, std_dev: variance.sqrt().max(mean.abs() * 0.1).max(f64::EPSILON) }
+}
+
+fn cosine_distance(a: &[f64], b: &[f64]) -> f64 {
+	let dot: f64 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+	let norm = |v: &[f64]| v.iter().map(|x| x * x).sum::<f64>().sqrt();
+	let (na, nb) = (norm(a), norm(b));
+	if na == 0.0 || nb == 0.0 {
+		return 0.0;
+	}
+	1.0 - dot / (na * nb)
+}
+
+type Feature = (&'static str, fn(&StyleFeatures) -> f64);
+
+const SCALAR_FEATURES: [Feature; 3] = [
+	("avg_sentence_length", |f| f.avg_sentence_length),
+	("vocabulary_richness", |f| f.vocabulary_richness),
+	("readability_grade", |f| f.readability_grade),
+];
+
+fn compare(sample: &StyleSample, earlier: &[&StyleSample], documents: i64) -> RevisionDrift {
+	let mut drift = RevisionDrift {
+		revision_id: sample.revision_id,
+		revision_time: sample.revision_time.clone(),
+		features: sample.features.clone(),
+		baseline: None,
+		deviations: Vec::new(),
+		flagged: false,
+	};
+	if earlier.len() < MIN_BASELINE_SAMPLES {
+		return drift;
+	}
+
+	let [sentence, richness, grade] = SCALAR_FEATURES.map(|(_, value)| feature_baseline(earlier, value));
+	for ((feature, value), baseline) in SCALAR_FEATURES.iter().zip([&sentence, &richness, &grade]) {
+		let z = (value(&sample.features) - baseline.mean) / baseline.std_dev;
+		if z.abs() >= DRIFT_Z {
+			drift.deviations.push(Deviation { feature, value: value(&sample.features), baseline: Some(baseline.mean), score: z });
+		}
+	}
+
+	// Word-weighted average profile of the earlier writing
+	let total: f64 = earlier.iter().map(|s| s.features.words as f64).sum();
+	let profile: Vec<f64> = (0..FUNCTION_WORDS.len())
+		.map(|i| earlier.iter().map(|s| s.features.function_words.get(i).unwrap_or(&0.0) * s.features.words as f64).sum::<f64>() / total)
+		.collect();
+	let distance = cosine_distance(&sample.features.function_words, &profile);
+	if distance >= DRIFT_FUNCTION_WORDS {
+		drift.deviations.push(Deviation { feature: "function_words", value: distance, baseline: None, score: distance });
+	}
+
+	drift.flagged = !drift.deviations.is_empty();
+	drift.baseline = Some(StyleBaseline {
+		samples: earlier.len(),
+		documents,
+		avg_sentence_length: sentence,
+		vocabulary_richness: richness,
+		readability_grade: grade,
+	});
+	drift
+}
+
+// Compares each measured revision of a document with the same student's writing in their other
+// documents from before that revision. Students are recognized by the Drive owner's email, as display
+// names change and collide; until the email is known, a document has no baseline.
+pub async fn document_drift(pool: &SqlitePool, document_id: i64) -> Result<Vec<RevisionDrift>, sqlx::Error> {
+	let rows = sqlx::query!(
+		r#"
+		SELECT s.revision_id AS "revision_id!", s.document_id, s.revision_time, s.words,
+			s.avg_sentence_length, s.vocabulary_richness, s.readability_grade, s.function_words
+		FROM revision_style s
+		JOIN documents d ON d.id = s.document_id
+		WHERE s.document_id = ?
+			OR LOWER(d.owner_email) = (SELECT LOWER(owner_email) FROM documents WHERE id = ?)
+		ORDER BY s.revision_time
+		"#,
+		document_id,
+		document_id
+	)
+	.fetch_all(pool)
+	.await?;
+
+	let samples: Vec<(i64, StyleSample)> = rows
+		.into_iter()
+		.map(|r| {
+			(
+				r.document_id,
+				StyleSample {
+					revision_id: r.revision_id,
+					revision_time: r.revision_time,
+					features: StyleFeatures {
+						words: r.words,
+						avg_sentence_length: r.avg_sentence_length,
+						vocabulary_richness: r.vocabulary_richness,
+						readability_grade: r.readability_grade,
+						function_words: serde_json::from_str(&r.function_words).unwrap_or_default(),
+					},
+				},
+			)
+		})
+		.collect();
+
+	Ok(samples
+		.iter()
+		.filter(|(doc, _)| *doc == document_id)
+		.map(|(_, sample)| {
+			let earlier: Vec<(i64, &StyleSample)> = samples
+				.iter()
+				.filter(|(doc, s)| *doc != document_id && s.revision_time < sample.revision_time)
+				.map(|(doc, s)| (*doc, s))
+				.collect();
+			let documents = earlier.iter().map(|(doc, _)| *doc).collect::<HashSet<_>>().len() as i64;
+			let earlier: Vec<&StyleSample> = earlier.into_iter().map(|(_, s)| s).collect();
+			compare(sample, &earlier, documents)
+		})
+		.collect())
+}
@@ -0,0 +1,210 @@
+use std::collections::{HashMap, HashSet};
+
+use sqlx::SqlitePool;
+
+use crate::poller::OwnedWordChange;
+use crate::similarity::shingles;
+
+// Text that appears in a template a document is subject to is the instructor's, not the student's.
+// It is recognized by shingles, so it is still found after students reformat or move it around.
+//
+// Assignment templates apply to everyone who sees a linked document, so they are counted into the
+// shared document_revisions.template_words. Folder templates belong to the user who filed the document,
+// so they are only applied when that user looks at it, on top of the stored count.
+
+fn hashes_of(contents: &[String]) -> HashSet<i64> {
+	contents.iter().flat_map(|content| shingles(content)).map(|s| s.hash).collect()
+}
+
+// Shingle hashes of the templates of every assignment a document is linked to
+pub async fn template_hashes(pool: &SqlitePool, document_id: i64) -> Result<HashSet<i64>, sqlx::Error> {
+	let contents = sqlx::query_scalar!(
+		r#"
+		SELECT t.content
+		FROM templates t
+		WHERE t.assignment_id IN (SELECT assignment_id FROM assignment_documents WHERE document_id = ?)
+		"#,
+		document_id
+	)
+	.fetch_all(pool)
+	.await?;
+
+	Ok(hashes_of(&contents))
+}
+
+// Shingle hashes of every registered template, whoever registered it
+pub async fn all_template_hashes(pool: &SqlitePool) -> Result<HashSet<i64>, sqlx::Error> {
+	let contents = sqlx::query_scalar!("SELECT content FROM templates").fetch_all(pool).await?;
+	Ok(hashes_of(&contents))
+}
+
+// Shingle hashes of the templates of the user's folders a document is filed under (at any depth)
+async fn folder_template_hashes(pool: &SqlitePool, user_id: i64, document_id: i64) -> Result<HashSet<i64>, sqlx::Error> {
+	let contents = sqlx::query_scalar!(
+		r#"
+		WITH RECURSIVE ancestors(id) AS (
+			SELECT id_parent FROM user_documents WHERE document_id = ? AND user_id = ? AND id_parent IS NOT NULL
+			UNION
+			SELECT ud.id_parent FROM user_documents ud JOIN ancestors a ON ud.id = a.id WHERE ud.id_parent IS NOT NULL
+		)
+		SELECT t.content
+		FROM templates t
+		WHERE t.folder_id IN (SELECT id FROM ancestors) AND t.user_id = ?
+		"#,
+		document_id,
+		user_id,
+		user_id
+	)
+	.fetch_all(pool)
+	.await?;
+
+	Ok(hashes_of(&contents))
+}
+
+// Every template that applies to a document as one user sees it
+pub async fn user_template_hashes(pool: &SqlitePool, user_id: i64, document_id: i64) -> Result<HashSet<i64>, sqlx::Error> {
+	let mut hashes = template_hashes(pool, document_id).await?;
+	hashes.extend(folder_template_hashes(pool, user_id, document_id).await?);
+	Ok(hashes)
+}
+
+// Which characters of `text` belong to a template passage
+fn covered_chars(text: &str, hashes: &HashSet<i64>) -> Vec<bool> {
+	let mut covered = vec![false; text.chars().count()];
+	if hashes.is_empty() {
+		return covered;
+	}
+
+	for shingle in shingles(text).into_iter().filter(|s| hashes.contains(&s.hash)) {
+		covered[shingle.span.start..shingle.span.end].iter_mut().for_each(|c| *c = true);
+	}
+	covered
+}
+
+// For each diff entry, whether it is template text.
+// Removed entries are never template text, since they are not part of the new version.
+pub fn template_mask(diff: &[OwnedWordChange], hashes: &HashSet<i64>) -> Vec<bool> {
+	// Rebuild the new version of the text, remembering where each entry starts
+	let mut text = String::new();
+	let mut ranges = Vec::with_capacity(diff.len());
+	let mut offset = 0;
+	for change in diff {
+		match change {
+			OwnedWordChange::Added(t) | OwnedWordChange::Unchanged(t) => {
+				let len = t.chars().count();
+				ranges.push(Some(offset..offset + len));
+				text.push_str(t);
+				offset += len;
+			}
+			OwnedWordChange::Removed(_) => ranges.push(None),
+		}
+	}
+
+	let covered = covered_chars(&text, hashes);
+
+	diff.iter()
+		.zip(ranges)
+		.map(|(change, range)| match (change, range) {
+			(OwnedWordChange::Added(t) | OwnedWordChange::Unchanged(t), Some(r)) => is_covered(t, &covered[r]),
+			_ => false,
+		})
+		.collect()
+}
+
+// Shingles only span words, so punctuation attached to a template word counts as template too
+fn is_covered(token: &str, covered: &[bool]) -> bool {
+	if token.is_empty() {
+		return false;
+	}
+	let has_word = token.chars().any(char::is_alphanumeric);
+	token
+		.chars()
+		.zip(covered)
+		.all(|(c, &covered)| covered || (has_word && !c.is_alphanumeric()))
+}
+
+// Added words of a diff that are template text
+pub fn template_words_in_diff(diff: &[OwnedWordChange], mask: &[bool]) -> i64 {
+	diff.iter()
+		.zip(mask)
+		.filter(|(change, &is_template)| {
+			is_template && matches!(change, OwnedWordChange::Added(w) if !w.trim().is_empty())
+		})
+		.count() as i64
+}
+
+// Words of a full text that are template text
+pub fn template_words_in_text(content: &str, hashes: &HashSet<i64>) -> i64 {
+	let covered = covered_chars(content, hashes);
+	let mut count = 0;
+	let mut offset = 0;
+	for word in content.split_inclusive(char::is_whitespace) {
+		let len = word.chars().count();
+		let trimmed = word.trim();
+		if !trimmed.is_empty() && is_covered(trimmed, &covered[offset..offset + len]) {
+			count += 1;
+		}
+		offset += len;
+	}
+	count
+}
+
+fn revision_template_words(diff: Option<&str>, hashes: &HashSet<i64>) -> i64 {
+	let diff: Vec<OwnedWordChange> = diff.and_then(|d| serde_json::from_str(d).ok()).unwrap_or_default();
+	template_words_in_diff(&diff, &template_mask(&diff, hashes))
+}
+
+// Recomputes the stored template_words of every revision of a document, after assignment templates or links change
+pub async fn refresh_template_words(pool: &SqlitePool, document_id: i64) -> Result<(), sqlx::Error> {
+	let hashes = template_hashes(pool, document_id).await?;
+
+	let revisions = sqlx::query!(
+		r#"SELECT id AS "id!", diff FROM document_revisions WHERE document_id = ?"#,
+		document_id
+	)
+	.fetch_all(pool)
+	.await?;
+
+	for revision in revisions {
+		let template_words = revision_template_words(revision.diff.as_deref(), &hashes);
+
+		sqlx::query!(
+			"UPDATE document_revisions SET template_words = ? WHERE id = ?",
+			template_words,
+			revision.id
+		)
+		.execute(pool)
+		.await?;
+	}
+
+	Ok(())
+}
+
+// Template words per revision as one user sees them, when that user's folder templates apply to the document.
+// None means the stored template_words are already right for this user.
+pub async fn user_template_words(
+	pool: &SqlitePool,
+	user_id: i64,
+	document_id: i64,
+) -> Result<Option<HashMap<i64, i64>>, sqlx::Error> {
+	let folder_hashes = folder_template_hashes(pool, user_id, document_id).await?;
+	if folder_hashes.is_empty() {
+		return Ok(None);
+	}
+	let mut hashes = template_hashes(pool, document_id).await?;
+	hashes.extend(folder_hashes);
+
+	let revisions = sqlx::query!(
+		r#"SELECT id AS "id!", diff FROM document_revisions WHERE document_id = ?"#,
+		document_id
+	)
+	.fetch_all(pool)
+	.await?;
+
+	Ok(Some(
+		revisions
+			.into_iter()
+			.map(|r| (r.id, revision_template_words(r.diff.as_deref(), &hashes)))
+			.collect(),
+	))
+}
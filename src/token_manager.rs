@@ -0,0 +1,413 @@
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use chrono::Utc;
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::Mutex;
+
+// Refresh this long before Google's stated expiry so in-flight requests never carry a stale token
+const REFRESH_MARGIN: Duration = Duration::from_secs(5 * 60);
+const MAX_ATTEMPTS: u32 = 3;
+
+pub const DRIVE_SCOPE: &str = "https://www.googleapis.com/auth/drive";
+pub const DRIVE_READONLY_SCOPE: &str = "https://www.googleapis.com/auth/drive.readonly";
+const TOKEN_URI: &str = "https://oauth2.googleapis.com/token";
+// Where docwatch-authctl writes the refresh token and the server reads it, unless GOOGLE_TOKEN_PATH is set
+pub const DEFAULT_TOKEN_PATH: &str = "/opt/docwatch/google_token.json";
+
+// How much of Drive Docwatch may touch, set with GOOGLE_DRIVE_ACCESS in .env.
+// Read-only is the default; "full" is only needed for the write paths in google_api.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DriveAccess {
+	ReadOnly,
+	Full,
+}
+
+impl DriveAccess {
+	pub fn from_env() -> Self {
+		match std::env::var("GOOGLE_DRIVE_ACCESS").as_deref() {
+			Ok("full") => DriveAccess::Full,
+			_ => DriveAccess::ReadOnly,
+		}
+	}
+
+	pub fn scope(self) -> &'static str {
+		match self {
+			DriveAccess::ReadOnly => DRIVE_READONLY_SCOPE,
+			DriveAccess::Full => DRIVE_SCOPE,
+		}
+	}
+
+	// Full Drive access implies read-only access
+	fn is_granted_by(self, scopes: &[String]) -> bool {
+		scopes.iter().any(|s| s == DRIVE_SCOPE || (self == DriveAccess::ReadOnly && s == DRIVE_READONLY_SCOPE))
+	}
+}
+
+// Where access tokens come from
+#[derive(Debug, Clone)]
+pub enum Credentials {
+	// Refresh token written by docwatch-authctl (installed-app OAuth)
+	OAuthRefreshToken { token_path: String },
+	// Service-account key; `subject` impersonates a Workspace user via domain-wide delegation
+	ServiceAccount { key_path: String, subject: Option<String> },
+}
+
+impl Credentials {
+	fn path(&self) -> &str {
+		match self {
+			Credentials::OAuthRefreshToken { token_path } => token_path,
+			Credentials::ServiceAccount { key_path, .. } => key_path,
+		}
+	}
+
+	// Changes when docwatch-authctl writes a new grant or the key is replaced
+	fn modified(&self) -> Option<SystemTime> {
+		std::fs::metadata(self.path()).and_then(|m| m.modified()).ok()
+	}
+
+	// GOOGLE_SERVICE_ACCOUNT_KEY takes precedence over the OAuth refresh token file
+	pub fn from_env() -> Self {
+		match std::env::var("GOOGLE_SERVICE_ACCOUNT_KEY") {
+			Ok(key_path) => Credentials::ServiceAccount {
+				key_path,
+				subject: std::env::var("GOOGLE_DELEGATED_USER").ok(),
+			},
+			Err(_) => Credentials::OAuthRefreshToken {
+				token_path: std::env::var("GOOGLE_TOKEN_PATH").unwrap_or_else(|_| DEFAULT_TOKEN_PATH.to_string()),
+			},
+		}
+	}
+}
+
+type FormParams = Vec<(&'static str, String)>;
+
+#[derive(Deserialize)]
+struct ServiceAccountKey {
+	client_email: String,
+	private_key: String,
+	token_uri: Option<String>,
+}
+
+#[derive(Serialize)]
+struct JwtClaims<'a> {
+	iss: &'a str,
+	scope: &'a str,
+	aud: &'a str,
+	iat: i64,
+	exp: i64,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	sub: Option<&'a str>,
+}
+
+#[derive(Debug, Clone)]
+pub enum TokenError {
+	// Credentials are missing or were revoked; someone has to run docwatch-authctl again
+	ReauthorizationRequired(String),
+	// Network or server trouble that survived all retries
+	Unavailable(String),
+}
+
+impl fmt::Display for TokenError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			TokenError::ReauthorizationRequired(msg) => write!(f, "Reauthorization required: {}", msg),
+			TokenError::Unavailable(msg) => write!(f, "Token refresh failed: {}", msg),
+		}
+	}
+}
+
+impl std::error::Error for TokenError {}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthState {
+	Unknown,
+	Ok,
+	ReauthorizationRequired,
+	Error,
+}
+
+// Snapshot reported by the admin API
+#[derive(Debug, Clone, Serialize)]
+pub struct AuthStatus {
+	pub state: AuthState,
+	pub drive_access: DriveAccess,
+	pub granted_scopes: Option<Vec<String>>,
+	pub message: Option<String>,
+	pub last_refresh: Option<String>, // RFC 3339
+	pub expires_at: Option<String>,
+}
+
+struct CachedToken {
+	access_token: String,
+	refresh_at: Instant,
+	// Modification time of the credentials file it was issued for
+	credentials_modified: Option<SystemTime>,
+}
+
+struct Inner {
+	cached: Option<CachedToken>,
+	status: AuthStatus,
+	// Set by validate_scopes; a refreshed token carries the same grant, so refreshing doesn't clear it
+	scope_error: Option<String>,
+}
+
+// Hands out Google access tokens, refreshing them only when they are about to expire
+#[derive(Clone)]
+pub struct TokenManager {
+	inner: Arc<Mutex<Inner>>,
+	http: reqwest::Client,
+	credentials: Credentials,
+	access: DriveAccess,
+}
+
+impl Default for TokenManager {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl TokenManager {
+	pub fn new() -> Self {
+		Self::with_credentials(Credentials::from_env())
+	}
+
+	pub fn with_credentials(credentials: Credentials) -> Self {
+		let access = DriveAccess::from_env();
+		Self {
+			inner: Arc::new(Mutex::new(Inner {
+				cached: None,
+				status: AuthStatus {
+					state: AuthState::Unknown,
+					drive_access: access,
+					granted_scopes: None,
+					message: None,
+					last_refresh: None,
+					expires_at: None,
+				},
+				scope_error: None,
+			})),
+			http: reqwest::Client::new(),
+			credentials,
+			access,
+		}
+	}
+
+	pub async fn access_token(&self) -> Result<String, TokenError> {
+		let mut inner = self.inner.lock().await;
+		let credentials_modified = self.credentials.modified();
+
+		// A token issued for credentials that have since been replaced may lack a newly granted scope
+		if let Some(cached) = &inner.cached {
+			if Instant::now() < cached.refresh_at && cached.credentials_modified == credentials_modified {
+				return Ok(cached.access_token.clone());
+			}
+		}
+
+		match self.refresh_with_retry().await {
+			Ok((access_token, expires_in)) => {
+				let now = Utc::now();
+				let lifetime = Duration::from_secs(expires_in);
+				inner.cached = Some(CachedToken {
+					access_token: access_token.clone(),
+					refresh_at: Instant::now() + lifetime.saturating_sub(REFRESH_MARGIN),
+					credentials_modified,
+				});
+				match inner.scope_error.clone() {
+					Some(msg) => {
+						inner.status.state = AuthState::ReauthorizationRequired;
+						inner.status.message = Some(msg);
+					}
+					None => {
+						inner.status.state = AuthState::Ok;
+						inner.status.message = None;
+					}
+				}
+				inner.status.last_refresh = Some(now.to_rfc3339());
+				inner.status.expires_at = Some((now + chrono::Duration::seconds(expires_in as i64)).to_rfc3339());
+				Ok(access_token)
+			}
+			Err(e) => {
+				inner.cached = None;
+				inner.status.state = match e {
+					TokenError::ReauthorizationRequired(_) => AuthState::ReauthorizationRequired,
+					TokenError::Unavailable(_) => AuthState::Error,
+				};
+				inner.status.message = Some(e.to_string());
+				inner.status.expires_at = None;
+				Err(e)
+			}
+		}
+	}
+
+	// Drops the cached access token, so the next request gets a new one from the stored credentials
+	pub async fn invalidate(&self) {
+		self.inner.lock().await.cached = None;
+	}
+
+	pub async fn status(&self) -> AuthStatus {
+		// A scope failure isn't cleared by refreshing, so check again in case docwatch-authctl was run since.
+		// The cached token still carries the old grant, so a new one is fetched for the check.
+		if self.inner.lock().await.scope_error.is_some() {
+			self.invalidate().await;
+			let _ = self.validate_scopes().await;
+		}
+		self.inner.lock().await.status.clone()
+	}
+
+	pub fn drive_access(&self) -> DriveAccess {
+		self.access
+	}
+
+	// Asks Google which scopes the current token carries and checks them against the configured access.
+	// A token missing the scope is reported as needing reauthorization rather than failing later per file.
+	pub async fn validate_scopes(&self) -> Result<Vec<String>, TokenError> {
+		let access_token = self.access_token().await?;
+
+		let info = self.http
+			.get("https://oauth2.googleapis.com/tokeninfo")
+			.query(&[("access_token", access_token.as_str())])
+			.send()
+			.await
+			.map_err(|e| TokenError::Unavailable(e.to_string()))?
+			.json::<Value>()
+			.await
+			.map_err(|e| TokenError::Unavailable(format!("Invalid tokeninfo response: {}", e)))?;
+
+		let scopes: Vec<String> = info["scope"]
+			.as_str()
+			.unwrap_or("")
+			.split_whitespace()
+			.map(|s| s.to_string())
+			.collect();
+
+		let mut inner = self.inner.lock().await;
+		inner.status.granted_scopes = Some(scopes.clone());
+
+		if !self.access.is_granted_by(&scopes) {
+			let e = TokenError::ReauthorizationRequired(format!(
+				"Token does not grant {}, run docwatch-authctl again",
+				self.access.scope()
+			));
+			inner.scope_error = Some(e.to_string());
+			inner.status.state = AuthState::ReauthorizationRequired;
+			inner.status.message = Some(e.to_string());
+			return Err(e);
+		}
+
+		// A new grant with the right scope clears an earlier failure
+		if inner.scope_error.take().is_some() {
+			inner.status.state = AuthState::Ok;
+			inner.status.message = None;
+		}
+
+		Ok(scopes)
+	}
+
+	async fn refresh_with_retry(&self) -> Result<(String, u64), TokenError> {
+		let mut attempt = 0;
+		loop {
+			attempt += 1;
+			match self.refresh().await {
+				Ok(token) => return Ok(token),
+				Err(TokenError::Unavailable(msg)) if attempt < MAX_ATTEMPTS => {
+					eprintln!("⚠️ Token refresh attempt {} failed: {}", attempt, msg);
+					tokio::time::sleep(Duration::from_secs(2u64.pow(attempt))).await;
+				}
+				Err(e) => return Err(e),
+			}
+		}
+	}
+
+	async fn refresh(&self) -> Result<(String, u64), TokenError> {
+		let (token_uri, params) = match &self.credentials {
+			Credentials::OAuthRefreshToken { token_path } => (TOKEN_URI.to_string(), refresh_token_params(token_path)?),
+			Credentials::ServiceAccount { key_path, subject } => service_account_params(key_path, subject.as_deref(), self.access)?,
+		};
+
+		let res = self.http
+			.post(&token_uri)
+			.form(&params)
+			.send()
+			.await
+			.map_err(|e| TokenError::Unavailable(e.to_string()))?;
+
+		let status = res.status();
+		let body = res
+			.json::<Value>()
+			.await
+			.map_err(|e| TokenError::Unavailable(format!("Invalid token response: {}", e)))?;
+
+		// invalid_grant means the refresh token was revoked or the assertion was rejected; retrying won't help
+		let error = body["error"].as_str();
+		if matches!(error, Some("invalid_grant") | Some("unauthorized_client")) || status == reqwest::StatusCode::UNAUTHORIZED {
+			let reason = body["error_description"].as_str().unwrap_or("credentials rejected");
+			return Err(TokenError::ReauthorizationRequired(reason.to_string()));
+		}
+
+		if !status.is_success() {
+			return Err(TokenError::Unavailable(format!("{}: {}", status, body)));
+		}
+
+		let access_token = body["access_token"]
+			.as_str()
+			.ok_or_else(|| TokenError::Unavailable("Token response has no access_token".into()))?
+			.to_string();
+		let expires_in = body["expires_in"].as_u64().unwrap_or(3600);
+
+		Ok((access_token, expires_in))
+	}
+}
+
+fn refresh_token_params(token_path: &str) -> Result<FormParams, TokenError> {
+	let client_id = std::env::var("GOOGLE_CLIENT_ID")
+		.map_err(|_| TokenError::ReauthorizationRequired("GOOGLE_CLIENT_ID is not set".into()))?;
+	let client_secret = std::env::var("GOOGLE_CLIENT_SECRET")
+		.map_err(|_| TokenError::ReauthorizationRequired("GOOGLE_CLIENT_SECRET is not set".into()))?;
+	let refresh_token = std::fs::read_to_string(token_path)
+		.ok()
+		.and_then(|data| serde_json::from_str::<Value>(&data).ok())
+		.and_then(|v| v["refresh_token"].as_str().map(|s| s.to_string()))
+		.ok_or_else(|| TokenError::ReauthorizationRequired(format!("No refresh token in {}", token_path)))?;
+
+	Ok(vec![
+		("client_id", client_id),
+		("client_secret", client_secret),
+		("refresh_token", refresh_token),
+		("grant_type", "refresh_token".to_string()),
+	])
+}
+
+// Builds a signed JWT bearer assertion (RFC 7523) from a service-account key file
+fn service_account_params(key_path: &str, subject: Option<&str>, access: DriveAccess) -> Result<(String, FormParams), TokenError> {
+	let key_json = std::fs::read_to_string(key_path)
+		.map_err(|e| TokenError::ReauthorizationRequired(format!("Cannot read service account key {}: {}", key_path, e)))?;
+	let key: ServiceAccountKey = serde_json::from_str(&key_json)
+		.map_err(|e| TokenError::ReauthorizationRequired(format!("Invalid service account key {}: {}", key_path, e)))?;
+
+	let token_uri = key.token_uri.unwrap_or_else(|| TOKEN_URI.to_string());
+	let iat = Utc::now().timestamp();
+	let claims = JwtClaims {
+		iss: &key.client_email,
+		scope: access.scope(),
+		aud: &token_uri,
+		iat,
+		exp: iat + 3600,
+		sub: subject,
+	};
+
+	let signing_key = EncodingKey::from_rsa_pem(key.private_key.as_bytes())
+		.map_err(|e| TokenError::ReauthorizationRequired(format!("Invalid service account private key: {}", e)))?;
+	let assertion = encode(&Header::new(Algorithm::RS256), &claims, &signing_key)
+		.map_err(|e| TokenError::ReauthorizationRequired(format!("Failed to sign JWT: {}", e)))?;
+
+	Ok((token_uri, vec![
+		("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer".to_string()),
+		("assertion", assertion),
+	]))
+}
@@ -0,0 +1,124 @@
+use serde::Serialize;
+use sqlx::SqlitePool;
+use uuid::Uuid;
+
+use crate::matching::glob_match;
+
+// "name matches `Essay 2*` and owner ends with `@school.edu` → add to folder X"
+#[derive(Debug, Clone, Serialize)]
+pub struct WatchRule {
+	pub id: i64,
+	pub user_id: i64,
+	pub name_pattern: Option<String>,
+	pub owner_pattern: Option<String>,
+	pub id_parent: Option<String>,
+	pub enabled: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RuleCandidate {
+	pub id: i64,
+	pub doc_id: String,
+	pub name: String,
+	pub owner_username: String,
+	pub owner_email: Option<String>,
+}
+
+impl WatchRule {
+	pub fn matches(&self, doc: &RuleCandidate) -> bool {
+		let name_ok = self.name_pattern.as_deref().is_none_or(|p| glob_match(p, &doc.name));
+		let owner_ok = self.owner_pattern.as_deref().is_none_or(|p| {
+			glob_match(p, &doc.owner_username) || doc.owner_email.as_deref().is_some_and(|email| glob_match(p, email))
+		});
+		name_ok && owner_ok
+	}
+}
+
+async fn all_candidates(pool: &SqlitePool) -> Result<Vec<RuleCandidate>, sqlx::Error> {
+	sqlx::query_as!(
+		RuleCandidate,
+		r#"SELECT id AS "id!", doc_id, name, owner_username, owner_email FROM documents"#
+	)
+	.fetch_all(pool)
+	.await
+}
+
+async fn add_to_folder(pool: &SqlitePool, rule: &WatchRule, document_id: i64) -> Result<u64, sqlx::Error> {
+	// Documents the user already watches stay where they are
+	let row_id = Uuid::new_v4().to_string();
+	let res = sqlx::query!(
+		"INSERT OR IGNORE INTO user_documents (id, user_id, document_id, id_parent) VALUES (?, ?, ?, ?)",
+		row_id,
+		rule.user_id,
+		document_id,
+		rule.id_parent
+	)
+	.execute(pool)
+	.await?;
+
+	Ok(res.rows_affected())
+}
+
+// Called by the poller for every newly inserted document
+pub async fn apply_rules_to_document(pool: &SqlitePool, document_id: i64) -> Result<(), sqlx::Error> {
+	let Some(doc) = sqlx::query_as!(
+		RuleCandidate,
+		r#"SELECT id AS "id!", doc_id, name, owner_username, owner_email FROM documents WHERE id = ?"#,
+		document_id
+	)
+	.fetch_optional(pool)
+	.await?
+	else {
+		return Ok(());
+	};
+
+	let rules = sqlx::query_as!(
+		WatchRule,
+		r#"SELECT id AS "id!", user_id, name_pattern, owner_pattern, id_parent, enabled FROM watch_rules WHERE enabled = TRUE ORDER BY id"#
+	)
+	.fetch_all(pool)
+	.await?;
+
+	// The first matching rule per user decides the folder
+	for rule in rules.iter().filter(|r| r.matches(&doc)) {
+		if add_to_folder(pool, rule, doc.id).await? > 0 {
+			println!("📂 Rule {} filed {} for user {}", rule.id, doc.name, rule.user_id);
+		}
+	}
+
+	Ok(())
+}
+
+#[derive(Debug, Serialize)]
+pub struct RulePreview {
+	pub documents: Vec<RuleCandidate>,
+	// Documents an email owner pattern can't be checked against yet, because their owner email isn't known
+	pub missing_owner_email: usize,
+}
+
+// Documents a rule would match right now, without changing anything
+pub async fn preview_rule(pool: &SqlitePool, rule: &WatchRule) -> Result<RulePreview, sqlx::Error> {
+	let (documents, others): (Vec<_>, Vec<_>) = all_candidates(pool).await?.into_iter().partition(|doc| rule.matches(doc));
+
+	let email_pattern = rule.owner_pattern.as_deref().is_some_and(|p| p.contains('@'));
+	let missing_owner_email = if email_pattern {
+		others
+			.iter()
+			.filter(|doc| doc.owner_email.is_none())
+			.filter(|doc| rule.name_pattern.as_deref().is_none_or(|p| glob_match(p, &doc.name)))
+			.count()
+	} else {
+		0
+	};
+
+	Ok(RulePreview { documents, missing_owner_email })
+}
+
+// Applies a rule to every document already known, returning how many were added
+pub async fn apply_rule_retroactively(pool: &SqlitePool, rule: &WatchRule) -> Result<u64, sqlx::Error> {
+	let mut added = 0;
+	for doc in preview_rule(pool, rule).await?.documents {
+		added += add_to_folder(pool, rule, doc.id).await?;
+	}
+	Ok(added)
+}